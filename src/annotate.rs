@@ -0,0 +1,289 @@
+//! Annotated screenshots with outlined, numbered regions and a legend (feature
+//! `image`).
+//!
+//! Used by [`VisionClient::annotate_image`](crate::VisionClient::annotate_image)
+//! to turn the boxes from [`grounding`](crate::grounding) into evidence that
+//! visual regression reports and bug tickets can embed. Labels are drawn with
+//! an 8x8 bitmap font; characters it lacks are drawn as `?`.
+
+use std::io::Cursor;
+
+use font8x8::{UnicodeFonts, BASIC_FONTS, LATIN_FONTS};
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+
+use crate::error::{Result, VisionError};
+use crate::grounding::{BoundingBox, GroundedBox, LocatedElement, PixelBox};
+
+/// Size of a glyph of the bitmap font, in font pixels.
+const GLYPH: u32 = 8;
+
+/// Space around the legend and number tags, in font pixels.
+const PADDING: u32 = 4;
+
+/// Region colors, cycled in order; all readable under white text.
+const PALETTE: [[u8; 3]; 8] = [
+    [230, 25, 75],
+    [0, 130, 200],
+    [60, 180, 75],
+    [245, 130, 48],
+    [145, 30, 180],
+    [0, 128, 128],
+    [240, 50, 230],
+    [170, 110, 40],
+];
+
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// A labelled region to outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// Text shown in the legend next to the region's number.
+    pub label: String,
+    /// The region, in normalized coordinates.
+    pub bbox: BoundingBox,
+}
+
+impl Annotation {
+    /// Create an annotation for `bbox`.
+    pub fn new(label: impl Into<String>, bbox: BoundingBox) -> Self {
+        Self {
+            label: label.into(),
+            bbox,
+        }
+    }
+}
+
+impl From<LocatedElement> for Annotation {
+    fn from(element: LocatedElement) -> Self {
+        Self::new(element.name, element.bbox)
+    }
+}
+
+impl From<GroundedBox> for Annotation {
+    fn from(found: GroundedBox) -> Self {
+        Self::new(found.label.unwrap_or_default(), found.bbox)
+    }
+}
+
+/// Outline and number each annotation on a copy of `image`, with a legend
+/// listing the labels added below it.
+///
+/// Regions are numbered from 1 in the order given.
+pub fn annotate(image: &DynamicImage, annotations: &[Annotation]) -> RgbaImage {
+    let (width, height) = (image.width(), image.height());
+    let scale = (width.min(height) / 400).clamp(1, 4);
+    let thickness = (width.min(height) / 400).clamp(2, 6);
+
+    // The legend shrinks its text on narrow images so labels stay readable.
+    let legend_scale = if width >= legend_width(24, scale) {
+        scale
+    } else {
+        1
+    };
+    let line_height = (GLYPH + PADDING) * legend_scale;
+    let legend_height = match annotations.len() {
+        0 => 0,
+        n => n as u32 * line_height + PADDING * legend_scale,
+    };
+
+    let mut canvas = RgbaImage::from_pixel(width, height + legend_height, WHITE);
+    image::imageops::replace(&mut canvas, &image.to_rgba8(), 0, 0);
+
+    for (i, annotation) in annotations.iter().enumerate() {
+        let color = color(i);
+        let region = annotation.bbox.to_pixels(width, height);
+        draw_outline(&mut canvas, region, thickness, color);
+        draw_tag(
+            &mut canvas,
+            &(i + 1).to_string(),
+            region,
+            height,
+            scale,
+            color,
+        );
+    }
+
+    let max_chars = width.saturating_sub(legend_width(0, legend_scale)) / (GLYPH * legend_scale);
+    for (i, annotation) in annotations.iter().enumerate() {
+        let y = height + i as u32 * line_height + PADDING * legend_scale;
+        let x = PADDING * legend_scale;
+        let swatch = GLYPH * legend_scale;
+        fill_rect(&mut canvas, x, y, swatch, swatch, color(i));
+        let text = format!("{}. {}", i + 1, annotation.label);
+        draw_text(
+            &mut canvas,
+            x + swatch + PADDING * legend_scale,
+            y,
+            &truncate(&text, max_chars as usize),
+            legend_scale,
+            BLACK,
+        );
+    }
+    canvas
+}
+
+/// Decode an encoded image, annotate it with [`annotate`] and encode the result
+/// as PNG.
+pub fn annotate_png(data: &[u8], annotations: &[Annotation]) -> Result<Vec<u8>> {
+    let image = crate::preprocess::decode_image(data)?;
+    let annotated = annotate(&image, annotations);
+    let mut png = Vec::new();
+    annotated
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to encode PNG: {}", e)))?;
+    Ok(png)
+}
+
+fn color(index: usize) -> Rgba<u8> {
+    let [r, g, b] = PALETTE[index % PALETTE.len()];
+    Rgba([r, g, b, 255])
+}
+
+/// Width taken by the legend's padding and swatch plus `chars` characters.
+fn legend_width(chars: u32, scale: u32) -> u32 {
+    (PADDING * 3 + GLYPH * (chars + 1)) * scale
+}
+
+/// Shorten `text` to at most `max_chars` characters, ending in "..." if cut.
+fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(max_chars.saturating_sub(3)).collect();
+    format!("{}...", kept)
+}
+
+/// Fill a rectangle, clipped to the canvas.
+fn fill_rect(canvas: &mut RgbaImage, x: u32, y: u32, width: u32, height: u32, color: Rgba<u8>) {
+    let x_end = x.saturating_add(width).min(canvas.width());
+    let y_end = y.saturating_add(height).min(canvas.height());
+    for py in y..y_end {
+        for px in x..x_end {
+            canvas.put_pixel(px, py, color);
+        }
+    }
+}
+
+/// Outline `region` with lines `thickness` pixels wide, drawn inside it.
+fn draw_outline(canvas: &mut RgbaImage, region: PixelBox, thickness: u32, color: Rgba<u8>) {
+    let PixelBox {
+        x,
+        y,
+        width,
+        height,
+    } = region;
+    let t = thickness.min(width).min(height);
+    fill_rect(canvas, x, y, width, t, color);
+    fill_rect(canvas, x, (y + height).saturating_sub(t), width, t, color);
+    fill_rect(canvas, x, y, t, height, color);
+    fill_rect(canvas, (x + width).saturating_sub(t), y, t, height, color);
+}
+
+/// Draw `number` in a filled tag above the region's top left corner, or just
+/// inside it when there is no room above.
+fn draw_tag(
+    canvas: &mut RgbaImage,
+    number: &str,
+    region: PixelBox,
+    image_height: u32,
+    scale: u32,
+    color: Rgba<u8>,
+) {
+    let tag_width = (number.len() as u32 * GLYPH + PADDING) * scale;
+    let tag_height = (GLYPH + PADDING) * scale;
+    let x = region.x.min(canvas.width().saturating_sub(tag_width));
+    let y = if region.y >= tag_height {
+        region.y - tag_height
+    } else {
+        region.y.min(image_height.saturating_sub(tag_height))
+    };
+    fill_rect(canvas, x, y, tag_width, tag_height, color);
+    let inset = PADDING / 2 * scale;
+    draw_text(canvas, x + inset, y + inset, number, scale, WHITE);
+}
+
+/// Draw `text` with its top left corner at (`x`, `y`), each font pixel
+/// `scale` pixels wide.
+fn draw_text(canvas: &mut RgbaImage, x: u32, y: u32, text: &str, scale: u32, color: Rgba<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph = BASIC_FONTS
+            .get(c)
+            .or_else(|| LATIN_FONTS.get(c))
+            .or_else(|| BASIC_FONTS.get('?'))
+            .unwrap_or_default();
+        let left = x + i as u32 * GLYPH * scale;
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..GLYPH {
+                if bits & (1 << col) != 0 {
+                    let px = left + col * scale;
+                    let py = y + row as u32 * scale;
+                    fill_rect(canvas, px, py, scale, scale, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blank(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(width, height, WHITE))
+    }
+
+    #[test]
+    fn test_annotate_outlines_and_numbers_regions() {
+        let annotations = [
+            Annotation::new(
+                "Submit button",
+                BoundingBox::new(100.0, 500.0, 500.0, 900.0),
+            ),
+            Annotation::new("Logo", BoundingBox::new(0.0, 0.0, 200.0, 200.0)),
+        ];
+        let out = annotate(&blank(400, 200), &annotations);
+        assert_eq!(out.width(), 400);
+        assert_eq!(out.height(), 200 + 2 * (GLYPH + PADDING) + PADDING);
+
+        // Outline of the first region (pixels 40..200 x 100..180).
+        assert_eq!(*out.get_pixel(40, 150), color(0));
+        assert_eq!(*out.get_pixel(199, 150), color(0));
+        assert_eq!(*out.get_pixel(120, 140), WHITE);
+        // The second region's tag sits inside it, at the top edge.
+        assert_eq!(*out.get_pixel(0, 0), color(1));
+        // Legend swatches below the image.
+        assert_eq!(*out.get_pixel(PADDING, 200 + PADDING), color(0));
+        assert_eq!(
+            *out.get_pixel(PADDING, 200 + PADDING + GLYPH + PADDING),
+            color(1)
+        );
+    }
+
+    #[test]
+    fn test_annotate_without_annotations_keeps_size() {
+        let out = annotate(&blank(50, 40), &[]);
+        assert_eq!(out.dimensions(), (50, 40));
+    }
+
+    #[test]
+    fn test_annotate_png_roundtrip() {
+        let mut data = Vec::new();
+        blank(64, 64)
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        let annotations = [Annotation::new(
+            "é ✓",
+            BoundingBox::new(0.0, 0.0, 1000.0, 1000.0),
+        )];
+        let png = annotate_png(&data, &annotations).unwrap();
+        let (w, h) = crate::preprocess::image_dimensions(&png).unwrap();
+        assert_eq!((w, h), (64, 64 + GLYPH + PADDING + PADDING));
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("a much longer label", 10), "a much ...");
+    }
+}
@@ -0,0 +1,114 @@
+//! Ready-made [`HttpClient`](crate::HttpClient) implementations.
+//!
+//! Each backend sits behind the cargo feature of the same name and is built from
+//! a [`VisionConfig`], so it uses the configured timeout, proxy and TLS settings:
+//!
+//! | Feature | Client | Notes |
+//! |---------|--------|-------|
+//! | `reqwest` | [`ReqwestHttpClient`] | async, true streaming |
+//! | `hyper` | [`HyperHttpClient`] | async, HTTP/1.1 over rustls (ring), true streaming |
+//! | `ureq` | [`UreqHttpClient`] | blocking I/O on tokio's blocking pool |
+//!
+//! With any backend enabled, [`VisionClient::from_config`](crate::VisionClient::from_config)
+//! builds a client using [`DefaultHttpClient`] (`reqwest`, else `hyper`, else `ureq`).
+
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+use std::pin::Pin;
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+use std::task::{Context, Poll};
+
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+use futures_core::Stream;
+
+use crate::config::VisionConfig;
+use crate::error::{Result, VisionError};
+
+#[cfg(feature = "hyper")]
+mod hyper;
+#[cfg(feature = "reqwest")]
+mod reqwest;
+#[cfg(feature = "ureq")]
+mod ureq;
+
+#[cfg(feature = "hyper")]
+pub use self::hyper::HyperHttpClient;
+#[cfg(feature = "reqwest")]
+pub use self::reqwest::ReqwestHttpClient;
+#[cfg(feature = "ureq")]
+pub use self::ureq::UreqHttpClient;
+
+/// The backend used by [`VisionClient::from_config`](crate::VisionClient::from_config).
+#[cfg(feature = "reqwest")]
+pub type DefaultHttpClient = ReqwestHttpClient;
+
+/// The backend used by [`VisionClient::from_config`](crate::VisionClient::from_config).
+#[cfg(all(feature = "hyper", not(feature = "reqwest")))]
+pub type DefaultHttpClient = HyperHttpClient;
+
+/// The backend used by [`VisionClient::from_config`](crate::VisionClient::from_config).
+#[cfg(all(feature = "ureq", not(any(feature = "reqwest", feature = "hyper"))))]
+pub type DefaultHttpClient = UreqHttpClient;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Read the PEM file named by `root_ca_path`, if set.
+fn read_root_ca(config: &VisionConfig) -> Result<Option<Vec<u8>>> {
+    match &config.root_ca_path {
+        Some(path) => std::fs::read(path)
+            .map(Some)
+            .map_err(|e| VisionError::Config(format!("cannot read root CA file {}: {}", path, e))),
+        None => Ok(None),
+    }
+}
+
+/// Adapts a backend's chunk stream to [`BodyStream`](crate::http::BodyStream) items.
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+struct MapBody<S>(Pin<Box<S>>);
+
+#[cfg(any(feature = "reqwest", feature = "hyper"))]
+impl<S, B, E> Stream for MapBody<S>
+where
+    S: Stream<Item = std::result::Result<B, E>>,
+    B: Into<Vec<u8>>,
+    E: Into<BoxError>,
+{
+    type Item = std::result::Result<Vec<u8>, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0
+            .as_mut()
+            .poll_next(cx)
+            .map(|item| item.map(|r| r.map(Into::into).map_err(Into::into)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_root_ca() {
+        assert!(read_root_ca(&VisionConfig::new("k")).unwrap().is_none());
+
+        let missing = VisionConfig::new("k").with_root_ca("/nonexistent/ca.pem");
+        assert!(matches!(
+            read_root_ca(&missing),
+            Err(VisionError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_default_client_from_config() {
+        let config = VisionConfig::new("k")
+            .with_base_url("https://api.test/v4/")
+            .with_proxy("http://127.0.0.1:3128");
+        let client = crate::VisionClient::from_config(config).unwrap();
+        assert_eq!(
+            client.config().proxy.as_deref(),
+            Some("http://127.0.0.1:3128")
+        );
+
+        let bad_ca = VisionConfig::new("k").with_root_ca("/nonexistent/ca.pem");
+        assert!(crate::VisionClient::from_config(bad_ca).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use super::{read_root_ca, BoxError, MapBody};
+use crate::config::VisionConfig;
+use crate::error::{Result, VisionError};
+use crate::http::{HttpClient, HttpResponse, HttpStreamResponse};
+
+/// [`HttpClient`] backed by [`reqwest::Client`] (feature `reqwest`).
+///
+/// Extra root certificates from `root_ca_path` are trusted in addition to the
+/// platform roots. Without an explicit `proxy`, reqwest's system proxy
+/// detection (`HTTPS_PROXY` etc.) applies.
+#[derive(Debug, Clone)]
+pub struct ReqwestHttpClient {
+    client: ::reqwest::Client,
+}
+
+impl ReqwestHttpClient {
+    /// Build a client with the timeout, proxy and TLS settings from `config`.
+    pub fn from_config(config: &VisionConfig) -> Result<Self> {
+        let mut builder = ::reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .tls_danger_accept_invalid_certs(config.danger_accept_invalid_certs);
+        if let Some(proxy) = &config.proxy {
+            let proxy = ::reqwest::Proxy::all(proxy)
+                .map_err(|e| VisionError::Config(format!("invalid proxy URL {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(pem) = read_root_ca(config)? {
+            let certs = ::reqwest::Certificate::from_pem_bundle(&pem)
+                .map_err(|e| VisionError::Config(format!("invalid root CA file: {}", e)))?;
+            builder = builder.tls_certs_merge(certs);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| VisionError::Config(format!("failed to build HTTP client: {}", e)))?;
+        Ok(Self { client })
+    }
+
+    /// Wrap an already configured [`reqwest::Client`].
+    pub fn from_client(client: ::reqwest::Client) -> Self {
+        Self { client }
+    }
+
+    fn request(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: impl Into<::reqwest::Body>,
+    ) -> ::reqwest::RequestBuilder {
+        let mut req = self.client.post(url);
+        for &(k, v) in headers {
+            req = req.header(k, v);
+        }
+        req.body(body)
+    }
+}
+
+impl HttpClient for ReqwestHttpClient {
+    async fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> std::result::Result<HttpResponse, BoxError> {
+        self.post_owned(url, headers, body.to_vec()).await
+    }
+
+    async fn post_owned(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> std::result::Result<HttpResponse, BoxError> {
+        let resp = self
+            .request(url, headers, body)
+            .send()
+            .await
+            .map_err(map_error)?;
+        Ok(HttpResponse {
+            status: resp.status().as_u16(),
+            body: resp.text().await.map_err(map_error)?,
+        })
+    }
+
+    async fn post_stream(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> std::result::Result<HttpStreamResponse, BoxError> {
+        let resp = self
+            .request(url, headers, body.to_vec())
+            .send()
+            .await
+            .map_err(map_error)?;
+        Ok(HttpStreamResponse {
+            status: resp.status().as_u16(),
+            body: Box::pin(MapBody(Box::pin(resp.bytes_stream()))),
+        })
+    }
+}
+
+fn map_error(err: ::reqwest::Error) -> BoxError {
+    if err.is_timeout() {
+        Box::new(VisionError::Timeout)
+    } else {
+        Box::new(err)
+    }
+}
@@ -0,0 +1,167 @@
+use std::io::Read;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use super::{read_root_ca, BoxError};
+use crate::config::VisionConfig;
+use crate::error::{Result, VisionError};
+use crate::http::{HttpClient, HttpResponse, HttpStreamResponse};
+
+/// Size of the chunks read from a streaming response body.
+const STREAM_CHUNK_SIZE: usize = 8 * 1024;
+
+/// [`HttpClient`] backed by a blocking [`ureq::Agent`] (feature `ureq`).
+///
+/// Requests run on tokio's blocking thread pool, so a tokio runtime is required.
+/// Note that `root_ca_path` *replaces* ureq's bundled Mozilla roots rather than
+/// extending them. Without an explicit `proxy`, ureq reads `HTTPS_PROXY` etc.
+#[derive(Debug, Clone)]
+pub struct UreqHttpClient {
+    agent: ::ureq::Agent,
+}
+
+impl UreqHttpClient {
+    /// Build an agent with the timeout, proxy and TLS settings from `config`.
+    pub fn from_config(config: &VisionConfig) -> Result<Self> {
+        let mut tls = ::ureq::tls::TlsConfig::builder()
+            .disable_verification(config.danger_accept_invalid_certs);
+        if let Some(pem) = read_root_ca(config)? {
+            let certs = ::ureq::tls::parse_pem(&pem)
+                .filter_map(|item| match item {
+                    Ok(::ureq::tls::PemItem::Certificate(cert)) => Some(Ok(cert)),
+                    Ok(_) => None,
+                    Err(e) => Some(Err(e)),
+                })
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|e| VisionError::Config(format!("invalid root CA file: {}", e)))?;
+            if certs.is_empty() {
+                return Err(VisionError::Config(
+                    "root CA file contains no certificates".to_string(),
+                ));
+            }
+            tls = tls.root_certs(::ureq::tls::RootCerts::new_with_certs(&certs));
+        }
+
+        let mut builder = ::ureq::Agent::config_builder()
+            .timeout_global(Some(Duration::from_secs(config.timeout_secs)))
+            .http_status_as_error(false)
+            .tls_config(tls.build());
+        if let Some(proxy) = &config.proxy {
+            let proxy = ::ureq::Proxy::new(proxy)
+                .map_err(|e| VisionError::Config(format!("invalid proxy URL {}: {}", proxy, e)))?;
+            builder = builder.proxy(Some(proxy));
+        }
+        Ok(Self {
+            agent: builder.build().into(),
+        })
+    }
+
+    /// Wrap an already configured [`ureq::Agent`].
+    ///
+    /// The agent must be configured with `http_status_as_error(false)` so error
+    /// responses reach [`VisionError::from_response`].
+    pub fn from_agent(agent: ::ureq::Agent) -> Self {
+        Self { agent }
+    }
+
+    /// Send the request on the blocking pool and return the response head.
+    async fn send(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> std::result::Result<::ureq::http::Response<::ureq::Body>, BoxError> {
+        let agent = self.agent.clone();
+        let url = url.to_string();
+        let headers: Vec<(String, String)> = headers
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        tokio::task::spawn_blocking(move || {
+            let mut req = agent.post(&url);
+            for (k, v) in &headers {
+                req = req.header(k, v);
+            }
+            req.send(body).map_err(map_error)
+        })
+        .await?
+    }
+}
+
+impl HttpClient for UreqHttpClient {
+    async fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> std::result::Result<HttpResponse, BoxError> {
+        self.post_owned(url, headers, body.to_vec()).await
+    }
+
+    async fn post_owned(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> std::result::Result<HttpResponse, BoxError> {
+        let mut resp = self.send(url, headers, body).await?;
+        let status = resp.status().as_u16();
+        let body = tokio::task::spawn_blocking(move || {
+            resp.body_mut().read_to_string().map_err(map_error)
+        })
+        .await??;
+        Ok(HttpResponse { status, body })
+    }
+
+    async fn post_stream(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> std::result::Result<HttpStreamResponse, BoxError> {
+        let resp = self.send(url, headers, body.to_vec()).await?;
+        let status = resp.status().as_u16();
+        let mut reader = resp.into_body().into_reader();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::task::spawn_blocking(move || {
+            let mut buf = vec![0; STREAM_CHUNK_SIZE];
+            loop {
+                let chunk = match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => Ok(buf[..n].to_vec()),
+                    Err(e) => Err(BoxError::from(e)),
+                };
+                let failed = chunk.is_err();
+                if tx.blocking_send(chunk).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(HttpStreamResponse {
+            status,
+            body: Box::pin(ChannelBody(rx)),
+        })
+    }
+}
+
+/// Body stream fed by the blocking reader task.
+struct ChannelBody(mpsc::Receiver<std::result::Result<Vec<u8>, BoxError>>);
+
+impl Stream for ChannelBody {
+    type Item = std::result::Result<Vec<u8>, BoxError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+fn map_error(err: ::ureq::Error) -> BoxError {
+    match err {
+        ::ureq::Error::Timeout(_) => Box::new(VisionError::Timeout),
+        err => Box::new(err),
+    }
+}
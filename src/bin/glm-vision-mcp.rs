@@ -0,0 +1,38 @@
+//! MCP server exposing the glm-vision-rs tools over stdio.
+//!
+//! Configure it with the environment variables documented on
+//! `VisionConfig::from_env`, e.g. in an MCP client config:
+//!
+//! ```json
+//! {
+//!   "mcpServers": {
+//!     "glm-vision": {
+//!       "command": "glm-vision-mcp",
+//!       "env": { "GLM_VISION_API_KEY": "...", "GLM_VISION_PROVIDER": "zai-coding" }
+//!     }
+//!   }
+//! }
+//! ```
+
+use std::sync::Arc;
+
+use glm_vision_rs::{VisionClient, VisionConfig};
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = Arc::new(VisionClient::from_config(VisionConfig::from_env()?)?);
+
+    // stdout carries the protocol; diagnostics go to stderr.
+    eprintln!(
+        "{} {} MCP server listening on stdio",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION")
+    );
+    glm_vision_rs::mcp::serve(
+        client,
+        tokio::io::BufReader::new(tokio::io::stdin()),
+        tokio::io::stdout(),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,1014 @@
+//! Command-line interface for the glm-vision-rs tools.
+//!
+//! ```sh
+//! glm-vision --provider zai-coding extract-text screenshots/*.png --language rust
+//! glm-vision ui-diff-check expected.png actual.png --json > diff.json
+//! find shots -name '*.png' | glm-vision diagnose-error - --out-dir reports/
+//! glm-vision regression --expected-dir baseline --actual-dir shots --html report.html --junit junit.xml
+//! ```
+
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "video")]
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use glm_vision_rs::backends::ReqwestHttpClient;
+use glm_vision_rs::diff::Severity;
+use glm_vision_rs::regression::{self, CaseOutcome, RegressionSuite};
+#[cfg(feature = "video")]
+use glm_vision_rs::video::VideoSampling;
+use glm_vision_rs::{
+    tools, HttpClient, HttpResponse, Provider, TileMode, VisionClient, VisionConfig,
+};
+
+/// HTTP client that records every successful response body, so `--json` can
+/// print the raw API responses.
+#[derive(Clone)]
+struct CapturingClient {
+    inner: ReqwestHttpClient,
+    bodies: Arc<Mutex<Vec<String>>>,
+}
+
+impl CapturingClient {
+    fn record(&self, response: &HttpResponse) {
+        if response.is_success() {
+            self.bodies.lock().unwrap().push(response.body.clone());
+        }
+    }
+}
+
+impl HttpClient for CapturingClient {
+    async fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let response = self.inner.post(url, headers, body).await?;
+        self.record(&response);
+        Ok(response)
+    }
+
+    async fn post_owned(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let response = self.inner.post_owned(url, headers, body).await?;
+        self.record(&response);
+        Ok(response)
+    }
+}
+
+/// Prints the library's warnings, such as media type mismatches, to stderr.
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn && metadata.target().starts_with("glm_vision_rs")
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("glm-vision: warning: {}", record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[derive(Parser)]
+#[command(
+    name = "glm-vision",
+    version,
+    about = "Analyze images and videos with GLM-4.6V",
+    after_help = "Config flags fall back to the GLM_VISION_* environment variables \
+                  read by VisionConfig::from_env."
+)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    #[command(flatten)]
+    output: OutputArgs,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args, Default)]
+struct ConfigArgs {
+    /// API key
+    #[arg(long, global = true)]
+    api_key: Option<String>,
+
+    /// API provider (sets the base URL)
+    #[arg(long, value_enum, global = true)]
+    provider: Option<ProviderArg>,
+
+    /// Custom API base URL (overrides --provider)
+    #[arg(long, global = true)]
+    base_url: Option<String>,
+
+    /// Vision model name
+    #[arg(long, global = true)]
+    model: Option<String>,
+
+    /// Sampling temperature
+    #[arg(long, global = true)]
+    temperature: Option<f32>,
+
+    /// Top-p sampling
+    #[arg(long, global = true)]
+    top_p: Option<f32>,
+
+    /// Maximum tokens in the response
+    #[arg(long, global = true)]
+    max_tokens: Option<u32>,
+
+    /// Enable or disable thinking/reasoning mode
+    #[arg(long, global = true)]
+    thinking: Option<bool>,
+
+    /// Request timeout in seconds
+    #[arg(long, global = true)]
+    timeout_secs: Option<u64>,
+
+    /// Maximum image file size in MB
+    #[arg(long, global = true)]
+    max_image_size_mb: Option<u64>,
+
+    /// Maximum video file size in MB
+    #[arg(long, global = true)]
+    max_video_size_mb: Option<u64>,
+
+    /// Downscale local images so neither side exceeds this many pixels, and
+    /// recompress images over the size limit instead of rejecting them
+    #[arg(long, global = true)]
+    max_image_edge: Option<u32>,
+
+    /// Split large screenshots into overlapping tiles for extract-text and
+    /// ui-diff-check, sent together or one request per tile
+    #[arg(long, value_enum, global = true)]
+    tiling: Option<TilingArg>,
+
+    /// Proxy URL for API requests
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+
+    /// PEM file with extra trusted root certificates
+    #[arg(long, global = true)]
+    root_ca: Option<String>,
+}
+
+#[derive(Args)]
+struct OutputArgs {
+    /// Print the raw API response JSON instead of the answer text (an array
+    /// if the tool sent several requests; one JSON line per input if there
+    /// are several and no --out-dir)
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Write one result file per input into this directory
+    #[arg(long, global = true)]
+    out_dir: Option<PathBuf>,
+
+    /// Number of inputs to process concurrently
+    #[arg(short, long, default_value_t = 1, global = true)]
+    jobs: usize,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ProviderArg {
+    Zhipu,
+    Zai,
+    ZaiCoding,
+}
+
+impl From<ProviderArg> for Provider {
+    fn from(p: ProviderArg) -> Self {
+        match p {
+            ProviderArg::Zhipu => Provider::Zhipu,
+            ProviderArg::Zai => Provider::Zai,
+            ProviderArg::ZaiCoding => Provider::ZaiCoding,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TilingArg {
+    MultiImage,
+    Sequential,
+}
+
+impl From<TilingArg> for TileMode {
+    fn from(t: TilingArg) -> Self {
+        match t {
+            TilingArg::MultiImage => TileMode::MultiImage,
+            TilingArg::Sequential => TileMode::Sequential,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SeverityArg {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl From<SeverityArg> for Severity {
+    fn from(s: SeverityArg) -> Self {
+        match s {
+            SeverityArg::Low => Severity::Low,
+            SeverityArg::Medium => Severity::Medium,
+            SeverityArg::High => Severity::High,
+            SeverityArg::Critical => Severity::Critical,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputType {
+    Code,
+    Prompt,
+    Spec,
+    Description,
+}
+
+impl OutputType {
+    fn as_str(self) -> &'static str {
+        match self {
+            OutputType::Code => "code",
+            OutputType::Prompt => "prompt",
+            OutputType::Spec => "spec",
+            OutputType::Description => "description",
+        }
+    }
+}
+
+/// Inputs are file paths, HTTP(S) URLs, glob patterns, or `-` to read
+/// newline-separated sources from stdin.
+#[derive(Clone, Subcommand)]
+enum Command {
+    /// General-purpose image analysis
+    AnalyzeImage {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long, default_value = "Describe this image in detail.")]
+        prompt: String,
+    },
+    /// Extract text, code or logs from screenshots
+    ExtractText {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(
+            short,
+            long,
+            default_value = "Extract all text from this screenshot, preserving its formatting."
+        )]
+        prompt: String,
+        /// Programming language of code in the screenshot
+        #[arg(long)]
+        language: Option<String>,
+    },
+    /// Diagnose errors shown in screenshots
+    DiagnoseError {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long, default_value = "What is this error and how do I fix it?")]
+        prompt: String,
+        /// What was being done when the error occurred
+        #[arg(long)]
+        context: Option<String>,
+    },
+    /// Explain technical diagrams
+    UnderstandDiagram {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long, default_value = "Explain this diagram in detail.")]
+        prompt: String,
+        /// Diagram type hint (e.g. "UML class", "sequence", "ER")
+        #[arg(long)]
+        diagram_type: Option<String>,
+    },
+    /// Analyze charts, graphs and dashboards
+    AnalyzeDataViz {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(
+            short,
+            long,
+            default_value = "What insights does this visualization show?"
+        )]
+        prompt: String,
+        /// Analysis focus (e.g. "trends", "anomalies", "comparison")
+        #[arg(long)]
+        focus: Option<String>,
+    },
+    /// Compare an expected and an actual UI screenshot
+    UiDiffCheck {
+        expected: String,
+        actual: String,
+        #[arg(
+            short,
+            long,
+            default_value = "List all visual differences between these two screenshots."
+        )]
+        prompt: String,
+    },
+    /// Compare many expected/actual screenshot pairs, --jobs at a time, and
+    /// write HTML and JUnit reports
+    Regression {
+        /// JSON manifest listing the cases
+        #[arg(
+            long,
+            required_unless_present = "expected_dir",
+            conflicts_with = "expected_dir"
+        )]
+        manifest: Option<PathBuf>,
+        /// Directory of expected screenshots, paired with --actual-dir by file name
+        #[arg(long, requires = "actual_dir")]
+        expected_dir: Option<PathBuf>,
+        /// Directory of actual screenshots
+        #[arg(long, requires = "expected_dir")]
+        actual_dir: Option<PathBuf>,
+        #[arg(short, long, default_value = regression::DEFAULT_PROMPT)]
+        prompt: String,
+        /// Fail cases with a difference of this severity or worse
+        #[arg(long, value_enum, default_value = "high")]
+        fail_on: SeverityArg,
+        /// JSON file caching the reports of unchanged screenshot pairs
+        #[arg(long)]
+        cache: Option<PathBuf>,
+        /// Write an HTML report with side-by-side screenshots to this file
+        #[arg(long)]
+        html: Option<PathBuf>,
+        /// Write a JUnit XML report to this file
+        #[arg(long)]
+        junit: Option<PathBuf>,
+    },
+    /// Convert UI screenshots into code, prompts, specs or descriptions
+    UiToArtifact {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long, default_value = "Recreate this interface.")]
+        prompt: String,
+        #[arg(long, value_enum, default_value = "code")]
+        output_type: OutputType,
+    },
+    /// Analyze videos
+    AnalyzeVideo {
+        #[arg(required = true)]
+        inputs: Vec<String>,
+        #[arg(short, long, default_value = "Describe what happens in this video.")]
+        prompt: String,
+        /// Send a frame every SECS seconds instead of the whole video
+        #[cfg(feature = "video")]
+        #[arg(long, value_name = "SECS", value_parser = parse_seconds, conflicts_with = "scene_change")]
+        sample_every: Option<Duration>,
+        /// Send a frame at each scene change above THRESHOLD (0.0-1.0) instead
+        /// of the whole video
+        #[cfg(feature = "video")]
+        #[arg(long, value_name = "THRESHOLD")]
+        scene_change: Option<f64>,
+        /// Maximum number of frames to send when sampling
+        #[cfg(feature = "video")]
+        #[arg(long, default_value_t = 16)]
+        max_frames: usize,
+    },
+}
+
+impl Command {
+    /// Tool name, as used in `tools` and in output file names.
+    fn tool_name(&self) -> &'static str {
+        match self {
+            Command::AnalyzeImage { .. } => "analyze_image",
+            Command::ExtractText { .. } => "extract_text",
+            Command::DiagnoseError { .. } => "diagnose_error",
+            Command::UnderstandDiagram { .. } => "understand_diagram",
+            Command::AnalyzeDataViz { .. } => "analyze_data_viz",
+            Command::UiDiffCheck { .. } => "ui_diff_check",
+            Command::Regression { .. } => "regression",
+            Command::UiToArtifact { .. } => "ui_to_artifact",
+            Command::AnalyzeVideo { .. } => "analyze_video",
+        }
+    }
+
+    fn inputs(&self) -> Option<&[String]> {
+        match self {
+            Command::AnalyzeImage { inputs, .. }
+            | Command::ExtractText { inputs, .. }
+            | Command::DiagnoseError { inputs, .. }
+            | Command::UnderstandDiagram { inputs, .. }
+            | Command::AnalyzeDataViz { inputs, .. }
+            | Command::UiToArtifact { inputs, .. }
+            | Command::AnalyzeVideo { inputs, .. } => Some(inputs),
+            Command::UiDiffCheck { .. } | Command::Regression { .. } => None,
+        }
+    }
+
+    /// Run the tool on a single source.
+    async fn run(
+        &self,
+        client: &VisionClient<CapturingClient>,
+        source: &str,
+    ) -> glm_vision_rs::error::Result<String> {
+        match self {
+            Command::AnalyzeImage { prompt, .. } => {
+                tools::analyze_image(client, source, prompt).await
+            }
+            Command::ExtractText {
+                prompt, language, ..
+            } => tools::extract_text(client, source, prompt, language.as_deref()).await,
+            Command::DiagnoseError {
+                prompt, context, ..
+            } => tools::diagnose_error(client, source, prompt, context.as_deref()).await,
+            Command::UnderstandDiagram {
+                prompt,
+                diagram_type,
+                ..
+            } => tools::understand_diagram(client, source, prompt, diagram_type.as_deref()).await,
+            Command::AnalyzeDataViz { prompt, focus, .. } => {
+                tools::analyze_data_viz(client, source, prompt, focus.as_deref()).await
+            }
+            Command::UiDiffCheck {
+                expected,
+                actual,
+                prompt,
+            } => tools::ui_diff_check(client, expected, actual, prompt).await,
+            Command::Regression { .. } => unreachable!("regression runs through run_regression"),
+            Command::UiToArtifact {
+                prompt,
+                output_type,
+                ..
+            } => tools::ui_to_artifact(client, source, Some(output_type.as_str()), prompt).await,
+            Command::AnalyzeVideo { prompt, .. } => {
+                #[cfg(feature = "video")]
+                if let Some(sampling) = self.video_sampling() {
+                    return tools::analyze_video_frames(client, source, prompt, &sampling).await;
+                }
+                tools::analyze_video(client, source, prompt).await
+            }
+        }
+    }
+}
+
+#[cfg(feature = "video")]
+impl Command {
+    /// Frame sampling requested for `analyze-video`, if any.
+    fn video_sampling(&self) -> Option<VideoSampling> {
+        let Command::AnalyzeVideo {
+            sample_every,
+            scene_change,
+            max_frames,
+            ..
+        } = self
+        else {
+            return None;
+        };
+        let sampling = match (sample_every, scene_change) {
+            (Some(interval), _) => VideoSampling::every(*interval),
+            (None, Some(threshold)) => VideoSampling::scene_changes(*threshold),
+            (None, None) => return None,
+        };
+        Some(sampling.with_max_frames(*max_frames))
+    }
+}
+
+#[cfg(feature = "video")]
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|e| format!("{}", e))?;
+    match Duration::try_from_secs_f64(secs) {
+        Ok(d) if !d.is_zero() => Ok(d),
+        _ => Err("must be a positive number of seconds".to_string()),
+    }
+}
+
+/// Build the client config from `GLM_VISION_*` variables (read through `env`,
+/// as in [`VisionConfig::from_vars`]) with the command-line flags on top.
+fn build_config(
+    args: ConfigArgs,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<VisionConfig, String> {
+    // The key and endpoint are required, so they go through the lookup for
+    // `from_vars` to see the flags before it checks them.
+    let provider = args
+        .provider
+        .and_then(|p| p.to_possible_value())
+        .map(|v| v.get_name().to_string());
+    let mut config = VisionConfig::from_vars(|key| {
+        match key {
+            "GLM_VISION_API_KEY" => args.api_key.clone(),
+            "GLM_VISION_PROVIDER" => provider.clone(),
+            "GLM_VISION_BASE_URL" => args.base_url.clone(),
+            _ => None,
+        }
+        .or_else(|| env(key))
+    })
+    .map_err(|e| e.to_string())?;
+
+    if let Some(model) = args.model {
+        config = config.with_model(model);
+    }
+    if let Some(temperature) = args.temperature {
+        config = config.with_temperature(temperature);
+    }
+    if let Some(thinking) = args.thinking {
+        config = config.with_thinking(thinking);
+    }
+    if let Some(top_p) = args.top_p {
+        config.top_p = top_p;
+    }
+    if let Some(max_tokens) = args.max_tokens {
+        config.max_tokens = max_tokens;
+    }
+    if let Some(timeout_secs) = args.timeout_secs {
+        config.timeout_secs = timeout_secs;
+    }
+    if let Some(mb) = args.max_image_size_mb {
+        config.max_image_size_mb = mb;
+    }
+    if let Some(mb) = args.max_video_size_mb {
+        config.max_video_size_mb = mb;
+    }
+    if let Some(max_edge) = args.max_image_edge {
+        let resize = config.image_resize.take().unwrap_or_default();
+        config = config.with_image_resize(resize.with_max_edge(max_edge));
+    }
+    if let Some(mode) = args.tiling {
+        let tiling = config.tiling.take().unwrap_or_default();
+        config = config.with_tiling(tiling.with_mode(mode.into()));
+    }
+    if let Some(proxy) = args.proxy {
+        config = config.with_proxy(proxy);
+    }
+    if let Some(path) = args.root_ca {
+        config = config.with_root_ca(path);
+    }
+    Ok(config)
+}
+
+/// Expand `-` (one source per line of `stdin`) and glob patterns into a flat
+/// list of sources.
+fn expand_inputs(inputs: &[String], mut stdin: impl BufRead) -> Result<Vec<String>, String> {
+    let mut sources = Vec::new();
+    for input in inputs {
+        if input == "-" {
+            for line in (&mut stdin).lines() {
+                let line = line.map_err(|e| format!("failed to read stdin: {e}"))?;
+                let line = line.trim();
+                if !line.is_empty() {
+                    sources.push(line.to_string());
+                }
+            }
+        } else if !glm_vision_rs::types::is_url(input) && input.contains(['*', '?', '[']) {
+            let mut matches: Vec<String> = glob::glob(input)
+                .map_err(|e| format!("invalid glob '{input}': {e}"))?
+                .filter_map(|p| p.ok())
+                .map(|p| p.to_string_lossy().into_owned())
+                .collect();
+            if matches.is_empty() {
+                return Err(format!("no files match '{input}'"));
+            }
+            matches.sort();
+            sources.extend(matches);
+        } else {
+            sources.push(input.clone());
+        }
+    }
+    Ok(sources)
+}
+
+/// File stem used to name `--out-dir` results.
+fn output_stem(source: &str) -> String {
+    let name = source
+        .trim_end_matches('/')
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(source);
+    let stem = Path::new(name)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(name);
+    if stem.is_empty() {
+        "output".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// `--out-dir` file stems for all sources, one per source and all distinct.
+///
+/// Sources sharing a stem (`a/shot.png` and `b/shot.png`, or `shot.png` and
+/// `shot.jpg`) are named after their whole path instead, and any names still
+/// equal after that (the same input given twice) get their input number.
+fn output_stems(sources: &[String]) -> Vec<String> {
+    let stems: Vec<String> = sources.iter().map(|s| output_stem(s)).collect();
+    let names: Vec<String> = stems
+        .iter()
+        .zip(sources)
+        .map(|(stem, source)| {
+            if stems.iter().filter(|s| *s == stem).count() > 1 {
+                flat_path(source)
+            } else {
+                stem.clone()
+            }
+        })
+        .collect();
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            if names.iter().filter(|n| *n == name).count() > 1 {
+                format!("{name}-{}", index + 1)
+            } else {
+                name.clone()
+            }
+        })
+        .collect()
+}
+
+/// A source path or URL flattened into one file name, e.g. `a/b/shot.png` to
+/// `a_b_shot.png`.
+fn flat_path(source: &str) -> String {
+    let name = source
+        .split(['/', '\\', ':'])
+        .filter(|part| !matches!(*part, "" | "." | ".."))
+        .collect::<Vec<_>>()
+        .join("_");
+    if name.is_empty() {
+        "output".to_string()
+    } else {
+        name
+    }
+}
+
+struct JobOutput {
+    source: String,
+    result: Result<String, String>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    if log::set_logger(&StderrLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    match run(cli).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("glm-vision: {e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Returns `Ok(false)` if any input failed.
+async fn run(cli: Cli) -> Result<bool, String> {
+    let config = build_config(cli.config, |key| std::env::var(key).ok())?;
+    let http = ReqwestHttpClient::from_config(&config).map_err(|e| e.to_string())?;
+    if let Command::Regression { .. } = cli.command {
+        let client = VisionClient::new(config, http);
+        return run_regression(&client, &cli.command, cli.output.jobs).await;
+    }
+
+    let command = Arc::new(cli.command);
+    let sources = match command.inputs() {
+        Some(inputs) => expand_inputs(inputs, std::io::stdin().lock())?,
+        None => match command.as_ref() {
+            Command::UiDiffCheck { actual, .. } => vec![actual.clone()],
+            _ => unreachable!("only ui-diff-check has no input list"),
+        },
+    };
+
+    if let Some(dir) = &cli.output.out_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+
+    let stems = output_stems(&sources);
+    let json = cli.output.json;
+    let semaphore = Arc::new(tokio::sync::Semaphore::new(cli.output.jobs.max(1)));
+    let mut tasks = tokio::task::JoinSet::new();
+    for (index, source) in sources.into_iter().enumerate() {
+        let http = CapturingClient {
+            inner: http.clone(),
+            bodies: Arc::default(),
+        };
+        let client = VisionClient::new(config.clone(), http);
+        let command = Arc::clone(&command);
+        let semaphore = Arc::clone(&semaphore);
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let result = match command.run(&client, &source).await {
+                Ok(text) if json => {
+                    let bodies = std::mem::take(&mut *client.http().bodies.lock().unwrap());
+                    Ok(responses_json(bodies, text))
+                }
+                Ok(text) => Ok(text),
+                Err(e) => Err(e.to_string()),
+            };
+            (index, JobOutput { source, result })
+        });
+    }
+
+    let mut outputs = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        outputs.push(joined.map_err(|e| format!("task failed: {e}"))?);
+    }
+    outputs.sort_by_key(|(index, _)| *index);
+
+    let multiple = outputs.len() > 1;
+    let mut all_ok = true;
+    for (index, output) in outputs {
+        let text = match output.result {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}: {e}", output.source);
+                all_ok = false;
+                continue;
+            }
+        };
+        match &cli.output.out_dir {
+            Some(dir) => {
+                let (ext, text) = if json {
+                    ("json", pretty_json(&text))
+                } else {
+                    ("md", text)
+                };
+                let path = dir.join(format!("{}.{}.{}", stems[index], command.tool_name(), ext));
+                std::fs::write(&path, &text)
+                    .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+                println!("{}", path.display());
+            }
+            // JSON Lines, so that the output as a whole stays parseable.
+            None if multiple && json => println!(
+                r#"{{"source":{},"response":{}}}"#,
+                serde_json::Value::from(output.source),
+                text
+            ),
+            None if multiple => println!("==> {} <==\n{}\n", output.source, text),
+            None if json => println!("{}", pretty_json(&text)),
+            None => println!("{}", text),
+        }
+    }
+    Ok(all_ok)
+}
+
+/// Run a `regression` suite, print one line per case and write the requested
+/// reports. Returns `Ok(false)` if any case failed.
+async fn run_regression(
+    client: &VisionClient<ReqwestHttpClient>,
+    command: &Command,
+    jobs: usize,
+) -> Result<bool, String> {
+    let Command::Regression {
+        manifest,
+        expected_dir,
+        actual_dir,
+        prompt,
+        fail_on,
+        cache,
+        html,
+        junit,
+    } = command
+    else {
+        unreachable!("only called for regression");
+    };
+    let suite = match (manifest, expected_dir, actual_dir) {
+        (Some(manifest), _, _) => RegressionSuite::from_manifest(manifest)
+            .map_err(|e| format!("failed to read {}: {e}", manifest.display()))?,
+        (None, Some(expected), Some(actual)) => RegressionSuite::from_dirs(expected, actual)
+            .map_err(|e| format!("failed to list screenshots: {e}"))?,
+        _ => unreachable!("clap requires a manifest or both directories"),
+    };
+    let mut suite = suite
+        .with_prompt(prompt)
+        .with_concurrency(jobs)
+        .with_fail_on((*fail_on).into());
+    if let Some(cache) = cache {
+        suite = suite.with_cache(cache);
+    }
+
+    let run = suite
+        .run(client)
+        .await
+        .map_err(|e| format!("failed to update the cache: {e}"))?;
+    for result in &run.results {
+        match &result.outcome {
+            CaseOutcome::Error(message) => println!("ERROR {}: {message}", result.case.name),
+            CaseOutcome::Compared(report) if result.passed(run.fail_on) => println!(
+                "PASS  {} ({} differences)",
+                result.case.name,
+                report.differences.len()
+            ),
+            CaseOutcome::Compared(report) => println!(
+                "FAIL  {}: {}",
+                result.case.name,
+                report
+                    .differences_at_least(run.fail_on)
+                    .map(|d| d.description.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+        }
+    }
+    println!(
+        "{} cases: {} failed, {} errors",
+        run.results.len(),
+        run.failures(),
+        run.errors()
+    );
+
+    if let Some(path) = html {
+        run.write_html(path)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+    if let Some(path) = junit {
+        run.write_junit(path)
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+    Ok(run.passed())
+}
+
+/// The raw responses of one input as compact JSON: the response itself if the
+/// tool sent one request, or an array of them if it sent several (one per
+/// tile, or retries after an invalid JSON answer). Falls back to the answer
+/// text as a JSON string if nothing was recorded.
+fn responses_json(bodies: Vec<String>, answer: String) -> String {
+    let mut responses: Vec<serde_json::Value> = bodies
+        .into_iter()
+        .map(|body| serde_json::from_str(&body).unwrap_or(serde_json::Value::String(body)))
+        .collect();
+    let value = match responses.len() {
+        0 => serde_json::Value::String(answer),
+        1 => responses.remove(0),
+        _ => serde_json::Value::Array(responses),
+    };
+    value.to_string()
+}
+
+/// Re-indent a raw response body the way the files in `examples/responses/` are stored.
+fn pretty_json(raw: &str) -> String {
+    serde_json::from_str::<serde_json::Value>(raw)
+        .and_then(|v| serde_json::to_string_pretty(&v))
+        .unwrap_or_else(|_| raw.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn vars(pairs: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        |key| {
+            pairs
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.to_string())
+        }
+    }
+
+    #[test]
+    fn test_build_config_flags_over_env() {
+        let env = vars(&[
+            ("GLM_VISION_API_KEY", "env-key"),
+            ("GLM_VISION_PROVIDER", "zhipu"),
+            ("GLM_VISION_MODEL", "env-model"),
+            ("GLM_VISION_MAX_TOKENS", "512"),
+            ("GLM_VISION_STRICT_MEDIA_TYPES", "true"),
+        ]);
+        let args = ConfigArgs {
+            provider: Some(ProviderArg::ZaiCoding),
+            model: Some("flag-model".to_string()),
+            temperature: Some(0.3),
+            ..ConfigArgs::default()
+        };
+        let config = build_config(args, env).unwrap();
+        assert_eq!(config.api_key, "env-key");
+        assert_eq!(config.base_url, Provider::ZaiCoding.base_url());
+        assert_eq!(config.model, "flag-model");
+        assert_eq!(config.temperature, 0.3);
+        assert_eq!(config.max_tokens, 512);
+        assert!(config.strict_media_types);
+    }
+
+    #[test]
+    fn test_build_config_without_env() {
+        let args = ConfigArgs {
+            api_key: Some("flag-key".to_string()),
+            base_url: Some("http://localhost:8080/v1/".to_string()),
+            ..ConfigArgs::default()
+        };
+        let config = build_config(args, vars(&[])).unwrap();
+        assert_eq!(config.api_key, "flag-key");
+        assert_eq!(config.base_url, "http://localhost:8080/v1/");
+
+        // Same variables and fallbacks as `VisionConfig::from_env`.
+        let env = vars(&[("Z_AI_API_KEY", "zai-key"), ("Z_AI_MODE", "ZAI")]);
+        let config = build_config(ConfigArgs::default(), env).unwrap();
+        assert_eq!(config.api_key, "zai-key");
+        assert_eq!(config.base_url, Provider::Zai.base_url());
+
+        let err = build_config(ConfigArgs::default(), vars(&[])).unwrap_err();
+        assert!(err.contains("GLM_VISION_API_KEY"), "{err}");
+        let args = ConfigArgs {
+            api_key: Some("flag-key".to_string()),
+            ..ConfigArgs::default()
+        };
+        assert!(build_config(args, vars(&[])).is_err());
+    }
+
+    #[test]
+    fn test_expand_inputs_stdin() {
+        let stdin = std::io::Cursor::new("a.png\n\n  b.png  \nhttps://example.com/c.png?x=1\n");
+        let sources = expand_inputs(&strings(&["first.png", "-", "last.png"]), stdin).unwrap();
+        assert_eq!(
+            sources,
+            [
+                "first.png",
+                "a.png",
+                "b.png",
+                "https://example.com/c.png?x=1",
+                "last.png"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expand_inputs_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.png", "a.png", "notes.txt"] {
+            std::fs::write(dir.path().join(name), b"x").unwrap();
+        }
+        let pattern = dir.path().join("*.png").to_string_lossy().into_owned();
+        let sources = expand_inputs(&[pattern], std::io::empty()).unwrap();
+        let names: Vec<_> = sources.iter().map(|s| output_stem(s)).collect();
+        assert_eq!(names, ["a", "b"]);
+
+        // URLs are never globbed, and a pattern without matches is an error.
+        let url = "https://example.com/shot.png?size=[2x]".to_string();
+        assert_eq!(
+            expand_inputs(std::slice::from_ref(&url), std::io::empty()).unwrap(),
+            [url]
+        );
+        let missing = dir.path().join("*.jpg").to_string_lossy().into_owned();
+        let err = expand_inputs(&[missing], std::io::empty()).unwrap_err();
+        assert!(err.starts_with("no files match"), "{err}");
+    }
+
+    #[test]
+    fn test_responses_json() {
+        let first = r#"{"id": "a", "choices": []}"#.to_string();
+        let second = r#"{"id": "b", "choices": []}"#.to_string();
+        assert_eq!(
+            responses_json(vec![first.clone()], "answer".to_string()),
+            r#"{"choices":[],"id":"a"}"#
+        );
+        assert_eq!(
+            responses_json(vec![first, second], "answer".to_string()),
+            r#"[{"choices":[],"id":"a"},{"choices":[],"id":"b"}]"#
+        );
+        assert_eq!(
+            responses_json(Vec::new(), "answer".to_string()),
+            r#""answer""#
+        );
+    }
+
+    #[test]
+    fn test_output_stem() {
+        assert_eq!(output_stem("shots/login.png"), "login");
+        assert_eq!(output_stem("C:\\shots\\login.png"), "login");
+        assert_eq!(
+            output_stem("https://example.com/a/chart.v2.jpg"),
+            "chart.v2"
+        );
+        assert_eq!(output_stem("shots/"), "shots");
+        assert_eq!(output_stem(""), "output");
+    }
+
+    #[test]
+    fn test_output_stems_disambiguate() {
+        let sources = strings(&["a/shot.png", "b/shot.png", "shot.jpg", "login.png"]);
+        assert_eq!(
+            output_stems(&sources),
+            ["a_shot.png", "b_shot.png", "shot.jpg", "login"]
+        );
+
+        let sources = strings(&["./shot.png", "shot.png", "../x/shot.png"]);
+        assert_eq!(
+            output_stems(&sources),
+            ["shot.png-1", "shot.png-2", "x_shot.png"]
+        );
+    }
+}
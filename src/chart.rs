@@ -0,0 +1,215 @@
+//! Data read from chart images by
+//! [`extract_chart_data`](crate::tools::extract_chart_data).
+//!
+//! [`ChartData`] is filled in through
+//! [`completion_json`](crate::VisionClient::completion_json), so the doc
+//! comments on these types are part of the schema shown to the model. Values
+//! the model could not read from a label are flagged as
+//! [`estimated`](DataPoint::estimated).
+
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The dataset behind a chart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ChartData {
+    pub kind: ChartKind,
+    /// Chart title, if shown.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Horizontal axis (categories for bar charts, slice labels for pie charts).
+    #[serde(default)]
+    pub x_axis: Axis,
+    /// Vertical axis (values).
+    #[serde(default)]
+    pub y_axis: Axis,
+    /// Legend entries in display order.
+    #[serde(default)]
+    pub legend: Vec<String>,
+    /// One series per legend entry.
+    pub series: Vec<Series>,
+}
+
+impl ChartData {
+    /// Whether any value was estimated from its position rather than read
+    /// from a label.
+    pub fn is_estimated(&self) -> bool {
+        self.series
+            .iter()
+            .flat_map(|s| &s.points)
+            .any(|p| p.estimated)
+    }
+
+    /// The data as CSV, one row per point: `series,x,y,estimated`.
+    ///
+    /// Fields are quoted as in RFC 4180 when needed.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("series,x,y,estimated\r\n");
+        for series in &self.series {
+            for point in &series.points {
+                let row = [
+                    series.name.clone(),
+                    point.x.to_string(),
+                    point.y.to_string(),
+                    point.estimated.to_string(),
+                ];
+                csv.push_str(&csv_row(&row));
+            }
+        }
+        csv
+    }
+
+    /// The data as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Kind of chart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartKind {
+    Line,
+    Bar,
+    StackedBar,
+    Area,
+    Scatter,
+    Pie,
+    Histogram,
+    Other,
+}
+
+/// An axis title and unit.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Axis {
+    /// Axis title, if shown.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Unit of the values (e.g. "USD", "%", "ms"), if known.
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+/// A named series of points.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Series {
+    /// Legend label of the series.
+    pub name: String,
+    /// Points in x axis order.
+    pub points: Vec<DataPoint>,
+}
+
+/// A single value of a series.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct DataPoint {
+    pub x: XValue,
+    /// Value in the y axis unit.
+    pub y: f64,
+    /// True when the value was estimated from its position against the axis
+    /// rather than read from a label on the chart.
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+/// A position on the x axis: a number, or a category or date label.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum XValue {
+    Number(f64),
+    Label(String),
+}
+
+impl fmt::Display for XValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XValue::Number(n) => write!(f, "{}", n),
+            XValue::Label(s) => f.write_str(s),
+        }
+    }
+}
+
+/// A CSV record terminated by CRLF, quoting fields that contain commas,
+/// quotes, line breaks or surrounding spaces.
+pub(crate) fn csv_row(fields: &[String]) -> String {
+    let mut row = fields
+        .iter()
+        .map(|field| {
+            let needs_quotes = field.contains([',', '"', '\n', '\r'])
+                || field.starts_with(' ')
+                || field.ends_with(' ');
+            if needs_quotes {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::parse_json;
+
+    const ANSWER: &str = r#"{
+        "kind": "bar",
+        "title": "Revenue",
+        "x_axis": {"label": "Quarter"},
+        "y_axis": {"label": "Revenue", "unit": "USD"},
+        "legend": ["2023", "2024, est."],
+        "series": [
+            {"name": "2023", "points": [{"x": "Q1", "y": 1200}, {"x": "Q2", "y": 1350.5}]},
+            {"name": "2024, est.", "points": [{"x": "Q1", "y": 1500, "estimated": true}]}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_chart_data() {
+        let chart: ChartData = parse_json(ANSWER).unwrap();
+        assert_eq!(chart.kind, ChartKind::Bar);
+        assert_eq!(chart.y_axis.unit.as_deref(), Some("USD"));
+        assert_eq!(chart.series[0].points[1].x, XValue::Label("Q2".to_string()));
+        assert!(!chart.series[0].points[0].estimated);
+        assert!(chart.is_estimated());
+
+        let scatter: ChartData = parse_json(
+            r#"{"kind": "scatter", "series": [{"name": "a", "points": [{"x": 1.5, "y": 2}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(scatter.series[0].points[0].x, XValue::Number(1.5));
+        assert!(!scatter.is_estimated());
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let chart: ChartData = parse_json(ANSWER).unwrap();
+        assert_eq!(
+            chart.to_csv(),
+            "series,x,y,estimated\r\n\
+             2023,Q1,1200,false\r\n\
+             2023,Q2,1350.5,false\r\n\
+             \"2024, est.\",Q1,1500,true\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_json_roundtrip() {
+        let chart: ChartData = parse_json(ANSWER).unwrap();
+        let again: ChartData = serde_json::from_str(&chart.to_json()).unwrap();
+        assert_eq!(again, chart);
+    }
+
+    #[test]
+    fn test_csv_row_quoting() {
+        let fields = ["plain", "a \"quote\"", " padded", "two\nlines"].map(String::from);
+        assert_eq!(
+            csv_row(&fields),
+            "plain,\"a \"\"quote\"\"\",\" padded\",\"two\nlines\"\r\n"
+        );
+    }
+}
@@ -1,22 +1,60 @@
-use anyhow::{bail, Context, Result};
-use std::path::Path;
+use std::io::Read;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::write::EncoderStringWriter;
+use base64::Engine;
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 
 use crate::config::VisionConfig;
+use crate::error::{Result, VisionError};
 use crate::http::HttpClient;
+use crate::media::{decode_data_url, MediaSource};
+use crate::stream::VisionStream;
+use crate::structured;
 use crate::types::*;
 
+/// Provider `finish_reason` reported when output was blocked by the content filter.
+const FINISH_REASON_SENSITIVE: &str = "sensitive";
+
+/// Headroom added to the request buffer for JSON structure and parameters.
+const REQUEST_OVERHEAD: usize = 1024;
+
+/// Number of leading bytes read from local files for type detection.
+const SNIFF_LEN: usize = 64;
+
+/// Largest image, in MB, read to be resized, converted, tiled, annotated or
+/// split into frames. `max_image_size_mb` applies to the encoded result instead.
+pub const MAX_IMAGE_INPUT_MB: u64 = 64;
+
+/// Largest video, in MB, buffered from a [`MediaSource::Reader`] to sample
+/// frames from. Files are streamed and have no such limit.
+pub const MAX_VIDEO_INPUT_MB: u64 = 1024;
+
 /// HTTP client for the GLM-4V vision completions API.
 pub struct VisionClient<H: HttpClient> {
     http: H,
     config: VisionConfig,
 }
 
+#[cfg(any(feature = "reqwest", feature = "hyper", feature = "ureq"))]
+impl VisionClient<crate::backends::DefaultHttpClient> {
+    /// Create a client using the bundled [`DefaultHttpClient`](crate::backends::DefaultHttpClient),
+    /// configured from `config` (timeout, proxy and TLS settings).
+    pub fn from_config(config: VisionConfig) -> Result<Self> {
+        let http = crate::backends::DefaultHttpClient::from_config(&config)?;
+        Ok(Self::new(config, http))
+    }
+}
+
 impl<H: HttpClient> VisionClient<H> {
     /// Create a new VisionClient with the given configuration and HTTP client.
     ///
     /// The caller is responsible for configuring the HTTP client (timeouts, TLS,
     /// proxies, etc.). See [`VisionConfig::timeout_secs`] for the recommended
-    /// timeout value.
+    /// timeout value, or use `from_config` with one of the bundled backends.
     pub fn new(config: VisionConfig, http: H) -> Self {
         Self { http, config }
     }
@@ -26,104 +64,547 @@ impl<H: HttpClient> VisionClient<H> {
         &self.config
     }
 
-    /// Process an image source (local file path or URL) into a `ContentPart`.
+    /// Returns a reference to the underlying HTTP client.
+    pub fn http(&self) -> &H {
+        &self.http
+    }
+
+    /// Process an image source into a `ContentPart`.
     ///
     /// - URLs are passed through directly.
-    /// - Local files are validated, base64-encoded, and wrapped in a data URL.
-    pub fn process_image(&self, source: &str) -> Result<ContentPart> {
-        if is_url(source) {
-            return Ok(ContentPart::ImageUrl {
-                image_url: UrlHolder {
-                    url: source.to_string(),
-                },
-            });
+    /// - Files, bytes, readers and data URLs are validated against their detected
+    ///   type and `max_image_size_mb`, then base64-encoded into a data URL.
+    pub async fn process_image(&self, source: impl Into<MediaSource>) -> Result<ContentPart> {
+        let read_limit_mb = if cfg!(feature = "image") {
+            MAX_IMAGE_INPUT_MB
+        } else {
+            self.config.max_image_size_mb
+        };
+        match self
+            .load_media(source.into(), "Image", read_limit_mb)
+            .await?
+        {
+            LoadedMedia::Url(url) => Ok(ContentPart::ImageUrl {
+                image_url: UrlHolder { url },
+            }),
+            LoadedMedia::Local(media) => self.local_image_part(media).await,
         }
+    }
+
+    /// Check, convert or resize as configured, and encode a local image.
+    async fn local_image_part(&self, media: LocalMedia) -> Result<ContentPart> {
+        let mime =
+            self.check_media_type(&media.label, media.media_type(), "image", is_accepted_image)?;
 
-        let path = Path::new(source);
-        if !path.exists() {
-            bail!("Image file not found: {}", source);
+        #[cfg(feature = "image")]
+        if self.config.image_resize.is_some() {
+            let data = media.into_bytes().await?;
+            return self.fit_image(&data, mime).map(|(part, _)| part);
+        }
+        if is_convertible_image(mime) {
+            return self.convert_image(media, mime).await;
         }
 
-        let metadata = std::fs::metadata(path)
-            .with_context(|| format!("Failed to read metadata: {}", source))?;
-        let size_mb = metadata.len() / (1024 * 1024);
+        let size_mb = media.len() / (1024 * 1024);
         if size_mb > self.config.max_image_size_mb {
-            bail!(
+            return Err(VisionError::InvalidMedia(format!(
                 "Image file too large: {}MB (max {}MB)",
-                size_mb,
-                self.config.max_image_size_mb
-            );
+                size_mb, self.config.max_image_size_mb
+            )));
         }
+        Ok(ContentPart::ImageUrl {
+            image_url: UrlHolder {
+                url: media.into_data_url(mime).await?,
+            },
+        })
+    }
+
+    /// Convert an image type the API does not accept (WebP, GIF, BMP, TIFF) to
+    /// PNG, or JPEG if the PNG would exceed `max_image_size_mb`. Inputs over
+    /// [`MAX_IMAGE_INPUT_MB`] are rejected before they are read.
+    #[cfg(feature = "image")]
+    async fn convert_image(&self, media: LocalMedia, mime: &'static str) -> Result<ContentPart> {
+        let options = crate::ImageResize {
+            max_edge: None,
+            ..crate::ImageResize::default()
+        };
+        let max_bytes = self.config.max_image_size_mb * 1024 * 1024;
+        let data = media.into_bytes().await?;
+        let fitted = crate::preprocess::fit_image(&data, mime, max_bytes, &options)?;
+        Ok(image_data_part(fitted.mime, &fitted.data))
+    }
 
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    #[cfg(not(feature = "image"))]
+    async fn convert_image(&self, media: LocalMedia, mime: &'static str) -> Result<ContentPart> {
+        Err(VisionError::InvalidMedia(format!(
+            "Unsupported image format: {} contains {}; enable the `image` feature to convert it",
+            media.label, mime
+        )))
+    }
+
+    /// Process an image into one content part per frame.
+    ///
+    /// Animated GIFs are expanded into at most `max_frames` evenly spaced frames,
+    /// each sent as a PNG, so the model can follow a bug repro step by step. Other
+    /// images (and URLs) yield a single part, as from [`process_image`](Self::process_image).
+    #[cfg(feature = "image")]
+    pub async fn process_image_frames(
+        &self,
+        source: impl Into<MediaSource>,
+        max_frames: usize,
+    ) -> Result<Vec<ContentPart>> {
+        let media = match self
+            .load_media(source.into(), "Image", MAX_IMAGE_INPUT_MB)
+            .await?
+        {
+            LoadedMedia::Url(url) => {
+                return Ok(vec![ContentPart::ImageUrl {
+                    image_url: UrlHolder { url },
+                }])
+            }
+            LoadedMedia::Local(media) => media,
+        };
         let mime =
-            image_mime_type(ext).with_context(|| format!("Unsupported image format: .{}", ext))?;
+            self.check_media_type(&media.label, media.media_type(), "image", is_accepted_image)?;
+        if mime != "image/gif" {
+            return Ok(vec![self.local_image_part(media).await?]);
+        }
 
-        let data =
-            std::fs::read(path).with_context(|| format!("Failed to read image: {}", source))?;
-        let b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data);
-        let data_url = format!("data:{};base64,{}", mime, b64);
+        let options = self
+            .config
+            .image_resize
+            .clone()
+            .unwrap_or(crate::ImageResize {
+                max_edge: None,
+                ..crate::ImageResize::default()
+            });
+        let max_bytes = self.config.max_image_size_mb * 1024 * 1024;
+        let data = media.into_bytes().await?;
+        crate::preprocess::gif_frames(&data, max_frames)?
+            .iter()
+            .map(|frame| {
+                let fitted = crate::preprocess::fit_image(frame, "image/png", max_bytes, &options)?;
+                Ok(image_data_part(fitted.mime, &fitted.data))
+            })
+            .collect()
+    }
 
-        Ok(ContentPart::ImageUrl {
-            image_url: UrlHolder { url: data_url },
-        })
+    /// Process a local or in-memory image, downscaling and recompressing it to fit
+    /// `max_image_size_mb` and the [`image_resize`](VisionConfig::image_resize)
+    /// limits (defaults if unset).
+    ///
+    /// Returns the content part and the [`ImageScale`](crate::preprocess::ImageScale)
+    /// applied, for mapping coordinates in the answer back to the original image.
+    #[cfg(feature = "image")]
+    pub async fn prepare_image(
+        &self,
+        source: impl Into<MediaSource>,
+    ) -> Result<(ContentPart, crate::preprocess::ImageScale)> {
+        let media = match self
+            .load_media(source.into(), "Image", MAX_IMAGE_INPUT_MB)
+            .await?
+        {
+            LoadedMedia::Url(url) => {
+                return Err(VisionError::InvalidMedia(format!(
+                    "Cannot resize a remote image: {}",
+                    url
+                )))
+            }
+            LoadedMedia::Local(media) => media,
+        };
+        let mime =
+            self.check_media_type(&media.label, media.media_type(), "image", is_accepted_image)?;
+        let data = media.into_bytes().await?;
+        self.fit_image(&data, mime)
     }
 
-    /// Process a video source (local file path or URL) into a `ContentPart`.
+    /// Split a local or in-memory image into overlapping tiles, each encoded as
+    /// a content part and paired with the region of the image it covers.
     ///
-    /// - URLs are passed through directly.
-    /// - Local files are validated, base64-encoded, and wrapped in a data URL.
-    pub fn process_video(&self, source: &str) -> Result<ContentPart> {
-        if is_url(source) {
-            return Ok(ContentPart::VideoUrl {
-                video_url: UrlHolder {
-                    url: source.to_string(),
-                },
-            });
+    /// Images that fit in a single tile yield one part, as from
+    /// [`prepare_image`](Self::prepare_image). Each tile is fitted to
+    /// `max_image_size_mb` and the [`image_resize`](VisionConfig::image_resize)
+    /// limits (defaults if unset). Decoding runs on tokio's blocking pool.
+    #[cfg(feature = "image")]
+    pub async fn process_image_tiles(
+        &self,
+        source: impl Into<MediaSource>,
+        tiling: &crate::ImageTiling,
+    ) -> Result<Vec<(crate::preprocess::TileRegion, ContentPart)>> {
+        let tiles = self.tile_images(vec![source.into()], tiling).await?;
+        Ok(tiles
+            .into_iter()
+            .filter_map(|(region, mut parts)| Some((region, parts.pop()??)))
+            .collect())
+    }
+
+    /// Split two images, such as the expected and actual screenshots of a UI
+    /// diff, into the same overlapping tiles.
+    ///
+    /// The layout is computed for the larger of the two, so each region covers
+    /// the same pixels of both; a side is `None` where its image does not reach
+    /// the region. Otherwise like [`process_image_tiles`](Self::process_image_tiles).
+    #[cfg(feature = "image")]
+    pub async fn process_image_tile_pairs(
+        &self,
+        first: impl Into<MediaSource>,
+        second: impl Into<MediaSource>,
+        tiling: &crate::ImageTiling,
+    ) -> Result<Vec<(crate::preprocess::TileRegion, [Option<ContentPart>; 2])>> {
+        let tiles = self
+            .tile_images(vec![first.into(), second.into()], tiling)
+            .await?;
+        Ok(tiles
+            .into_iter()
+            .map(|(region, mut parts)| {
+                let second = parts.pop().flatten();
+                (region, [parts.pop().flatten(), second])
+            })
+            .collect())
+    }
+
+    /// Cut each of `sources` into the tiles of one layout, with one entry per
+    /// source in each tile (`None` where that image does not reach it).
+    #[cfg(feature = "image")]
+    async fn tile_images(
+        &self,
+        sources: Vec<MediaSource>,
+        tiling: &crate::ImageTiling,
+    ) -> Result<Vec<(crate::preprocess::TileRegion, Vec<Option<ContentPart>>)>> {
+        let mut images = Vec::with_capacity(sources.len());
+        for source in sources {
+            let media = match self.load_media(source, "Image", MAX_IMAGE_INPUT_MB).await? {
+                LoadedMedia::Url(url) => {
+                    return Err(VisionError::InvalidMedia(format!(
+                        "Cannot tile a remote image: {}",
+                        url
+                    )))
+                }
+                LoadedMedia::Local(media) => media,
+            };
+            let mime = self.check_media_type(
+                &media.label,
+                media.media_type(),
+                "image",
+                is_accepted_image,
+            )?;
+            let data = media.into_bytes().await?;
+            let dimensions = crate::preprocess::image_dimensions(&data)?;
+            images.push((data, mime, dimensions));
         }
 
-        let path = Path::new(source);
-        if !path.exists() {
-            bail!("Video file not found: {}", source);
+        let (width, height) = images
+            .iter()
+            .fold((0, 0), |(w, h), (_, _, (iw, ih))| (w.max(*iw), h.max(*ih)));
+        let regions = crate::preprocess::tile_layout(width, height, tiling);
+        if regions.len() == 1 {
+            let parts = images
+                .iter()
+                .map(|(data, mime, _)| self.fit_image(data, mime).map(|(part, _)| Some(part)))
+                .collect::<Result<_>>()?;
+            return Ok(vec![(regions[0], parts)]);
         }
 
-        let metadata = std::fs::metadata(path)
-            .with_context(|| format!("Failed to read metadata: {}", source))?;
-        let size_mb = metadata.len() / (1024 * 1024);
+        let options = self.config.image_resize.clone().unwrap_or_default();
+        let max_bytes = self.config.max_image_size_mb * 1024 * 1024;
+        tokio::task::spawn_blocking(move || {
+            let mut tiles: Vec<_> = regions
+                .iter()
+                .map(|region| (*region, Vec::with_capacity(images.len())))
+                .collect();
+            for (data, mime, (width, height)) in images {
+                let image = crate::preprocess::decode_image(&data)?;
+                drop(data);
+                for (region, parts) in &mut tiles {
+                    let part = match region.clip(width, height) {
+                        Some(r) => {
+                            let tile = image.crop_imm(r.x, r.y, r.width, r.height);
+                            let fitted =
+                                crate::preprocess::fit_decoded(&tile, mime, max_bytes, &options)?;
+                            Some(image_data_part(fitted.mime, &fitted.data))
+                        }
+                        None => None,
+                    };
+                    parts.push(part);
+                }
+            }
+            Ok(tiles)
+        })
+        .await
+        .map_err(|e| VisionError::InvalidMedia(format!("Image tiling failed: {}", e)))?
+    }
+
+    /// Draw numbered outlines for `annotations` on a local or in-memory image,
+    /// with a legend of their labels below it, and return the result as PNG.
+    ///
+    /// See [`annotate`](crate::annotate::annotate). Drawing runs on tokio's
+    /// blocking pool.
+    #[cfg(feature = "image")]
+    pub async fn annotate_image(
+        &self,
+        source: impl Into<MediaSource>,
+        annotations: &[crate::annotate::Annotation],
+    ) -> Result<Vec<u8>> {
+        let media = match self
+            .load_media(source.into(), "Image", MAX_IMAGE_INPUT_MB)
+            .await?
+        {
+            LoadedMedia::Url(url) => {
+                return Err(VisionError::InvalidMedia(format!(
+                    "Cannot annotate a remote image: {}",
+                    url
+                )))
+            }
+            LoadedMedia::Local(media) => media,
+        };
+        self.check_media_type(&media.label, media.media_type(), "image", is_accepted_image)?;
+        let data = media.into_bytes().await?;
+        let annotations = annotations.to_vec();
+        tokio::task::spawn_blocking(move || crate::annotate::annotate_png(&data, &annotations))
+            .await
+            .map_err(|e| VisionError::InvalidMedia(format!("Annotation failed: {}", e)))?
+    }
+
+    #[cfg(feature = "image")]
+    fn fit_image(
+        &self,
+        data: &[u8],
+        mime: &'static str,
+    ) -> Result<(ContentPart, crate::preprocess::ImageScale)> {
+        let resize = self.config.image_resize.clone().unwrap_or_default();
+        let max_bytes = self.config.max_image_size_mb * 1024 * 1024;
+        let fitted = crate::preprocess::fit_image(data, mime, max_bytes, &resize)?;
+        Ok((image_data_part(fitted.mime, &fitted.data), fitted.scale))
+    }
+
+    /// Process a video source into a `ContentPart`.
+    ///
+    /// - URLs are passed through directly.
+    /// - Files, bytes, readers and data URLs are validated against their detected
+    ///   type and `max_video_size_mb`, then base64-encoded into a data URL.
+    ///
+    /// Local files are checked before they are read, then streamed through the
+    /// base64 encoder on tokio's blocking pool, so the raw bytes are never held
+    /// in memory. Sending the part serializes a second encoded copy into the
+    /// request body.
+    pub async fn process_video(&self, source: impl Into<MediaSource>) -> Result<ContentPart> {
+        let media = match self
+            .load_media(source.into(), "Video", self.config.max_video_size_mb)
+            .await?
+        {
+            LoadedMedia::Url(url) => {
+                return Ok(ContentPart::VideoUrl {
+                    video_url: UrlHolder { url },
+                })
+            }
+            LoadedMedia::Local(media) => media,
+        };
+
+        let size_mb = media.len() / (1024 * 1024);
         if size_mb > self.config.max_video_size_mb {
-            bail!(
+            return Err(VisionError::InvalidMedia(format!(
                 "Video file too large: {}MB (max {}MB)",
-                size_mb,
-                self.config.max_video_size_mb
-            );
+                size_mb, self.config.max_video_size_mb
+            )));
         }
+        let mime = self.check_media_type(
+            &media.label,
+            media.media_type(),
+            "video",
+            is_supported_video,
+        )?;
 
-        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-        let mime =
-            video_mime_type(ext).with_context(|| format!("Unsupported video format: .{}", ext))?;
+        Ok(ContentPart::VideoUrl {
+            video_url: UrlHolder {
+                url: media.into_data_url(mime).await?,
+            },
+        })
+    }
 
-        let data =
-            std::fs::read(path).with_context(|| format!("Failed to read video: {}", source))?;
-        let b64 = base64::Engine::encode(&base64::engine::general_purpose::STANDARD, &data);
-        let data_url = format!("data:{};base64,{}", mime, b64);
+    /// Sample frames from a local or in-memory MP4, MOV, MKV or WebM video and
+    /// encode each as an image content part, paired with its timestamp.
+    ///
+    /// `max_video_size_mb` does not apply; each frame is fitted to
+    /// `max_image_size_mb` and the [`image_resize`](VisionConfig::image_resize)
+    /// limits (defaults if unset). Decoding runs on tokio's blocking pool.
+    #[cfg(feature = "video")]
+    pub async fn process_video_frames(
+        &self,
+        source: impl Into<MediaSource>,
+        sampling: &crate::video::VideoSampling,
+    ) -> Result<Vec<(std::time::Duration, ContentPart)>> {
+        let media = match self
+            .load_media(source.into(), "Video", MAX_VIDEO_INPUT_MB)
+            .await?
+        {
+            LoadedMedia::Url(url) => {
+                return Err(VisionError::InvalidMedia(format!(
+                    "Cannot sample frames from a remote video: {}",
+                    url
+                )))
+            }
+            LoadedMedia::Local(media) => media,
+        };
+        let mime = self.check_media_type(
+            &media.label,
+            media.media_type(),
+            "video",
+            is_supported_video,
+        )?;
 
-        Ok(ContentPart::VideoUrl {
-            video_url: UrlHolder { url: data_url },
+        let sampling = sampling.clone();
+        let options = self.config.image_resize.clone().unwrap_or_default();
+        let max_bytes = self.config.max_image_size_mb * 1024 * 1024;
+        tokio::task::spawn_blocking(move || {
+            let frames = match media.content {
+                LocalContent::Bytes(data) => {
+                    crate::video::sample_frames(std::io::Cursor::new(data), mime, &sampling)?
+                }
+                LocalContent::File { path, .. } => {
+                    let file = std::fs::File::open(path)
+                        .map_err(|e| file_error(media.kind, &media.label, e))?;
+                    crate::video::sample_frames(std::io::BufReader::new(file), mime, &sampling)?
+                }
+            };
+            frames
+                .into_iter()
+                .map(|frame| {
+                    let image = image::DynamicImage::ImageRgb8(frame.image);
+                    let fitted =
+                        crate::preprocess::fit_decoded(&image, "image/png", max_bytes, &options)?;
+                    Ok((frame.timestamp, image_data_part(fitted.mime, &fitted.data)))
+                })
+                .collect()
         })
+        .await
+        .map_err(|e| VisionError::InvalidMedia(format!("Frame sampling failed: {}", e)))?
     }
 
-    /// Send a vision completion request and return the raw JSON response body.
-    pub async fn completion_raw(
+    /// Resolve a media source to a URL to pass through, its encoded bytes, or a
+    /// local file whose leading bytes have been read for type detection.
+    ///
+    /// `kind` ("Image" or "Video") is used in error messages. Local files are only
+    /// probed here, off the async runtime; their content is read later, once the
+    /// type and size have been checked. Readers are read up to `read_limit_mb`:
+    /// the size limit when their content is sent as is, or the larger
+    /// [`MAX_IMAGE_INPUT_MB`] or [`MAX_VIDEO_INPUT_MB`] when it is decoded first.
+    async fn load_media(
         &self,
-        system_prompt: &str,
-        content_parts: Vec<ContentPart>,
-        user_prompt: &str,
-    ) -> Result<String> {
-        let mut user_content = content_parts;
-        user_content.push(ContentPart::Text {
-            text: user_prompt.to_string(),
-        });
+        source: MediaSource,
+        kind: &'static str,
+        read_limit_mb: u64,
+    ) -> Result<LoadedMedia> {
+        let label = source.describe();
+        let (declared, content) = match source {
+            MediaSource::Url(url) => return Ok(LoadedMedia::Url(url)),
+            MediaSource::Path(path) => {
+                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                let declared = image_mime_type(ext).or_else(|| video_mime_type(ext));
+                let probe = path.clone();
+                let (len, header) = blocking(move || {
+                    let file = std::fs::File::open(&probe)?;
+                    let len = file.metadata()?.len();
+                    let mut header = Vec::with_capacity(SNIFF_LEN);
+                    file.take(SNIFF_LEN as u64).read_to_end(&mut header)?;
+                    Ok((len, header))
+                })
+                .await
+                .map_err(|e| file_error(kind, &label, e))?;
+                (declared, LocalContent::File { path, len, header })
+            }
+            MediaSource::Bytes { data, mime } => (
+                mime.as_deref().and_then(known_mime_type),
+                LocalContent::Bytes(data),
+            ),
+            MediaSource::Reader { reader, mime } => {
+                // Stop reading once the limit is passed, so an endless reader
+                // cannot fill memory before the size check.
+                let limit = (read_limit_mb + 1) * 1024 * 1024;
+                let mut data = Vec::new();
+                tokio::io::AsyncReadExt::read_to_end(
+                    &mut tokio::io::AsyncReadExt::take(reader, limit),
+                    &mut data,
+                )
+                .await
+                .map_err(|e| {
+                    VisionError::InvalidMedia(format!(
+                        "Failed to read {} from reader: {}",
+                        kind.to_lowercase(),
+                        e
+                    ))
+                })?;
+                if data.len() as u64 >= limit {
+                    return Err(VisionError::InvalidMedia(format!(
+                        "{} file too large: reader exceeds the {}MB limit",
+                        kind, read_limit_mb
+                    )));
+                }
+                (
+                    mime.as_deref().and_then(known_mime_type),
+                    LocalContent::Bytes(data),
+                )
+            }
+            MediaSource::DataUrl(url) => {
+                let (mime, data) = decode_data_url(&url).ok_or_else(|| {
+                    VisionError::InvalidMedia("Invalid base64 data URL".to_string())
+                })?;
+                (known_mime_type(mime), LocalContent::Bytes(data))
+            }
+        };
+        Ok(LoadedMedia::Local(LocalMedia {
+            label,
+            kind,
+            declared,
+            content,
+        }))
+    }
+
+    /// Determine the MIME type of media content.
+    ///
+    /// Content that is not a supported `kind` is rejected. Declared/content type
+    /// mismatches are rejected when `strict_media_types` is set, and otherwise
+    /// logged as a warning before the detected type is used.
+    fn check_media_type(
+        &self,
+        label: &str,
+        media: MediaType,
+        kind: &str,
+        supported: fn(&str) -> bool,
+    ) -> Result<&'static str> {
+        let mime = media.mime().ok_or_else(|| {
+            VisionError::InvalidMedia(format!(
+                "Unsupported {} format: {} is not a recognized media file",
+                kind, label
+            ))
+        })?;
+        if !supported(mime) {
+            return Err(VisionError::InvalidMedia(format!(
+                "Unsupported {} format: {} contains {}",
+                kind, label, mime
+            )));
+        }
+        if media.is_mismatch() {
+            let message = format!(
+                "Media type mismatch: {} is declared as {} but contains {}",
+                label,
+                media.declared.unwrap_or("unknown"),
+                mime
+            );
+            if self.config.strict_media_types {
+                return Err(VisionError::InvalidMedia(message));
+            }
+            log::warn!("{}; sending it as {}", message, mime);
+        }
+        Ok(mime)
+    }
 
+    /// Build the serialized chat request body for the given messages.
+    fn request_body(
+        &self,
+        messages: &[VisionMessage],
+        stream: bool,
+        response_format: Option<ResponseFormat>,
+    ) -> Result<Vec<u8>> {
         let thinking = if self.config.thinking_enabled {
             Some(ThinkingConfig {
                 kind: "enabled".to_string(),
@@ -134,25 +615,62 @@ impl<H: HttpClient> VisionClient<H> {
 
         let request = VisionChatRequest {
             model: self.config.model.clone(),
-            messages: vec![
-                VisionMessage {
-                    role: "system".to_string(),
-                    content: VisionContent::Text(system_prompt.to_string()),
-                },
-                VisionMessage {
-                    role: "user".to_string(),
-                    content: VisionContent::Parts(user_content),
-                },
-            ],
+            messages,
             thinking,
-            stream: false,
+            stream,
             temperature: self.config.temperature,
             top_p: self.config.top_p,
             max_tokens: self.config.max_tokens,
+            response_format,
         };
 
-        let url = self.config.completions_url();
-        let body = serde_json::to_vec(&request).context("Failed to serialize request")?;
+        // Size the buffer up front so large data URLs are copied in without
+        // reallocating. This is a second copy of each URL while `messages` lives.
+        let payload: usize = messages.iter().map(payload_len).sum();
+        let mut body = Vec::with_capacity(payload + REQUEST_OVERHEAD);
+        serde_json::to_writer(&mut body, &request).map_err(|e| {
+            VisionError::InvalidRequest(format!("Failed to serialize request: {e}"))
+        })?;
+        Ok(body)
+    }
+
+    /// Returns the completions URL, or a config error if no endpoint is set.
+    fn completions_url(&self) -> Result<String> {
+        if self.config.base_url.trim().is_empty() {
+            return Err(VisionError::Config(
+                "no base URL set; use with_provider() or with_base_url()".to_string(),
+            ));
+        }
+        Ok(self.config.completions_url())
+    }
+
+    /// Send a vision completion request and return the raw JSON response body.
+    pub async fn completion_raw(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+    ) -> Result<String> {
+        let messages = single_turn(system_prompt, content_parts, user_prompt);
+        let body = self.request_body(&messages, false, None)?;
+        // Free the data URLs now that the body holds a copy, rather than
+        // keeping both for the whole request.
+        drop(messages);
+        self.send(body).await
+    }
+
+    /// Send a multi-message conversation and return the raw JSON response body.
+    ///
+    /// `messages` is sent as-is, so it should start with a system message and end
+    /// with a user message. See [`VisionSession`](crate::session::VisionSession)
+    /// for a higher-level wrapper that tracks history.
+    pub async fn chat_raw(&self, messages: &[VisionMessage]) -> Result<String> {
+        self.send(self.request_body(messages, false, None)?).await
+    }
+
+    /// Post a serialized non-streaming request and return the raw response body.
+    async fn send(&self, body: Vec<u8>) -> Result<String> {
+        let url = self.completions_url()?;
         let auth = format!("Bearer {}", self.config.api_key);
         let headers = [
             ("Authorization", auth.as_str()),
@@ -163,21 +681,56 @@ impl<H: HttpClient> VisionClient<H> {
 
         let response = self
             .http
-            .post(&url, &headers, &body)
+            .post_owned(&url, &headers, body)
             .await
-            .map_err(|e| anyhow::anyhow!("Failed to send vision API request: {e}"))?;
+            .map_err(VisionError::from_transport)?;
 
         if !response.is_success() {
-            bail!(
-                "Vision API error HTTP {}: {}",
-                response.status,
-                response.body
-            );
+            return Err(VisionError::from_response(response.status, response.body));
         }
 
         Ok(response.body)
     }
 
+    /// Send a streaming (`stream: true`) vision completion request.
+    ///
+    /// Returns a [`VisionStream`] of server-sent deltas carrying `content` and
+    /// `reasoning_content` as they are generated. Whether chunks arrive
+    /// incrementally depends on the [`HttpClient::post_stream`] implementation.
+    pub async fn completion_stream(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+    ) -> Result<VisionStream> {
+        let url = self.completions_url()?;
+        let messages = single_turn(system_prompt, content_parts, user_prompt);
+        let body = self.request_body(&messages, true, None)?;
+        drop(messages);
+        let auth = format!("Bearer {}", self.config.api_key);
+        let headers = [
+            ("Authorization", auth.as_str()),
+            ("Content-Type", "application/json"),
+            ("Accept", "text/event-stream"),
+            ("X-Title", "4.5V MCP Local"),
+            ("Accept-Language", "en-US,en"),
+        ];
+
+        let response = self
+            .http
+            .post_stream(&url, &headers, &body)
+            .await
+            .map_err(VisionError::from_transport)?;
+
+        if !response.is_success() {
+            let status = response.status;
+            let body = response.text().await.unwrap_or_default();
+            return Err(VisionError::from_response(status, body));
+        }
+
+        Ok(VisionStream::new(response.body))
+    }
+
     /// Send a vision completion request and return the extracted text content.
     pub async fn completion(
         &self,
@@ -185,23 +738,33 @@ impl<H: HttpClient> VisionClient<H> {
         content_parts: Vec<ContentPart>,
         user_prompt: &str,
     ) -> Result<String> {
+        self.completion_detailed(system_prompt, content_parts, user_prompt)
+            .await
+            .map(|c| c.content)
+    }
+
+    /// Send a vision completion request and return the content together with
+    /// reasoning, finish reason, token usage and response identifiers.
+    pub async fn completion_detailed(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+    ) -> Result<VisionCompletion> {
         let raw = self
             .completion_raw(system_prompt, content_parts, user_prompt)
             .await?;
+        parse_completion(raw)
+    }
 
-        let chat_response: VisionChatResponse =
-            serde_json::from_str(&raw).context("Failed to parse vision API response")?;
-
-        chat_response
-            .choices
-            .into_iter()
-            .next()
-            .and_then(|c| c.message.content)
-            .context("Vision API response missing content")
+    /// Send a multi-message conversation and return the parsed completion.
+    pub async fn chat(&self, messages: &[VisionMessage]) -> Result<VisionCompletion> {
+        let raw = self.chat_raw(messages).await?;
+        parse_completion(raw)
     }
 
     /// Same as `completion` but with exponential-backoff retry for transient errors
-    /// (HTTP 429 and 5xx).
+    /// (see [`VisionError::is_retryable`]).
     pub async fn completion_with_retry(
         &self,
         system_prompt: &str,
@@ -209,30 +772,324 @@ impl<H: HttpClient> VisionClient<H> {
         user_prompt: &str,
         max_retries: u32,
     ) -> Result<String> {
-        let mut last_err = None;
+        self.completion_detailed_with_retry(system_prompt, content_parts, user_prompt, max_retries)
+            .await
+            .map(|c| c.content)
+    }
 
-        for attempt in 0..=max_retries {
-            match self
-                .completion(system_prompt, content_parts.clone(), user_prompt)
-                .await
-            {
-                Ok(result) => return Ok(result),
-                Err(e) => {
-                    let err_str = e.to_string();
-                    let is_retryable = err_str.contains("HTTP 429") || err_str.contains("HTTP 5");
-
-                    if !is_retryable || attempt == max_retries {
-                        return Err(e);
-                    }
-
-                    let wait = std::time::Duration::from_millis(1000 * 2u64.pow(attempt));
-                    tokio::time::sleep(wait).await;
-                    last_err = Some(e);
+    /// Same as `completion_detailed` but with exponential-backoff retry for transient
+    /// errors (see [`VisionError::is_retryable`]).
+    pub async fn completion_detailed_with_retry(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+        max_retries: u32,
+    ) -> Result<VisionCompletion> {
+        let messages = single_turn(system_prompt, content_parts, user_prompt);
+        self.chat_with_retry(&messages, max_retries).await
+    }
+
+    /// Same as `chat` but with exponential-backoff retry for transient errors
+    /// (see [`VisionError::is_retryable`]).
+    pub async fn chat_with_retry(
+        &self,
+        messages: &[VisionMessage],
+        max_retries: u32,
+    ) -> Result<VisionCompletion> {
+        with_retry(max_retries, || self.chat(messages)).await
+    }
+
+    /// Send a vision completion request for a JSON answer and deserialize it
+    /// into `T`.
+    ///
+    /// The JSON Schema of `T` is appended to the system prompt and the request
+    /// asks for `response_format: {"type": "json_object"}`, so `T` should be a
+    /// struct. Code fences, reasoning and prose around the JSON are stripped.
+    /// An answer that does not deserialize is sent back with the error, up to
+    /// [`json_repairs`](VisionConfig::json_repairs) times, before failing with
+    /// [`VisionError::Parse`].
+    pub async fn completion_json<T: DeserializeOwned + JsonSchema>(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+    ) -> Result<T> {
+        self.completion_json_detailed_with_retry(system_prompt, content_parts, user_prompt, 0)
+            .await
+            .map(|(value, _)| value)
+    }
+
+    /// Same as `completion_json` but with exponential-backoff retry for
+    /// transient errors, also returning the [`VisionCompletion`] of the accepted
+    /// answer with the token usage of every attempt.
+    pub async fn completion_json_detailed_with_retry<T: DeserializeOwned + JsonSchema>(
+        &self,
+        system_prompt: &str,
+        content_parts: Vec<ContentPart>,
+        user_prompt: &str,
+        max_retries: u32,
+    ) -> Result<(T, VisionCompletion)> {
+        let system_prompt = format!(
+            "{}\n\n{}",
+            system_prompt,
+            structured::schema_instructions::<T>()
+        );
+        let mut messages = single_turn(&system_prompt, content_parts, user_prompt);
+        let mut usage: Option<VisionUsage> = None;
+        let mut repairs = 0;
+        loop {
+            let mut completion = with_retry(max_retries, || async {
+                let body =
+                    self.request_body(&messages, false, Some(ResponseFormat::json_object()))?;
+                parse_completion(self.send(body).await?)
+            })
+            .await?;
+            if let Some(u) = &completion.usage {
+                usage.get_or_insert_with(VisionUsage::default).merge(u);
+            }
+            match structured::parse_json(&completion.content) {
+                Ok(value) => {
+                    completion.usage = usage;
+                    return Ok((value, completion));
                 }
+                Err(VisionError::Parse { message, .. }) if repairs < self.config.json_repairs => {
+                    messages.push(VisionMessage::assistant(completion.content));
+                    messages.push(VisionMessage::user(vec![ContentPart::Text {
+                        text: structured::repair_prompt(&message),
+                    }]));
+                    repairs += 1;
+                }
+                Err(e) => return Err(e),
             }
         }
+    }
+}
+
+/// Run `attempt` until it succeeds, fails with a non-transient error, or has
+/// been retried `max_retries` times, with exponential backoff.
+async fn with_retry<T, F, Fut>(max_retries: u32, mut attempt: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T>>,
+{
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Ok(result) => return Ok(result),
+            Err(e) if e.is_retryable() && retries < max_retries => {
+                let wait = std::time::Duration::from_millis(1000 * 2u64.pow(retries));
+                tokio::time::sleep(wait).await;
+                retries += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Media resolved by [`VisionClient::load_media`].
+enum LoadedMedia {
+    Url(String),
+    Local(LocalMedia),
+}
+
+/// Local or in-memory media whose type and size can be checked before it is
+/// encoded.
+struct LocalMedia {
+    label: String,
+    /// "Image" or "Video", for error messages.
+    kind: &'static str,
+    declared: Option<&'static str>,
+    content: LocalContent,
+}
+
+enum LocalContent {
+    Bytes(Vec<u8>),
+    /// A file that has not been read yet, with its size and leading bytes.
+    File {
+        path: PathBuf,
+        len: u64,
+        header: Vec<u8>,
+    },
+}
+
+impl LocalMedia {
+    /// Size of the encoded media in bytes.
+    fn len(&self) -> u64 {
+        match &self.content {
+            LocalContent::Bytes(data) => data.len() as u64,
+            LocalContent::File { len, .. } => *len,
+        }
+    }
+
+    /// Type detected from the leading bytes, plus the declared type.
+    fn media_type(&self) -> MediaType {
+        let head = match &self.content {
+            LocalContent::Bytes(data) => data,
+            LocalContent::File { header, .. } => header,
+        };
+        MediaType::with_declared(self.declared, head)
+    }
+
+    /// Read the full content into memory to decode it, after checking it
+    /// against [`MAX_IMAGE_INPUT_MB`].
+    #[cfg(feature = "image")]
+    async fn into_bytes(self) -> Result<Vec<u8>> {
+        let len = self.len();
+        if len > MAX_IMAGE_INPUT_MB * 1024 * 1024 {
+            return Err(VisionError::InvalidMedia(format!(
+                "{} file too large to decode: {}MB (max {}MB)",
+                self.kind,
+                len / (1024 * 1024),
+                MAX_IMAGE_INPUT_MB
+            )));
+        }
+        match self.content {
+            LocalContent::Bytes(data) => Ok(data),
+            LocalContent::File { path, .. } => blocking(move || std::fs::read(path))
+                .await
+                .map_err(|e| file_error(self.kind, &self.label, e)),
+        }
+    }
+
+    /// Encode the content as a `data:` URL of type `mime`.
+    ///
+    /// Files are streamed through the encoder into a buffer sized for the
+    /// result, so the raw bytes are never held in memory.
+    async fn into_data_url(self, mime: &'static str) -> Result<String> {
+        match self.content {
+            LocalContent::Bytes(data) => Ok(data_url(mime, &data)),
+            LocalContent::File { path, len, .. } => blocking(move || {
+                let mut file = std::fs::File::open(path)?;
+                let mut encoder =
+                    EncoderStringWriter::from_consumer(data_url_buffer(mime, len), &STANDARD);
+                std::io::copy(&mut file, &mut encoder)?;
+                Ok(encoder.into_inner())
+            })
+            .await
+            .map_err(|e| file_error(self.kind, &self.label, e)),
+        }
+    }
+}
+
+/// Run blocking file IO on tokio's blocking thread pool.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> std::io::Result<T> + Send + 'static,
+) -> std::io::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+fn file_error(kind: &str, label: &str, err: std::io::Error) -> VisionError {
+    if err.kind() == std::io::ErrorKind::NotFound {
+        VisionError::InvalidMedia(format!("{} file not found: {}", kind, label))
+    } else {
+        VisionError::InvalidMedia(format!(
+            "Failed to read {}: {}: {}",
+            kind.to_lowercase(),
+            label,
+            err
+        ))
+    }
+}
+
+/// An empty string with the `data:<mime>;base64,` prefix and room for the
+/// encoding of `len` bytes.
+fn data_url_buffer(mime: &str, len: u64) -> String {
+    let encoded = usize::try_from(len)
+        .ok()
+        .and_then(|len| base64::encoded_len(len, true))
+        .unwrap_or(0);
+    let mut url = String::with_capacity("data:;base64,".len() + mime.len() + encoded);
+    url.push_str("data:");
+    url.push_str(mime);
+    url.push_str(";base64,");
+    url
+}
+
+/// Encode bytes as a `data:` URL of type `mime`.
+fn data_url(mime: &str, data: &[u8]) -> String {
+    let mut url = data_url_buffer(mime, data.len() as u64);
+    STANDARD.encode_string(data, &mut url);
+    url
+}
+
+/// Image types accepted from local files: sent as-is or converted.
+fn is_accepted_image(mime: &str) -> bool {
+    is_supported_image(mime) || is_convertible_image(mime)
+}
+
+/// Wrap encoded image bytes in a base64 data URL content part.
+#[cfg(feature = "image")]
+fn image_data_part(mime: &str, data: &[u8]) -> ContentPart {
+    ContentPart::ImageUrl {
+        image_url: UrlHolder {
+            url: data_url(mime, data),
+        },
+    }
+}
+
+/// Length of the text and URLs in a message, for sizing the request buffer.
+fn payload_len(message: &VisionMessage) -> usize {
+    match &message.content {
+        VisionContent::Text(text) => text.len(),
+        VisionContent::Parts(parts) => parts
+            .iter()
+            .map(|part| match part {
+                ContentPart::Text { text } => text.len(),
+                ContentPart::ImageUrl { image_url: url }
+                | ContentPart::VideoUrl { video_url: url } => url.url.len(),
+            })
+            .sum(),
+    }
+}
+
+/// Build the system + user messages for a single-turn completion.
+fn single_turn(
+    system_prompt: &str,
+    content_parts: Vec<ContentPart>,
+    user_prompt: &str,
+) -> Vec<VisionMessage> {
+    let mut user_content = content_parts;
+    user_content.push(ContentPart::Text {
+        text: user_prompt.to_string(),
+    });
+    vec![
+        VisionMessage::system(system_prompt),
+        VisionMessage::user(user_content),
+    ]
+}
+
+/// Parse a raw chat completion body into a [`VisionCompletion`].
+fn parse_completion(raw: String) -> Result<VisionCompletion> {
+    let chat_response: VisionChatResponse = match serde_json::from_str(&raw) {
+        Ok(r) => r,
+        Err(e) => {
+            return Err(VisionError::Parse {
+                message: e.to_string(),
+                body: raw,
+            })
+        }
+    };
 
-        Err(last_err.unwrap_or_else(|| anyhow::anyhow!("Max retries exceeded")))
+    let filtered = chat_response
+        .choices
+        .first()
+        .and_then(|c| c.finish_reason.as_deref())
+        == Some(FINISH_REASON_SENSITIVE);
+
+    match VisionCompletion::from_response(chat_response) {
+        Some(completion) => Ok(completion),
+        None if filtered => Err(VisionError::ContentFiltered {
+            status: None,
+            error: None,
+            body: raw,
+        }),
+        None => Err(VisionError::Parse {
+            message: "response missing content".to_string(),
+            body: raw,
+        }),
     }
 }
 
@@ -254,14 +1111,49 @@ mod tests {
         }
     }
 
+    fn test_config() -> VisionConfig {
+        VisionConfig::new("k").with_base_url("https://api.test/v4/")
+    }
+
     fn test_client() -> VisionClient<NoopHttp> {
         VisionClient::new(VisionConfig::new("test-key"), NoopHttp)
     }
 
-    #[test]
-    fn test_process_image_url_passthrough() {
+    /// Warnings logged so far by any test, through a logger installed on first use.
+    fn logged_warnings() -> Vec<String> {
+        static WARNINGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+        static INSTALL: std::sync::Once = std::sync::Once::new();
+
+        struct Capture;
+
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Warn
+            }
+
+            fn log(&self, record: &log::Record) {
+                if self.enabled(record.metadata()) {
+                    WARNINGS.lock().unwrap().push(record.args().to_string());
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        INSTALL.call_once(|| {
+            log::set_logger(&Capture).unwrap();
+            log::set_max_level(log::LevelFilter::Warn);
+        });
+        WARNINGS.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_process_image_url_passthrough() {
         let client = test_client();
-        let part = client.process_image("https://example.com/img.png").unwrap();
+        let part = client
+            .process_image("https://example.com/img.png")
+            .await
+            .unwrap();
         match part {
             ContentPart::ImageUrl { image_url } => {
                 assert_eq!(image_url.url, "https://example.com/img.png");
@@ -270,10 +1162,13 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_process_video_url_passthrough() {
+    #[tokio::test]
+    async fn test_process_video_url_passthrough() {
         let client = test_client();
-        let part = client.process_video("https://example.com/vid.mp4").unwrap();
+        let part = client
+            .process_video("https://example.com/vid.mp4")
+            .await
+            .unwrap();
         match part {
             ContentPart::VideoUrl { video_url } => {
                 assert_eq!(video_url.url, "https://example.com/vid.mp4");
@@ -282,31 +1177,34 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_process_image_file_not_found() {
+    #[tokio::test]
+    async fn test_process_image_file_not_found() {
         let client = test_client();
-        let result = client.process_image("/nonexistent/image.png");
+        let result = client.process_image("/nonexistent/image.png").await;
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
     }
 
-    #[test]
-    fn test_process_video_file_not_found() {
+    #[tokio::test]
+    async fn test_process_video_file_not_found() {
         let client = test_client();
-        let result = client.process_video("/nonexistent/video.mp4");
+        let result = client.process_video("/nonexistent/video.mp4").await;
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
     }
 
-    #[test]
-    fn test_process_image_local_file() {
+    #[tokio::test]
+    async fn test_process_image_local_file() {
         let dir = tempfile::tempdir().unwrap();
         let img_path = dir.path().join("test.png");
-        // Write a minimal valid byte sequence (not a real PNG, but enough for encoding)
-        std::fs::write(&img_path, b"fake-png-data").unwrap();
+        // A PNG signature followed by filler; only the header is inspected.
+        std::fs::write(&img_path, b"\x89PNG\r\n\x1a\nfake-png-data").unwrap();
 
         let client = test_client();
-        let part = client.process_image(img_path.to_str().unwrap()).unwrap();
+        let part = client
+            .process_image(img_path.to_str().unwrap())
+            .await
+            .unwrap();
         match part {
             ContentPart::ImageUrl { image_url } => {
                 assert!(image_url.url.starts_with("data:image/png;base64,"));
@@ -315,14 +1213,17 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_process_video_local_file() {
+    #[tokio::test]
+    async fn test_process_video_local_file() {
         let dir = tempfile::tempdir().unwrap();
         let vid_path = dir.path().join("test.mp4");
-        std::fs::write(&vid_path, b"fake-mp4-data").unwrap();
+        std::fs::write(&vid_path, b"\0\0\0\x18ftypisomfake-mp4-data").unwrap();
 
         let client = test_client();
-        let part = client.process_video(vid_path.to_str().unwrap()).unwrap();
+        let part = client
+            .process_video(vid_path.to_str().unwrap())
+            .await
+            .unwrap();
         match part {
             ContentPart::VideoUrl { video_url } => {
                 assert!(video_url.url.starts_with("data:video/mp4;base64,"));
@@ -331,15 +1232,726 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_process_video_streams_file_encoding() {
+        let dir = tempfile::tempdir().unwrap();
+        let vid_path = dir.path().join("clip.mp4");
+        // Several copy buffers' worth, not a multiple of 3, to exercise chunking.
+        let mut data = b"\0\0\0\x18ftypisom".to_vec();
+        data.extend((0..100_000u32).map(|i| (i % 251) as u8));
+        std::fs::write(&vid_path, &data).unwrap();
+
+        let client = test_client();
+        let part = client.process_video(vid_path.as_path()).await.unwrap();
+        let ContentPart::VideoUrl { video_url } = part else {
+            panic!("expected VideoUrl");
+        };
+        assert_eq!(video_url.url, data_url("video/mp4", &data));
+        assert_eq!(video_url.url.capacity(), video_url.url.len());
+    }
+
+    #[cfg(feature = "video")]
+    #[tokio::test]
+    async fn test_process_video_frames_from_bytes() {
+        use crate::video::VideoSampling;
+        use std::time::Duration;
+
+        let video =
+            crate::video::tests::synthetic_mp4(6, Duration::from_secs(1), |i| (i * 40) as u8);
+        let client = test_client();
+        let sampling = VideoSampling::every(Duration::from_secs(2));
+        let frames = client
+            .process_video_frames(MediaSource::bytes(video), &sampling)
+            .await
+            .unwrap();
+        let seconds: Vec<_> = frames.iter().map(|(t, _)| t.as_secs()).collect();
+        assert_eq!(seconds, vec![0, 2, 4]);
+        assert!(frames.iter().all(|(_, part)| matches!(
+            part,
+            ContentPart::ImageUrl { image_url } if image_url.url.starts_with("data:image/png;base64,")
+        )));
+
+        let err = client
+            .process_video_frames("https://example.com/clip.mp4", &sampling)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("remote video"));
+    }
+
+    #[cfg(feature = "video")]
+    #[tokio::test]
+    async fn test_process_video_frames_from_large_reader() {
+        use crate::video::VideoSampling;
+        use std::time::Duration;
+
+        // A video over `max_video_size_mb`, padded with a `free` box.
+        let mut video =
+            crate::video::tests::synthetic_mp4(4, Duration::from_secs(1), |i| (i * 60) as u8);
+        let padding = 3 * 1024 * 1024;
+        video.extend_from_slice(&(padding as u32 + 8).to_be_bytes());
+        video.extend_from_slice(b"free");
+        video.resize(video.len() + padding, 0);
+
+        let mut config = VisionConfig::new("test-key");
+        config.max_video_size_mb = 1;
+        let client = VisionClient::new(config, NoopHttp);
+        let sampling = VideoSampling::every(Duration::from_secs(2));
+        let frames = client
+            .process_video_frames(
+                MediaSource::reader(std::io::Cursor::new(video.clone())),
+                &sampling,
+            )
+            .await
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+
+        let err = client
+            .process_video(MediaSource::reader(std::io::Cursor::new(video)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("exceeds the 1MB limit"), "{err}");
+    }
+
     #[test]
-    fn test_process_image_unsupported_format() {
+    fn test_request_body_sized_for_payload() {
+        let client = test_client();
+        let part = ContentPart::ImageUrl {
+            image_url: UrlHolder {
+                url: data_url("image/png", &[7u8; 30_000]),
+            },
+        };
+        let messages = single_turn("sys", vec![part], "prompt");
+        let body = client.request_body(&messages, false, None).unwrap();
+        assert!(body.capacity() - body.len() <= REQUEST_OVERHEAD);
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["messages"][1]["content"][0]["type"], "image_url");
+    }
+
+    #[tokio::test]
+    async fn test_process_image_unsupported_format() {
         let dir = tempfile::tempdir().unwrap();
-        let img_path = dir.path().join("test.gif");
-        std::fs::write(&img_path, b"fake-gif").unwrap();
+        let img_path = dir.path().join("test.heic");
+        std::fs::write(&img_path, b"\0\0\0\x18ftypheic-fake-heic").unwrap();
 
         let client = test_client();
-        let result = client.process_image(img_path.to_str().unwrap());
+        let result = client.process_image(img_path.to_str().unwrap()).await;
         assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Unsupported"));
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("Unsupported"));
+        assert!(message.contains("image/heic"));
+
+        let txt_path = dir.path().join("notes.png");
+        std::fs::write(&txt_path, b"just some text").unwrap();
+        let result = client.process_image(txt_path.to_str().unwrap()).await;
+        assert!(matches!(result, Err(VisionError::InvalidMedia(_))));
+    }
+
+    #[tokio::test]
+    async fn test_process_in_memory_sources() {
+        let png = b"\x89PNG\r\n\x1a\nfake-png-data".to_vec();
+        let client = test_client();
+
+        let data_url = |part: ContentPart| match part {
+            ContentPart::ImageUrl { image_url } => image_url.url,
+            _ => panic!("expected ImageUrl"),
+        };
+        let from_bytes = data_url(
+            client
+                .process_image(MediaSource::bytes(png.clone()))
+                .await
+                .unwrap(),
+        );
+        assert!(from_bytes.starts_with("data:image/png;base64,"));
+
+        let from_reader = data_url(
+            client
+                .process_image(MediaSource::reader(std::io::Cursor::new(png.clone())))
+                .await
+                .unwrap(),
+        );
+        assert_eq!(from_reader, from_bytes);
+
+        let from_data_url = data_url(client.process_image(from_bytes.as_str()).await.unwrap());
+        assert_eq!(from_data_url, from_bytes);
+
+        let err = client
+            .process_image("data:image/png;base64,not base64!")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisionError::InvalidMedia(_)));
+    }
+
+    #[tokio::test]
+    async fn test_process_in_memory_limits() {
+        let strict = VisionClient::new(
+            VisionConfig::new("test-key").with_strict_media_types(true),
+            NoopHttp,
+        );
+        let png = b"\x89PNG\r\n\x1a\nfake-png-data".to_vec();
+        let err = strict
+            .process_image(MediaSource::bytes_with_mime(png, "image/jpeg"))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mismatch"));
+
+        let mut config = VisionConfig::new("test-key");
+        config.max_video_size_mb = 0;
+        let client = VisionClient::new(config, NoopHttp);
+        let mut mp4 = b"\0\0\0\x18ftypisom".to_vec();
+        mp4.resize(2 * 1024 * 1024, 0);
+        let err = client
+            .process_video(MediaSource::bytes(mp4))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"));
+
+        // An endless reader is cut off at the limit instead of read to the end.
+        let mut config = VisionConfig::new("test-key");
+        config.max_image_size_mb = 0;
+        let client = VisionClient::new(config, NoopHttp);
+        let err = client
+            .process_image(MediaSource::reader(tokio::io::repeat(0)))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_process_image_uses_sniffed_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let jpeg = b"\xff\xd8\xff\xe0fake-jpeg-data";
+        let mislabelled = dir.path().join("photo.png");
+        std::fs::write(&mislabelled, jpeg).unwrap();
+        let extensionless = dir.path().join("upload");
+        std::fs::write(&extensionless, jpeg).unwrap();
+
+        let client = test_client();
+        logged_warnings();
+        for path in [&mislabelled, &extensionless] {
+            match client.process_image(path.to_str().unwrap()).await.unwrap() {
+                ContentPart::ImageUrl { image_url } => {
+                    assert!(image_url.url.starts_with("data:image/jpeg;base64,"));
+                }
+                _ => panic!("expected ImageUrl with data URL"),
+            }
+        }
+        // Only the mismatch is reported; content without a declared type is not.
+        let warnings = logged_warnings();
+        let mentions = |path: &PathBuf| {
+            let path = path.to_str().unwrap();
+            warnings
+                .iter()
+                .filter(move |w| w.contains(path))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            mentions(&mislabelled),
+            [&format!(
+                "Media type mismatch: {} is declared as image/png but contains image/jpeg; \
+                 sending it as image/jpeg",
+                mislabelled.display()
+            )]
+        );
+        assert!(mentions(&extensionless).is_empty());
+
+        let strict = VisionClient::new(
+            VisionConfig::new("test-key").with_strict_media_types(true),
+            NoopHttp,
+        );
+        let err = strict
+            .process_image(mislabelled.to_str().unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("mismatch"));
+        assert!(strict
+            .process_image(extensionless.to_str().unwrap())
+            .await
+            .is_ok());
+    }
+
+    #[cfg(not(feature = "image"))]
+    #[tokio::test]
+    async fn test_process_image_convertible_requires_feature() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repro.gif");
+        std::fs::write(&path, b"GIF89a-fake-gif").unwrap();
+        let err = test_client()
+            .process_image(path.to_str().unwrap())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("`image` feature"));
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_converts_to_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = test_client();
+        for name in ["shot.webp", "scan.tiff", "capture.bmp", "still.gif"] {
+            let path = dir.path().join(name);
+            image::RgbImage::from_pixel(8, 8, image::Rgb([0, 128, 255]))
+                .save(&path)
+                .unwrap();
+            match client.process_image(path.to_str().unwrap()).await.unwrap() {
+                ContentPart::ImageUrl { image_url } => {
+                    assert!(
+                        image_url.url.starts_with("data:image/png;base64,"),
+                        "{name}"
+                    );
+                }
+                _ => panic!("expected ImageUrl with data URL"),
+            }
+        }
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_frames_expands_gif() {
+        use image::codecs::gif::GifEncoder;
+        use image::{Delay, Frame, Rgba, RgbaImage};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repro.gif");
+        let mut encoder = GifEncoder::new(std::fs::File::create(&path).unwrap());
+        for shade in 0..5u8 {
+            let frame = RgbaImage::from_pixel(4, 4, Rgba([shade * 50, 0, 0, 255]));
+            encoder
+                .encode_frame(Frame::from_parts(
+                    frame,
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(100, 1),
+                ))
+                .unwrap();
+        }
+        drop(encoder);
+
+        let client = test_client();
+        let frames = client
+            .process_image_frames(path.to_str().unwrap(), 3)
+            .await
+            .unwrap();
+        assert_eq!(frames.len(), 3);
+        let all = client
+            .process_image_frames(path.to_str().unwrap(), 10)
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 5);
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_rejects_oversized_input_before_decoding() {
+        // A sparse file: a GIF header, then nothing but its length.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("huge.gif");
+        let file = std::fs::File::create(&path).unwrap();
+        std::io::Write::write_all(&mut &file, b"GIF89a").unwrap();
+        file.set_len((MAX_IMAGE_INPUT_MB + 1) * 1024 * 1024)
+            .unwrap();
+
+        let client = test_client();
+        let path = path.to_str().unwrap();
+        let err = client.process_image(path).await.unwrap_err();
+        assert!(err.to_string().contains("too large to decode"), "{err}");
+        let err = client.process_image_frames(path, 4).await.unwrap_err();
+        assert!(err.to_string().contains("too large to decode"), "{err}");
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_prepare_image_reports_scale() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wide.png");
+        image::RgbImage::from_pixel(300, 100, image::Rgb([200, 10, 10]))
+            .save(&path)
+            .unwrap();
+
+        let client = VisionClient::new(
+            VisionConfig::new("test-key")
+                .with_image_resize(crate::ImageResize::default().with_max_edge(150)),
+            NoopHttp,
+        );
+        let (part, scale) = client.prepare_image(path.to_str().unwrap()).await.unwrap();
+        assert_eq!((scale.width, scale.height), (150, 50));
+        assert_eq!(scale.factor(), 0.5);
+        match part {
+            ContentPart::ImageUrl { image_url } => {
+                assert!(image_url.url.starts_with("data:image/png;base64,"));
+            }
+            _ => panic!("expected ImageUrl with data URL"),
+        }
+        assert!(client.process_image(path.to_str().unwrap()).await.is_ok());
+        assert!(client
+            .prepare_image("https://example.com/a.png")
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_resizes_large_reader() {
+        // Noise does not compress, so this PNG is over 4MB.
+        let mut state = 0x2545_f491_u32;
+        let noise = image::RgbImage::from_fn(1200, 1200, |_, _| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            image::Rgb([state as u8, (state >> 8) as u8, (state >> 16) as u8])
+        });
+        let mut png = Vec::new();
+        noise
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        assert!(png.len() > 2 * 1024 * 1024);
+
+        let mut config = VisionConfig::new("test-key");
+        config.max_image_size_mb = 1;
+        let client = VisionClient::new(config.clone(), NoopHttp);
+        let err = client
+            .process_image(MediaSource::reader(std::io::Cursor::new(png.clone())))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("too large"), "{err}");
+
+        let client = VisionClient::new(
+            config.with_image_resize(crate::ImageResize::default().with_max_edge(200)),
+            NoopHttp,
+        );
+        let part = client
+            .process_image(MediaSource::reader(std::io::Cursor::new(png)))
+            .await
+            .unwrap();
+        assert!(matches!(
+            part,
+            ContentPart::ImageUrl { image_url } if image_url.url.starts_with("data:image/")
+        ));
+    }
+
+    #[cfg(feature = "image")]
+    fn png_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::RgbImage::from_pixel(width, height, image::Rgb([20, 120, 200]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut data),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        data
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_tiles() {
+        let client = test_client();
+        let tiling = crate::ImageTiling::default()
+            .with_tile_size(200, 200)
+            .with_overlap(50);
+
+        let tiles = client
+            .process_image_tiles(MediaSource::bytes(png_bytes(150, 700)), &tiling)
+            .await
+            .unwrap();
+        assert_eq!(tiles.len(), 5);
+        assert_eq!(tiles[4].0.y + tiles[4].0.height, 700);
+        assert!(tiles.iter().all(|(r, _)| (r.width, r.height) == (150, 200)));
+
+        let small = png_bytes(100, 100);
+        let tiles = client
+            .process_image_tiles(MediaSource::bytes(small.clone()), &tiling)
+            .await
+            .unwrap();
+        assert_eq!(tiles.len(), 1);
+        match &tiles[0].1 {
+            ContentPart::ImageUrl { image_url } => {
+                assert_eq!(image_url.url, data_url("image/png", &small))
+            }
+            _ => panic!("expected ImageUrl"),
+        }
+
+        assert!(client
+            .process_image_tiles("https://example.com/a.png", &tiling)
+            .await
+            .is_err());
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_process_image_tile_pairs_uneven_heights() {
+        let client = test_client();
+        let tiling = crate::ImageTiling::default()
+            .with_tile_size(200, 200)
+            .with_overlap(50);
+        let pairs = client
+            .process_image_tile_pairs(
+                MediaSource::bytes(png_bytes(150, 700)),
+                MediaSource::bytes(png_bytes(150, 300)),
+                &tiling,
+            )
+            .await
+            .unwrap();
+        assert_eq!(pairs.len(), 5);
+        assert!(pairs.iter().all(|(_, [expected, _])| expected.is_some()));
+        let present: Vec<bool> = pairs.iter().map(|(_, [_, a])| a.is_some()).collect();
+        assert_eq!(present, [true, true, true, false, false]);
+    }
+
+    #[cfg(feature = "image")]
+    #[tokio::test]
+    async fn test_annotate_image() {
+        let client = test_client();
+        let annotations = [crate::annotate::Annotation::new(
+            "Header",
+            crate::grounding::BoundingBox::new(0.0, 0.0, 1000.0, 250.0),
+        )];
+        let png = client
+            .annotate_image(MediaSource::bytes(png_bytes(120, 80)), &annotations)
+            .await
+            .unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+        let (width, height) = crate::preprocess::image_dimensions(&png).unwrap();
+        assert_eq!(width, 120);
+        assert!(height > 80);
+
+        assert!(client
+            .annotate_image("https://example.com/a.png", &annotations)
+            .await
+            .is_err());
+    }
+
+    struct MockHttp {
+        status: u16,
+        body: &'static str,
+        sent: std::sync::Mutex<Option<serde_json::Value>>,
+    }
+
+    impl HttpClient for MockHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            body: &[u8],
+        ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            *self.sent.lock().unwrap() = Some(serde_json::from_slice(body)?);
+            Ok(HttpResponse {
+                status: self.status,
+                body: self.body.to_string(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completion_stream_sends_stream_flag() {
+        let http = MockHttp {
+            status: 200,
+            body: "data: {\"choices\":[{\"delta\":{\"content\":\"hi\"}}]}\n\ndata: [DONE]\n\n",
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let mut stream = client
+            .completion_stream("sys", vec![], "prompt")
+            .await
+            .unwrap();
+        let choice = stream.next().await.unwrap().unwrap();
+        assert_eq!(choice.delta.content.as_deref(), Some("hi"));
+        assert!(stream.next().await.is_none());
+
+        let sent = client.http.sent.lock().unwrap().take().unwrap();
+        assert_eq!(sent["stream"], true);
+        assert_eq!(sent["messages"][1]["content"][0]["text"], "prompt");
+    }
+
+    #[tokio::test]
+    async fn test_completion_stream_http_error() {
+        let http = MockHttp {
+            status: 429,
+            body: "{\"error\":{\"code\":\"1302\"}}",
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let err = client
+            .completion_stream("sys", vec![], "prompt")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, VisionError::RateLimited { .. }));
+        assert_eq!(err.api_error().unwrap().code.as_deref(), Some("1302"));
+    }
+
+    #[tokio::test]
+    async fn test_completion_detailed() {
+        let http = MockHttp {
+            status: 200,
+            body: include_str!("../examples/responses/20260213_125911_analyze_image.json"),
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let completion = client
+            .completion_detailed("sys", vec![], "prompt")
+            .await
+            .unwrap();
+        assert_eq!(completion.model.as_deref(), Some("glm-4.6v"));
+        assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+        assert!(completion.reasoning_content.is_some());
+        let usage = completion.usage.unwrap();
+        assert_eq!(usage.total_tokens, 1642);
+        assert_eq!(usage.reasoning_tokens(), 61);
+        assert_eq!(usage.cached_tokens(), 601);
+
+        let sent = client.http.sent.lock().unwrap().take().unwrap();
+        assert_eq!(sent["stream"], false);
+    }
+
+    #[tokio::test]
+    async fn test_completion_requires_base_url() {
+        let client = test_client();
+        let err = client
+            .completion("sys", vec![], "prompt")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisionError::Config(_)));
+    }
+
+    #[tokio::test]
+    async fn test_completion_retry_stops_on_client_error() {
+        let http = MockHttp {
+            status: 400,
+            body: "{\"error\":{\"code\":\"1210\",\"message\":\"bad params\"}}",
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let err = client
+            .completion_with_retry("sys", vec![], "prompt", 3)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisionError::Http { status: 400, .. }));
+        assert!(!err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_completion_content_filtered() {
+        let http = MockHttp {
+            status: 200,
+            body:
+                "{\"choices\":[{\"finish_reason\":\"sensitive\",\"message\":{\"content\":null}}]}",
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let err = client
+            .completion("sys", vec![], "prompt")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            VisionError::ContentFiltered { status: None, .. }
+        ));
+        assert!(err.body().unwrap().contains("sensitive"));
+    }
+
+    #[tokio::test]
+    async fn test_completion_invalid_json() {
+        let http = MockHttp {
+            status: 200,
+            body: "<html>oops</html>",
+            sent: Default::default(),
+        };
+        let client = VisionClient::new(test_config(), http);
+        let err = client
+            .completion("sys", vec![], "prompt")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisionError::Parse { .. }));
+        assert_eq!(err.body(), Some("<html>oops</html>"));
+    }
+
+    /// Answers each request with the next body in order, recording what was sent.
+    struct ScriptedHttp {
+        bodies: std::sync::Mutex<std::collections::VecDeque<String>>,
+        sent: std::sync::Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl ScriptedHttp {
+        fn answering(contents: &[&str]) -> Self {
+            let bodies = contents
+                .iter()
+                .map(|content| {
+                    serde_json::json!({
+                        "choices": [{"finish_reason": "stop", "message": {"content": content}}],
+                        "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+                    })
+                    .to_string()
+                })
+                .collect();
+            Self {
+                bodies: std::sync::Mutex::new(bodies),
+                sent: Default::default(),
+            }
+        }
+    }
+
+    impl HttpClient for ScriptedHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            body: &[u8],
+        ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            self.sent
+                .lock()
+                .unwrap()
+                .push(serde_json::from_slice(body)?);
+            let body = self
+                .bodies
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or("no more responses")?;
+            Ok(HttpResponse { status: 200, body })
+        }
+    }
+
+    #[derive(Debug, serde::Deserialize, JsonSchema)]
+    struct Verdict {
+        passed: bool,
+        reason: String,
+    }
+
+    #[tokio::test]
+    async fn test_completion_json_repairs_invalid_answer() {
+        let http = ScriptedHttp::answering(&[
+            "{\"passed\": \"yes\"}",
+            "```json\n{\"passed\": true, \"reason\": \"identical\"}\n```",
+        ]);
+        let client = VisionClient::new(test_config(), http);
+        let (verdict, completion) = client
+            .completion_json_detailed_with_retry::<Verdict>("sys", vec![], "prompt", 0)
+            .await
+            .unwrap();
+        assert!(verdict.passed);
+        assert_eq!(verdict.reason, "identical");
+        assert_eq!(completion.usage.unwrap().total_tokens, 30);
+
+        let sent = client.http.sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0]["response_format"]["type"], "json_object");
+        let system = sent[0]["messages"][0]["content"].as_str().unwrap();
+        assert!(system.starts_with("sys\n\n<json_output>"));
+        let messages = sent[1]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[2]["role"], "assistant");
+        let repair = messages[3]["content"][0]["text"].as_str().unwrap();
+        assert!(repair.contains("passed: invalid type"));
+    }
+
+    #[tokio::test]
+    async fn test_completion_json_without_repairs() {
+        let http = ScriptedHttp::answering(&["{\"passed\": true}"]);
+        let client = VisionClient::new(test_config().with_json_repairs(0), http);
+        let err = client
+            .completion_json::<Verdict>("sys", vec![], "prompt")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, VisionError::Parse { .. }));
+        assert_eq!(client.http.sent.lock().unwrap().len(), 1);
     }
 }
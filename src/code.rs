@@ -0,0 +1,573 @@
+//! Code read from screenshots by [`extract_code`](crate::tools::extract_code).
+//!
+//! [`extract_blocks`] pulls the fenced code blocks out of a model answer,
+//! removing a line-number gutter copied from the editor and naming the
+//! language from the fence tag or, failing that, from the code itself.
+//! [`Syntax::check`] then parses Rust (with `syn`), JSON, TOML and YAML
+//! locally, so that transcription mistakes such as a misread bracket can be
+//! sent back to the model with their exact position. Other languages are not
+//! checked.
+
+use std::fmt;
+
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+
+use crate::diagram::fenced_blocks;
+
+/// A block of code transcribed from an image.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodeBlock {
+    /// Language in lowercase (e.g. "rust", "python"), if known.
+    pub language: Option<String>,
+    /// The code, without line numbers.
+    pub code: String,
+    /// The syntax error left in the code after the repair attempts, if its
+    /// language has a local parser (see [`Syntax`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub syntax_error: Option<SyntaxError>,
+}
+
+impl CodeBlock {
+    /// The local parser for the block's language, if there is one.
+    pub fn syntax(&self) -> Option<Syntax> {
+        self.language.as_deref().and_then(Syntax::for_language)
+    }
+
+    /// Parse the code with [`Self::syntax`]; code in other languages passes.
+    pub fn check(&self) -> Result<(), SyntaxError> {
+        match self.syntax() {
+            Some(syntax) => syntax.check(&self.code),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A language with a local parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Syntax {
+    /// Rust source: a file, or statements as in a function body.
+    Rust,
+    Json,
+    Toml,
+    /// YAML, possibly with several `---` separated documents.
+    Yaml,
+}
+
+impl Syntax {
+    /// Language name, as in [`CodeBlock::language`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Syntax::Rust => "rust",
+            Syntax::Json => "json",
+            Syntax::Toml => "toml",
+            Syntax::Yaml => "yaml",
+        }
+    }
+
+    /// The parser for a language name or fence tag, case-insensitively.
+    pub fn for_language(language: &str) -> Option<Self> {
+        match normalize_language(language)?.as_str() {
+            "rust" => Some(Syntax::Rust),
+            "json" => Some(Syntax::Json),
+            "toml" => Some(Syntax::Toml),
+            "yaml" => Some(Syntax::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Parse `code`, returning the position of the first error.
+    pub fn check(&self, code: &str) -> Result<(), SyntaxError> {
+        match self {
+            Syntax::Rust => check_rust(code),
+            Syntax::Json => serde_json::from_str::<IgnoredAny>(code)
+                .map(drop)
+                .map_err(|e| {
+                    SyntaxError::new(e.line(), e.column(), without_position(&e.to_string()))
+                }),
+            Syntax::Toml => toml::from_str::<toml::Table>(code).map(drop).map_err(|e| {
+                let (line, column) = match e.span() {
+                    Some(span) => line_column(code, span.start),
+                    None => end_position(code),
+                };
+                SyntaxError::new(line, column, e.message().trim())
+            }),
+            Syntax::Yaml => check_yaml(code),
+        }
+    }
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Where and why code failed to parse.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyntaxError {
+    /// Line of the error, starting at 1.
+    pub line: usize,
+    /// Column of the error in characters, starting at 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl SyntaxError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        Self {
+            line: line.max(1),
+            column: column.max(1),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// The code blocks in a model answer.
+///
+/// Takes every ``` fenced block, or the whole answer if there are none;
+/// reasoning before a closing `</think>` is dropped. Line-number gutters are
+/// removed with [`strip_line_numbers`], and blocks without a recognized fence
+/// tag get the language from [`detect_language`].
+pub fn extract_blocks(text: &str) -> Vec<CodeBlock> {
+    let text = match text.rfind("</think>") {
+        Some(end) => &text[end + "</think>".len()..],
+        None => text,
+    };
+    let mut blocks = fenced_blocks(text);
+    if blocks.is_empty() {
+        blocks.push(("", text));
+    }
+    blocks
+        .into_iter()
+        .filter_map(|(tag, body)| {
+            let code = strip_line_numbers(trim_blank_lines(body));
+            if code.trim().is_empty() {
+                return None;
+            }
+            let language =
+                normalize_language(tag).or_else(|| detect_language(&code).map(String::from));
+            Some(CodeBlock {
+                language,
+                code,
+                syntax_error: None,
+            })
+        })
+        .collect()
+}
+
+/// The canonical lowercase name for a language name or fence tag (e.g. "rs"
+/// and "Rust" give "rust", "yml" gives "yaml"), or `None` for plain text.
+pub fn normalize_language(tag: &str) -> Option<String> {
+    let tag = tag.trim().trim_start_matches('.').to_lowercase();
+    let name = match tag.as_str() {
+        "" | "text" | "txt" | "plain" | "plaintext" => return None,
+        "rs" => "rust",
+        "py" | "py3" | "python3" => "python",
+        "js" | "jsx" | "mjs" | "cjs" | "node" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "yml" => "yaml",
+        "sh" | "bash" | "zsh" | "console" | "shell-session" => "shell",
+        "golang" => "go",
+        "c++" | "cxx" | "cc" | "hpp" => "cpp",
+        "c#" | "cs" => "csharp",
+        "kt" | "kts" => "kotlin",
+        "rb" => "ruby",
+        "ps1" | "pwsh" => "powershell",
+        "htm" => "html",
+        "dockerfile" => "docker",
+        other => other,
+    };
+    Some(name.to_string())
+}
+
+/// Markers of a language, as `^prefix` for the start of a trimmed line or a
+/// substring anywhere in it.
+const LANGUAGE_MARKERS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "^fn ",
+            "^pub fn ",
+            "^pub(crate) ",
+            "^impl ",
+            "^impl<",
+            "^use std::",
+            "^mod ",
+            "let mut ",
+            "#[derive(",
+            "&self",
+            "&mut ",
+            "println!(",
+            "::new(",
+            "-> Result<",
+            "Some(",
+            "Ok(",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "^def ",
+            "^import ",
+            "^from ",
+            "^class ",
+            "^elif ",
+            "self.",
+            "print(",
+            "__init__",
+            "^async def ",
+        ],
+    ),
+    ("go", &["^package ", "^func ", ":= ", "fmt.", "err != nil"]),
+    (
+        "typescript",
+        &[
+            "^interface ",
+            "^export interface ",
+            ": string",
+            ": number",
+            "^type ",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "^const ",
+            "^let ",
+            "^function ",
+            "=> {",
+            "console.log(",
+            "require(",
+            "^export ",
+            "===",
+        ],
+    ),
+    (
+        "java",
+        &[
+            "^public class ",
+            "^import java.",
+            "System.out.",
+            "^@Override",
+            "^package ",
+        ],
+    ),
+    (
+        "cpp",
+        &["^#include <", "std::cout", "^template<", "^namespace "],
+    ),
+    ("c", &["^#include ", "printf(", "malloc("]),
+    (
+        "shell",
+        &["^#!/bin/", "^$ ", "^echo ", "^export ", "^sudo ", "^cd "],
+    ),
+    (
+        "sql",
+        &[
+            "^SELECT ",
+            "^INSERT INTO ",
+            "^CREATE TABLE ",
+            "^UPDATE ",
+            " FROM ",
+            " WHERE ",
+        ],
+    ),
+    ("html", &["^<!DOCTYPE", "^<html", "^<div", "</div>"]),
+];
+
+/// Guess the language of `code` from keywords and syntax, or by parsing it as
+/// JSON, TOML or YAML when it looks like data rather than a program.
+pub fn detect_language(code: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = code.lines().map(str::trim).collect();
+    let score = |markers: &[&str]| {
+        markers
+            .iter()
+            .filter(|marker| match marker.strip_prefix('^') {
+                Some(prefix) => lines.iter().any(|l| l.starts_with(prefix)),
+                None => lines.iter().any(|l| l.contains(*marker)),
+            })
+            .count()
+    };
+    let best = LANGUAGE_MARKERS
+        .iter()
+        .map(|(language, markers)| (*language, score(markers)))
+        .fold(
+            None,
+            |best: Option<(&str, usize)>, (language, n)| match best {
+                Some((_, m)) if m >= n => best,
+                _ if n > 0 => Some((language, n)),
+                _ => best,
+            },
+        );
+    if let Some((language, _)) = best {
+        return Some(language);
+    }
+
+    let trimmed = code.trim_start();
+    if trimmed.starts_with(['{', '[']) && Syntax::Json.check(code).is_ok() {
+        return Some("json");
+    }
+    let toml_like = lines
+        .iter()
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .all(|l| (l.starts_with('[') && l.ends_with(']')) || l.contains(" = "));
+    if toml_like && Syntax::Toml.check(code).is_ok() {
+        return Some("toml");
+    }
+    match serde_yaml_ng::from_str::<serde_yaml_ng::Value>(code) {
+        Ok(serde_yaml_ng::Value::Mapping(_) | serde_yaml_ng::Value::Sequence(_)) => Some("yaml"),
+        _ => None,
+    }
+}
+
+/// Remove a line-number gutter copied from an editor or diff view.
+///
+/// Applies only when every non-blank line starts with a number, numbered
+/// consecutively; numbers may skip only across blank lines. A `|`, `│` or `:`
+/// separator after every number is removed too, as is the indentation the
+/// gutter adds in front of every line. Other code is returned unchanged.
+pub fn strip_line_numbers(code: &str) -> String {
+    // (line after the number, line after the separator) of each line.
+    let mut lines: Vec<(&str, Option<&str>)> = Vec::new();
+    let mut expected: Option<u64> = None;
+    let mut after_blank = false;
+    for line in code.lines() {
+        if line.trim().is_empty() {
+            lines.push(("", Some("")));
+            after_blank = true;
+            continue;
+        }
+        let Some((number, rest)) = split_gutter(line) else {
+            return code.to_string();
+        };
+        match expected {
+            Some(e) if number == e || (after_blank && number > e) => {}
+            Some(_) => return code.to_string(),
+            None => {}
+        }
+        expected = Some(number + 1);
+        after_blank = false;
+        let separated = rest
+            .trim_start_matches([' ', '\t'])
+            .strip_prefix(['|', '│', ':']);
+        lines.push((rest, separated));
+    }
+    if lines.iter().filter(|(rest, _)| !rest.is_empty()).count() < 2 {
+        return code.to_string();
+    }
+
+    let separated = lines.iter().all(|(_, separated)| separated.is_some());
+    let lines: Vec<&str> = lines
+        .into_iter()
+        .map(|(rest, after)| match after {
+            Some(after) if separated => after,
+            _ => rest,
+        })
+        .collect();
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or("").trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The line number and the rest of a line starting with a gutter.
+fn split_gutter(line: &str) -> Option<(u64, &str)> {
+    let trimmed = line.trim_start();
+    let rest = trimmed.trim_start_matches(|c: char| c.is_ascii_digit());
+    let digits = &trimmed[..trimmed.len() - rest.len()];
+    let separated = rest.is_empty() || rest.starts_with([' ', '\t', '|', '│', ':']);
+    if digits.is_empty() || digits.len() > 6 || !separated {
+        return None;
+    }
+    Some((digits.parse().ok()?, rest))
+}
+
+/// `text` without leading blank lines and trailing whitespace, keeping the
+/// indentation of the first line.
+fn trim_blank_lines(text: &str) -> &str {
+    let text = text.trim_end();
+    let start = text.len() - text.trim_start().len();
+    let start = text[..start].rfind('\n').map_or(0, |newline| newline + 1);
+    &text[start..]
+}
+
+/// Parse Rust as a file or, failing that, as the statements of a block, and
+/// report the error of whichever got further.
+fn check_rust(code: &str) -> Result<(), SyntaxError> {
+    let file_error = match syn::parse_file(code) {
+        Ok(_) => return Ok(()),
+        Err(e) => rust_error(code, &e, 0),
+    };
+    let block_error = match syn::parse_str::<syn::Block>(&format!("{{\n{}\n}}", code)) {
+        Ok(_) => return Ok(()),
+        Err(e) => rust_error(code, &e, 1),
+    };
+    if (block_error.line, block_error.column) > (file_error.line, file_error.column) {
+        Err(block_error)
+    } else {
+        Err(file_error)
+    }
+}
+
+/// A `syn` error, with its line moved up by the `offset` lines wrapped around
+/// the code and positions past the code clamped to its end.
+fn rust_error(code: &str, error: &syn::Error, offset: usize) -> SyntaxError {
+    let start = error.span().start();
+    let end = end_position(code);
+    let position = match start.line.checked_sub(offset) {
+        Some(line) if line >= 1 && line <= end.0 => (line, start.column + 1),
+        _ => end,
+    };
+    let message = match error.to_string().as_str() {
+        // A lexer error, at the unmatched delimiter or literal.
+        "cannot parse string into token stream" => {
+            "unbalanced delimiter or unterminated literal".to_string()
+        }
+        message => message.to_string(),
+    };
+    SyntaxError::new(position.0, position.1, message)
+}
+
+fn check_yaml(code: &str) -> Result<(), SyntaxError> {
+    for document in serde_yaml_ng::Deserializer::from_str(code) {
+        if let Err(e) = IgnoredAny::deserialize(document) {
+            let (line, column) = e
+                .location()
+                .map_or_else(|| end_position(code), |l| (l.line(), l.column()));
+            return Err(SyntaxError::new(
+                line,
+                column,
+                without_position(&e.to_string()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A parser message without the " at line N column M" it ends with.
+fn without_position(message: &str) -> &str {
+    message.split(" at line ").next().unwrap_or(message).trim()
+}
+
+/// The 1-based line and column of byte `offset` in `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+/// The position just after the last character of `text`.
+fn end_position(text: &str) -> (usize, usize) {
+    line_column(text.trim_end(), text.trim_end().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_line_numbers() {
+        let code = " 9 fn main() {\n10     let x = 1;\n\n14 }\n";
+        assert_eq!(strip_line_numbers(code), "fn main() {\n    let x = 1;\n\n}");
+        assert_eq!(strip_line_numbers("1 | a:\n2 |   b: 1"), "a:\n  b: 1");
+        // Not a gutter: numbers out of sequence, or lines without a number.
+        assert_eq!(strip_line_numbers("10 20\n30 40"), "10 20\n30 40");
+        assert_eq!(strip_line_numbers("1 x\nfoo"), "1 x\nfoo");
+        assert_eq!(strip_line_numbers("1 x"), "1 x");
+    }
+
+    #[test]
+    fn test_languages() {
+        assert_eq!(normalize_language("RS").as_deref(), Some("rust"));
+        assert_eq!(normalize_language("yml").as_deref(), Some("yaml"));
+        assert_eq!(normalize_language("text"), None);
+        assert_eq!(Syntax::for_language("JSON"), Some(Syntax::Json));
+        assert_eq!(Syntax::for_language("python"), None);
+
+        assert_eq!(
+            detect_language("fn main() {\n    println!(\"hi\");\n}"),
+            Some("rust")
+        );
+        assert_eq!(detect_language("def f(x):\n    return x"), Some("python"));
+        assert_eq!(detect_language("{\"a\": [1, 2]}"), Some("json"));
+        assert_eq!(detect_language("[package]\nname = \"demo\""), Some("toml"));
+        assert_eq!(
+            detect_language("services:\n  web:\n    image: nginx"),
+            Some("yaml")
+        );
+        assert_eq!(detect_language("just some words"), None);
+    }
+
+    #[test]
+    fn test_check_reports_positions() {
+        let rust = "fn main() {\n    let x = 1 +;\n}";
+        let error = Syntax::Rust.check(rust).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "line 2, column 16: expected an expression"
+        );
+        let error = Syntax::Rust.check("fn main() {\n    foo(;\n}").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 1));
+        assert_eq!(
+            error.message,
+            "unbalanced delimiter or unterminated literal"
+        );
+        assert!(Syntax::Rust.check("let x = 1;\nprintln!(\"{x}\");").is_ok());
+        assert_eq!(Syntax::Rust.check("fn main() {").unwrap_err().line, 1);
+
+        let error = Syntax::Json
+            .check("{\n  \"a\": 1,\n  \"b\" 2\n}")
+            .unwrap_err();
+        assert_eq!((error.line, error.column), (3, 7));
+        assert_eq!(error.message, "expected `:`");
+
+        let error = Syntax::Toml.check("[a]\nb = \n").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(Syntax::Toml.check("[a]\nb = 1").is_ok());
+
+        let error = Syntax::Yaml.check("a: 1\n---\nb: [1, 2\n").unwrap_err();
+        assert!(error.line >= 3, "{error}");
+        assert!(Syntax::Yaml.check("a: 1\n---\nb: 2").is_ok());
+    }
+
+    #[test]
+    fn test_extract_blocks() {
+        let answer = "</think>Here:\n```rs\n\n1 fn a() {}\n2 fn b() {}\n```\n\
+                      and\n```\n{\"k\": true}\n```\n```text\nplain\n```";
+        let blocks = extract_blocks(answer);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].code, "fn a() {}\nfn b() {}");
+        assert_eq!(blocks[1].language.as_deref(), Some("json"));
+        assert_eq!(blocks[2].language, None);
+        assert!(blocks.iter().all(|b| b.check().is_ok()));
+
+        let bare = extract_blocks("    let x = 1;\n");
+        assert_eq!(bare[0].code, "    let x = 1;");
+        assert!(extract_blocks("```\n\n```").is_empty());
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::VisionError;
+
 /// Known API providers for GLM-4V.
 ///
 /// Use `Provider::base_url()` to get the corresponding endpoint,
@@ -67,6 +69,48 @@ pub struct VisionConfig {
     /// Enable thinking/reasoning mode (default: true)
     #[serde(default = "default_thinking_enabled")]
     pub thinking_enabled: bool,
+
+    /// Downscale and recompress local images that exceed the size limit or
+    /// dimensions instead of rejecting them (default: none). Requires the `image`
+    /// feature; ignored without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_resize: Option<ImageResize>,
+
+    /// Split large local screenshots into overlapping tiles for `extract_text`
+    /// and `ui_diff_check` (default: none). Requires the `image` feature; ignored
+    /// without it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tiling: Option<ImageTiling>,
+
+    /// How many times [`completion_json`](crate::VisionClient::completion_json)
+    /// re-asks the model after an answer that does not match the schema (default: 2).
+    #[serde(default = "default_json_repairs")]
+    pub json_repairs: u32,
+
+    /// Reject local media whose extension disagrees with its content (default: false).
+    ///
+    /// When false, the type detected from the file content is used and the
+    /// mismatch is logged as a warning.
+    #[serde(default)]
+    pub strict_media_types: bool,
+
+    /// Proxy URL for API requests, e.g. `http://proxy.internal:3128` (default: none).
+    ///
+    /// Only used by the bundled HTTP clients in the `backends` module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Path to a PEM file with extra trusted root certificates (default: none).
+    ///
+    /// Only used by the bundled HTTP clients in the `backends` module.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub root_ca_path: Option<String>,
+
+    /// Disable TLS certificate verification (default: false). Never use in production.
+    ///
+    /// Only used by the bundled HTTP clients in the `backends` module.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
 }
 
 impl VisionConfig {
@@ -86,6 +130,13 @@ impl VisionConfig {
             max_image_size_mb: default_max_image_size_mb(),
             max_video_size_mb: default_max_video_size_mb(),
             thinking_enabled: default_thinking_enabled(),
+            image_resize: None,
+            tiling: None,
+            json_repairs: default_json_repairs(),
+            strict_media_types: false,
+            proxy: None,
+            root_ca_path: None,
+            danger_accept_invalid_certs: false,
         }
     }
 
@@ -119,6 +170,174 @@ impl VisionConfig {
         self
     }
 
+    /// Downscale and recompress oversized local images (requires the `image` feature).
+    pub fn with_image_resize(mut self, resize: ImageResize) -> Self {
+        self.image_resize = Some(resize);
+        self
+    }
+
+    /// Tile large local screenshots for text extraction and UI diffs (requires
+    /// the `image` feature).
+    pub fn with_tiling(mut self, tiling: ImageTiling) -> Self {
+        self.tiling = Some(tiling);
+        self
+    }
+
+    /// Set how many times a JSON answer that fails validation is repaired.
+    pub fn with_json_repairs(mut self, repairs: u32) -> Self {
+        self.json_repairs = repairs;
+        self
+    }
+
+    /// Reject local media files whose extension disagrees with their content.
+    pub fn with_strict_media_types(mut self, strict: bool) -> Self {
+        self.strict_media_types = strict;
+        self
+    }
+
+    /// Set the request timeout in seconds.
+    pub fn with_timeout_secs(mut self, secs: u64) -> Self {
+        self.timeout_secs = secs;
+        self
+    }
+
+    /// Route API requests through a proxy.
+    pub fn with_proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Trust the root certificates in the given PEM file.
+    pub fn with_root_ca(mut self, path: impl Into<String>) -> Self {
+        self.root_ca_path = Some(path.into());
+        self
+    }
+
+    /// Build a config from environment variables.
+    ///
+    /// | Variable | Field |
+    /// |----------|-------|
+    /// | `GLM_VISION_API_KEY` (or `Z_AI_API_KEY`) | `api_key` (required) |
+    /// | `GLM_VISION_PROVIDER` (`zhipu`, `zai`, `zai-coding`) | `base_url` via [`Provider`] |
+    /// | `Z_AI_MODE` (`ZHIPU`, `ZAI`) | `base_url`, as used by `@z_ai/mcp-server` |
+    /// | `GLM_VISION_BASE_URL` | `base_url` (overrides provider) |
+    /// | `GLM_VISION_MODEL` | `model` |
+    /// | `GLM_VISION_TEMPERATURE` | `temperature` |
+    /// | `GLM_VISION_TOP_P` | `top_p` |
+    /// | `GLM_VISION_MAX_TOKENS` | `max_tokens` |
+    /// | `GLM_VISION_TIMEOUT_SECS` | `timeout_secs` |
+    /// | `GLM_VISION_MAX_IMAGE_SIZE_MB` | `max_image_size_mb` |
+    /// | `GLM_VISION_MAX_VIDEO_SIZE_MB` | `max_video_size_mb` |
+    /// | `GLM_VISION_THINKING` (`true`/`false`) | `thinking_enabled` |
+    /// | `GLM_VISION_IMAGE_MAX_EDGE` | `image_resize` (enables it with this `max_edge`) |
+    /// | `GLM_VISION_TILING` (`multi-image`, `sequential`) | `tiling` (enables it in this mode) |
+    /// | `GLM_VISION_JSON_REPAIRS` | `json_repairs` |
+    /// | `GLM_VISION_STRICT_MEDIA_TYPES` (`true`/`false`) | `strict_media_types` |
+    /// | `GLM_VISION_PROXY` | `proxy` |
+    /// | `GLM_VISION_ROOT_CA` | `root_ca_path` |
+    ///
+    /// Either a provider or a base URL must be set.
+    pub fn from_env() -> Result<Self, VisionError> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    /// Like [`from_env`](Self::from_env), but looks variables up through `var`,
+    /// e.g. to layer command-line values over the environment.
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, VisionError> {
+        let var = |key: &str| var(key).filter(|v| !v.trim().is_empty());
+
+        let api_key = var("GLM_VISION_API_KEY")
+            .or_else(|| var("Z_AI_API_KEY"))
+            .ok_or_else(|| {
+                VisionError::Config("GLM_VISION_API_KEY (or Z_AI_API_KEY) is not set".to_string())
+            })?;
+        let mut config = Self::new(api_key);
+
+        if let Some(provider) = var("GLM_VISION_PROVIDER") {
+            let provider: Provider =
+                serde_json::from_value(serde_json::Value::String(provider.trim().to_lowercase()))
+                    .map_err(|_| {
+                    VisionError::Config(format!(
+                        "invalid GLM_VISION_PROVIDER '{}': expected zhipu, zai or zai-coding",
+                        provider
+                    ))
+                })?;
+            config = config.with_provider(provider);
+        } else if let Some(mode) = var("Z_AI_MODE") {
+            let provider = match mode.trim().to_uppercase().as_str() {
+                "ZHIPU" => Provider::Zhipu,
+                "ZAI" => Provider::Zai,
+                _ => {
+                    return Err(VisionError::Config(format!(
+                        "invalid Z_AI_MODE '{}': expected ZHIPU or ZAI",
+                        mode
+                    )))
+                }
+            };
+            config = config.with_provider(provider);
+        }
+        if let Some(url) = var("GLM_VISION_BASE_URL") {
+            config = config.with_base_url(url);
+        }
+        if config.base_url.is_empty() {
+            return Err(VisionError::Config(
+                "set GLM_VISION_PROVIDER, Z_AI_MODE or GLM_VISION_BASE_URL".to_string(),
+            ));
+        }
+
+        if let Some(model) = var("GLM_VISION_MODEL") {
+            config.model = model;
+        }
+        if let Some(v) = var("GLM_VISION_TEMPERATURE") {
+            config.temperature = parse_var("GLM_VISION_TEMPERATURE", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_TOP_P") {
+            config.top_p = parse_var("GLM_VISION_TOP_P", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_MAX_TOKENS") {
+            config.max_tokens = parse_var("GLM_VISION_MAX_TOKENS", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_TIMEOUT_SECS") {
+            config.timeout_secs = parse_var("GLM_VISION_TIMEOUT_SECS", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_MAX_IMAGE_SIZE_MB") {
+            config.max_image_size_mb = parse_var("GLM_VISION_MAX_IMAGE_SIZE_MB", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_MAX_VIDEO_SIZE_MB") {
+            config.max_video_size_mb = parse_var("GLM_VISION_MAX_VIDEO_SIZE_MB", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_THINKING") {
+            config.thinking_enabled = parse_var("GLM_VISION_THINKING", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_IMAGE_MAX_EDGE") {
+            config.image_resize = Some(ImageResize {
+                max_edge: Some(parse_var("GLM_VISION_IMAGE_MAX_EDGE", &v)?),
+                ..ImageResize::default()
+            });
+        }
+        if let Some(mode) = var("GLM_VISION_TILING") {
+            let mode: TileMode =
+                serde_json::from_value(serde_json::Value::String(mode.trim().to_lowercase()))
+                    .map_err(|_| {
+                        VisionError::Config(format!(
+                            "invalid GLM_VISION_TILING '{}': expected multi-image or sequential",
+                            mode
+                        ))
+                    })?;
+            config.tiling = Some(ImageTiling::default().with_mode(mode));
+        }
+        if let Some(v) = var("GLM_VISION_JSON_REPAIRS") {
+            config.json_repairs = parse_var("GLM_VISION_JSON_REPAIRS", &v)?;
+        }
+        if let Some(v) = var("GLM_VISION_STRICT_MEDIA_TYPES") {
+            config.strict_media_types = parse_var("GLM_VISION_STRICT_MEDIA_TYPES", &v)?;
+        }
+        config.proxy = var("GLM_VISION_PROXY");
+        config.root_ca_path = var("GLM_VISION_ROOT_CA");
+
+        Ok(config)
+    }
+
     /// Returns the full chat completions URL.
     pub fn completions_url(&self) -> String {
         let base = self.base_url.trim_end_matches('/');
@@ -126,6 +345,175 @@ impl VisionConfig {
     }
 }
 
+fn parse_var<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, VisionError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| VisionError::Config(format!("invalid {}: '{}'", key, value)))
+}
+
+/// Options for downscaling and recompressing local images, see
+/// [`VisionConfig::image_resize`].
+///
+/// Images that already fit within every limit are sent unchanged. Others are
+/// resized to fit `max_edge` and `max_pixels`, then re-encoded (PNG first for
+/// PNG input, then JPEG at decreasing quality, shrinking further if needed)
+/// until they fit under `max_image_size_mb`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageResize {
+    /// Maximum width or height in pixels (default: 2048).
+    #[serde(default = "default_max_edge")]
+    pub max_edge: Option<u32>,
+
+    /// Maximum total pixel count (default: none).
+    #[serde(default)]
+    pub max_pixels: Option<u64>,
+
+    /// Initial JPEG quality when re-encoding (default: 85).
+    #[serde(default = "default_jpeg_quality")]
+    pub jpeg_quality: u8,
+
+    /// Lowest JPEG quality tried before shrinking the image further (default: 50).
+    #[serde(default = "default_min_jpeg_quality")]
+    pub min_jpeg_quality: u8,
+}
+
+impl Default for ImageResize {
+    fn default() -> Self {
+        Self {
+            max_edge: default_max_edge(),
+            max_pixels: None,
+            jpeg_quality: default_jpeg_quality(),
+            min_jpeg_quality: default_min_jpeg_quality(),
+        }
+    }
+}
+
+impl ImageResize {
+    /// Set the maximum width or height in pixels.
+    pub fn with_max_edge(mut self, max_edge: u32) -> Self {
+        self.max_edge = Some(max_edge);
+        self
+    }
+
+    /// Set the maximum total pixel count.
+    pub fn with_max_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_pixels = Some(max_pixels);
+        self
+    }
+
+    /// Set the initial JPEG quality (1-100).
+    pub fn with_jpeg_quality(mut self, quality: u8) -> Self {
+        self.jpeg_quality = quality;
+        self
+    }
+}
+
+/// Options for splitting large screenshots into overlapping tiles, see
+/// [`VisionConfig::tiling`].
+///
+/// Images that fit in a single tile are sent as usual. Larger ones are cut into
+/// a grid of tiles, read row by row, where neighbouring tiles share `overlap`
+/// pixels so text on a tile edge is legible in at least one of them. If the
+/// grid would exceed `max_tiles`, the tiles are enlarged (and each is then
+/// downscaled as configured by [`VisionConfig::image_resize`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageTiling {
+    /// Tile width in pixels (default: 1536).
+    #[serde(default = "default_tile_edge")]
+    pub tile_width: u32,
+
+    /// Tile height in pixels (default: 1536).
+    #[serde(default = "default_tile_edge")]
+    pub tile_height: u32,
+
+    /// Pixels shared by neighbouring tiles (default: 128).
+    #[serde(default = "default_tile_overlap")]
+    pub overlap: u32,
+
+    /// Maximum number of tiles per image (default: 12).
+    #[serde(default = "default_max_tiles")]
+    pub max_tiles: usize,
+
+    /// How the tiles are sent (default: sequential).
+    #[serde(default)]
+    pub mode: TileMode,
+}
+
+impl Default for ImageTiling {
+    fn default() -> Self {
+        Self {
+            tile_width: default_tile_edge(),
+            tile_height: default_tile_edge(),
+            overlap: default_tile_overlap(),
+            max_tiles: default_max_tiles(),
+            mode: TileMode::default(),
+        }
+    }
+}
+
+impl ImageTiling {
+    /// Set the tile width and height in pixels.
+    pub fn with_tile_size(mut self, width: u32, height: u32) -> Self {
+        self.tile_width = width;
+        self.tile_height = height;
+        self
+    }
+
+    /// Set the number of pixels shared by neighbouring tiles.
+    pub fn with_overlap(mut self, overlap: u32) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Set the maximum number of tiles per image.
+    pub fn with_max_tiles(mut self, max_tiles: usize) -> Self {
+        self.max_tiles = max_tiles;
+        self
+    }
+
+    /// Set how the tiles are sent.
+    pub fn with_mode(mut self, mode: TileMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+/// How tiles of a large screenshot are sent to the model.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TileMode {
+    /// All tiles as ordered images in a single request.
+    MultiImage,
+    /// One request per tile, with the answers merged afterwards.
+    #[default]
+    Sequential,
+}
+
+fn default_tile_edge() -> u32 {
+    1536
+}
+
+fn default_tile_overlap() -> u32 {
+    128
+}
+
+fn default_max_tiles() -> usize {
+    12
+}
+
+fn default_max_edge() -> Option<u32> {
+    Some(2048)
+}
+
+fn default_jpeg_quality() -> u8 {
+    85
+}
+
+fn default_min_jpeg_quality() -> u8 {
+    50
+}
+
 fn default_model() -> String {
     "glm-4.6v".to_string()
 }
@@ -158,6 +546,10 @@ fn default_thinking_enabled() -> bool {
     true
 }
 
+fn default_json_repairs() -> u32 {
+    2
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -173,6 +565,10 @@ mod tests {
         assert_eq!(config.max_tokens, 32768);
         assert_eq!(config.timeout_secs, 300);
         assert!(config.thinking_enabled);
+        assert_eq!(config.json_repairs, 2);
+        assert!(config.proxy.is_none());
+        assert!(config.root_ca_path.is_none());
+        assert!(!config.danger_accept_invalid_certs);
     }
 
     #[test]
@@ -208,11 +604,17 @@ mod tests {
             .with_provider(Provider::Zai)
             .with_model("glm-4.7v")
             .with_temperature(0.5)
-            .with_thinking(false);
+            .with_thinking(false)
+            .with_timeout_secs(60)
+            .with_proxy("http://proxy:3128")
+            .with_root_ca("/etc/ssl/corp.pem");
         assert_eq!(config.base_url, "https://api.z.ai/api/paas/v4/");
         assert_eq!(config.model, "glm-4.7v");
         assert_eq!(config.temperature, 0.5);
         assert!(!config.thinking_enabled);
+        assert_eq!(config.timeout_secs, 60);
+        assert_eq!(config.proxy.as_deref(), Some("http://proxy:3128"));
+        assert_eq!(config.root_ca_path.as_deref(), Some("/etc/ssl/corp.pem"));
     }
 
     #[test]
@@ -237,6 +639,80 @@ mod tests {
         assert_eq!(parsed.base_url, "https://open.bigmodel.cn/api/paas/v4/");
     }
 
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: std::collections::HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let config = VisionConfig::from_vars(vars(&[
+            ("GLM_VISION_API_KEY", "env-key"),
+            ("GLM_VISION_PROVIDER", "zai-coding"),
+            ("GLM_VISION_MODEL", "glm-4.5v"),
+            ("GLM_VISION_TEMPERATURE", "0.2"),
+            ("GLM_VISION_THINKING", "false"),
+            ("GLM_VISION_MAX_IMAGE_SIZE_MB", "10"),
+            ("GLM_VISION_STRICT_MEDIA_TYPES", "true"),
+            ("GLM_VISION_JSON_REPAIRS", "0"),
+        ]))
+        .unwrap();
+        assert_eq!(config.api_key, "env-key");
+        assert_eq!(config.base_url, Provider::ZaiCoding.base_url());
+        assert_eq!(config.model, "glm-4.5v");
+        assert_eq!(config.temperature, 0.2);
+        assert!(!config.thinking_enabled);
+        assert_eq!(config.max_image_size_mb, 10);
+        assert!(config.strict_media_types);
+        assert_eq!(config.json_repairs, 0);
+        assert!(config.image_resize.is_none());
+
+        let config = VisionConfig::from_vars(vars(&[
+            ("GLM_VISION_API_KEY", "k"),
+            ("GLM_VISION_PROVIDER", "zai"),
+            ("GLM_VISION_IMAGE_MAX_EDGE", "1280"),
+        ]))
+        .unwrap();
+        let resize = config.image_resize.unwrap();
+        assert_eq!(resize.max_edge, Some(1280));
+        assert_eq!(resize.jpeg_quality, 85);
+        assert!(config.tiling.is_none());
+
+        let config = VisionConfig::from_vars(vars(&[
+            ("GLM_VISION_API_KEY", "k"),
+            ("GLM_VISION_PROVIDER", "zai"),
+            ("GLM_VISION_TILING", "multi-image"),
+        ]))
+        .unwrap();
+        let tiling = config.tiling.unwrap();
+        assert_eq!(tiling.mode, TileMode::MultiImage);
+        assert_eq!(tiling.tile_height, 1536);
+    }
+
+    #[test]
+    fn test_from_vars_zai_compat() {
+        let config =
+            VisionConfig::from_vars(vars(&[("Z_AI_API_KEY", "k"), ("Z_AI_MODE", "ZAI")])).unwrap();
+        assert_eq!(config.api_key, "k");
+        assert_eq!(config.base_url, Provider::Zai.base_url());
+    }
+
+    #[test]
+    fn test_from_vars_errors() {
+        assert!(VisionConfig::from_vars(vars(&[])).is_err());
+        assert!(VisionConfig::from_vars(vars(&[("GLM_VISION_API_KEY", "k")])).is_err());
+        let err = VisionConfig::from_vars(vars(&[
+            ("GLM_VISION_API_KEY", "k"),
+            ("GLM_VISION_BASE_URL", "https://x/"),
+            ("GLM_VISION_MAX_TOKENS", "lots"),
+        ]))
+        .unwrap_err();
+        assert!(err.to_string().contains("GLM_VISION_MAX_TOKENS"));
+    }
+
     #[test]
     fn test_provider_serde() {
         let json = serde_json::to_string(&Provider::ZaiCoding).unwrap();
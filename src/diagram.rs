@@ -0,0 +1,644 @@
+//! Diagram source from [`diagram_to_source`](crate::tools::diagram_to_source).
+//!
+//! [`extract_source`] pulls the diagram out of a model answer and
+//! [`check_syntax`] runs a quick local check of it: the diagram header, matched
+//! brackets and blocks, and for DOT the edge operators. It catches truncated or
+//! malformed answers before they reach a renderer, but it is not a full parser;
+//! source that passes may still fail to render.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::VisionError;
+
+/// A diagram-as-code language.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagramFormat {
+    Mermaid,
+    PlantUml,
+    /// Graphviz DOT.
+    Dot,
+    D2,
+}
+
+impl DiagramFormat {
+    /// Display name, as used in prompts.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiagramFormat::Mermaid => "Mermaid",
+            DiagramFormat::PlantUml => "PlantUML",
+            DiagramFormat::Dot => "Graphviz DOT",
+            DiagramFormat::D2 => "D2",
+        }
+    }
+
+    /// Language tag of a fenced code block holding this format.
+    pub fn fence_tag(&self) -> &'static str {
+        self.tags()[0]
+    }
+
+    /// Fence tags and names accepted for this format, preferred first.
+    fn tags(&self) -> &'static [&'static str] {
+        match self {
+            DiagramFormat::Mermaid => &["mermaid", "mmd"],
+            DiagramFormat::PlantUml => &["plantuml", "puml", "uml"],
+            DiagramFormat::Dot => &["dot", "graphviz", "gv"],
+            DiagramFormat::D2 => &["d2"],
+        }
+    }
+}
+
+impl fmt::Display for DiagramFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DiagramFormat {
+    type Err = VisionError;
+
+    /// Parse a format name or fence tag, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        [
+            DiagramFormat::Mermaid,
+            DiagramFormat::PlantUml,
+            DiagramFormat::Dot,
+            DiagramFormat::D2,
+        ]
+        .into_iter()
+        .find(|f| f.tags().contains(&s.as_str()))
+        .ok_or_else(|| {
+            VisionError::InvalidRequest(format!(
+                "Invalid diagram format '{}'. Must be one of: mermaid, plantuml, dot, d2",
+                s
+            ))
+        })
+    }
+}
+
+/// The diagram source in a model answer.
+///
+/// Takes the first fenced block tagged with the format (e.g. ```mermaid), else
+/// the first fenced block, else the whole answer; reasoning before a closing
+/// `</think>` is dropped.
+pub fn extract_source(text: &str, format: DiagramFormat) -> String {
+    let text = match text.rfind("</think>") {
+        Some(end) => &text[end + "</think>".len()..],
+        None => text,
+    };
+    let blocks = fenced_blocks(text);
+    let tagged = blocks.iter().find(|(tag, _)| {
+        let tag = tag.to_lowercase();
+        format.tags().contains(&tag.as_str())
+    });
+    match tagged.or(blocks.first()) {
+        Some((_, body)) => body.trim().to_string(),
+        None => text.trim().to_string(),
+    }
+}
+
+/// The (language tag, body) of each ``` fenced block in `text`.
+pub(crate) fn fenced_blocks(text: &str) -> Vec<(&str, &str)> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        let after = &rest[start + 3..];
+        let Some(newline) = after.find('\n') else {
+            break;
+        };
+        let tag = after[..newline].split_whitespace().next().unwrap_or("");
+        let body = &after[newline + 1..];
+        let Some(end) = body.find("```") else {
+            // An unterminated fence still holds the (truncated) source.
+            blocks.push((tag, body));
+            break;
+        };
+        blocks.push((tag, &body[..end]));
+        rest = &body[end + 3..];
+    }
+    blocks
+}
+
+/// Check `source` for syntax errors that a renderer would reject.
+///
+/// Returns a message such as `line 4: '[' is never closed`, suitable for
+/// sending back to the model.
+pub fn check_syntax(format: DiagramFormat, source: &str) -> Result<(), String> {
+    if source.trim().is_empty() {
+        return Err("the diagram source is empty".to_string());
+    }
+    match format {
+        DiagramFormat::Mermaid => check_mermaid(source),
+        DiagramFormat::PlantUml => check_plantuml(source),
+        DiagramFormat::Dot => check_dot(source),
+        DiagramFormat::D2 => {
+            let masked = mask(source, &D2_SYNTAX);
+            check_balanced(&masked, "{[(", |_, _| false)
+        }
+    }
+}
+
+/// Mermaid diagram keywords, as the first word of the source.
+const MERMAID_DIAGRAMS: &[&str] = &[
+    "flowchart",
+    "graph",
+    "sequenceDiagram",
+    "classDiagram",
+    "stateDiagram",
+    "stateDiagram-v2",
+    "erDiagram",
+    "journey",
+    "gantt",
+    "pie",
+    "quadrantChart",
+    "requirementDiagram",
+    "gitGraph",
+    "C4Context",
+    "C4Container",
+    "C4Component",
+    "C4Dynamic",
+    "C4Deployment",
+    "mindmap",
+    "timeline",
+    "sankey-beta",
+    "xychart-beta",
+    "block-beta",
+    "packet-beta",
+    "architecture-beta",
+    "kanban",
+];
+
+/// Statements opening a block closed by `end` in sequence diagrams.
+const SEQUENCE_BLOCKS: &[&str] = &[
+    "loop", "alt", "opt", "par", "critical", "break", "rect", "box",
+];
+
+fn check_mermaid(source: &str) -> Result<(), String> {
+    let masked = mask(source, &MERMAID_SYNTAX);
+    let mut lines = masked.lines().enumerate().peekable();
+
+    // Skip a `---` front matter block and blank lines.
+    if lines.peek().is_some_and(|(_, l)| l.trim() == "---") {
+        lines.next();
+        for (_, line) in lines.by_ref() {
+            if line.trim() == "---" {
+                break;
+            }
+        }
+    }
+    let Some((header_line, header)) = lines.find(|(_, l)| !l.trim().is_empty()) else {
+        return Err("the diagram has no content".to_string());
+    };
+    let keyword = header.split_whitespace().next().unwrap_or("");
+    if !MERMAID_DIAGRAMS.contains(&keyword) {
+        return Err(format!(
+            "line {}: expected a diagram type such as `flowchart` or `sequenceDiagram`, found `{}`",
+            header_line + 1,
+            keyword
+        ));
+    }
+
+    match keyword {
+        "flowchart" | "graph" => {
+            // `id>label]` is the asymmetric node shape; other `>` are arrows.
+            check_balanced(&masked, "{[(", |prev, c| {
+                c == '>' && prev.is_some_and(|p| p.is_alphanumeric() || p == '_')
+            })?;
+            check_blocks(&masked, &["subgraph"])
+        }
+        "sequenceDiagram" => check_blocks(&masked, SEQUENCE_BLOCKS),
+        "classDiagram" | "stateDiagram" | "stateDiagram-v2" => {
+            // Text after `:` is free-form, so only braces before it are counted.
+            let structure: String = masked
+                .lines()
+                .map(|l| l.split(':').next().unwrap_or(""))
+                .collect::<Vec<_>>()
+                .join("\n");
+            check_balanced(&structure, "{", |_, _| false)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn check_plantuml(source: &str) -> Result<(), String> {
+    let mut lines = source
+        .lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('\''));
+    let (_, first) = lines.next().unwrap_or_default();
+    let kind = first
+        .trim()
+        .strip_prefix("@start")
+        .map(|k| k.split_whitespace().next().unwrap_or(""))
+        .ok_or_else(|| "line 1: the diagram must start with `@startuml`".to_string())?;
+    let end = format!("@end{}", kind);
+    match lines.last() {
+        Some((_, last)) if last.trim() == end => Ok(()),
+        Some((n, last)) => Err(format!(
+            "line {}: expected `{}` to close the diagram, found `{}`",
+            n + 1,
+            end,
+            last.trim()
+        )),
+        None => Err(format!("the diagram is never closed with `{}`", end)),
+    }
+}
+
+fn check_dot(source: &str) -> Result<(), String> {
+    let masked = mask(source, &DOT_SYNTAX);
+    let header_end = masked
+        .find('{')
+        .ok_or_else(|| "expected `digraph {` or `graph {`".to_string())?;
+    let mut words = masked[..header_end]
+        .split_whitespace()
+        .map(str::to_lowercase);
+    let mut kind = words.next().unwrap_or_default();
+    if kind == "strict" {
+        kind = words.next().unwrap_or_default();
+    }
+    let (allowed, forbidden) = match kind.as_str() {
+        "digraph" => ("->", "--"),
+        "graph" => ("--", "->"),
+        _ => {
+            return Err(format!(
+                "line {}: expected `digraph` or `graph`, found `{}`",
+                line_of(&masked, masked.len() - masked.trim_start().len()),
+                kind
+            ))
+        }
+    };
+    check_balanced(&masked, "{[", |_, _| false)?;
+    let close = matching_brace(&masked, header_end);
+    if let Some(extra) = close
+        .map(|c| &masked[c + 1..])
+        .filter(|r| !r.trim().is_empty())
+    {
+        let at = masked.len() - extra.trim_start().len();
+        return Err(format!(
+            "line {}: unexpected text after the closing `}}` of the graph",
+            line_of(&masked, at)
+        ));
+    }
+    if let Some(at) = find_edge_op(&masked, forbidden) {
+        return Err(format!(
+            "line {}: `{}` edges are not allowed in a {}; use `{}`",
+            line_of(&masked, at),
+            forbidden,
+            kind,
+            allowed
+        ));
+    }
+    Ok(())
+}
+
+/// Byte offset of the `}` closing the `{` at `open`.
+fn matching_brace(masked: &str, open: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, c) in masked[open..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Offset of an edge operator `op` (`->` or `--`), ignoring `--` that is
+/// part of a longer run of dashes or of `->`.
+fn find_edge_op(masked: &str, op: &str) -> Option<usize> {
+    let bytes = masked.as_bytes();
+    masked.match_indices(op).map(|(i, _)| i).find(|&i| {
+        let before = i.checked_sub(1).map(|j| bytes[j]);
+        let after = bytes.get(i + 2).copied();
+        match op {
+            "--" => before != Some(b'-') && !matches!(after, Some(b'-' | b'>')),
+            _ => before != Some(b'-'),
+        }
+    })
+}
+
+/// How comments and strings are written in a language.
+struct Syntax {
+    /// Markers of comments running to the end of the line.
+    line_comments: &'static [&'static str],
+    /// `#` at the start of a line or after whitespace starts a comment.
+    hash_comments: bool,
+    /// `/* ... */` comments.
+    block_comments: bool,
+    quotes: &'static [char],
+    /// `=<...>` HTML-like strings with nested angle brackets (DOT labels).
+    html_strings: bool,
+}
+
+const MERMAID_SYNTAX: Syntax = Syntax {
+    line_comments: &["%%"],
+    hash_comments: false,
+    block_comments: false,
+    quotes: &['"'],
+    html_strings: false,
+};
+
+const DOT_SYNTAX: Syntax = Syntax {
+    line_comments: &["//"],
+    hash_comments: true,
+    block_comments: true,
+    quotes: &['"'],
+    html_strings: true,
+};
+
+const D2_SYNTAX: Syntax = Syntax {
+    line_comments: &[],
+    hash_comments: true,
+    block_comments: false,
+    quotes: &['"', '\''],
+    html_strings: false,
+};
+
+/// `source` with comments and the contents of strings replaced by spaces, so
+/// brackets and keywords in them are ignored. Line breaks are kept, so offsets
+/// and line numbers still match the source.
+fn mask(source: &str, syntax: &Syntax) -> String {
+    enum State {
+        Code,
+        LineComment,
+        BlockComment,
+        Quoted(char),
+        Html(usize),
+    }
+
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len());
+    let mut state = State::Code;
+    let mut i = 0;
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+    while i < chars.len() {
+        let c = chars[i];
+        let starts_with = |marker: &str| {
+            marker
+                .chars()
+                .enumerate()
+                .all(|(j, m)| chars.get(i + j) == Some(&m))
+        };
+        match state {
+            State::Code => {
+                let prev = i.checked_sub(1).map(|j| chars[j]);
+                if syntax.line_comments.iter().any(|m| starts_with(m))
+                    || (syntax.hash_comments && c == '#' && prev.is_none_or(char::is_whitespace))
+                {
+                    state = State::LineComment;
+                    out.push(' ');
+                } else if syntax.block_comments && starts_with("/*") {
+                    state = State::BlockComment;
+                    out.push_str("  ");
+                    i += 1;
+                } else if syntax.quotes.contains(&c) {
+                    state = State::Quoted(c);
+                    out.push(c);
+                } else if syntax.html_strings
+                    && c == '<'
+                    && chars[..i].iter().rev().find(|c| !c.is_whitespace()) == Some(&'=')
+                {
+                    state = State::Html(1);
+                    out.push(' ');
+                } else {
+                    out.push(c);
+                }
+            }
+            State::LineComment => {
+                if c == '\n' {
+                    state = State::Code;
+                }
+                out.push(blank(c));
+            }
+            State::BlockComment => {
+                if starts_with("*/") {
+                    state = State::Code;
+                    out.push_str("  ");
+                    i += 1;
+                } else {
+                    out.push(blank(c));
+                }
+            }
+            State::Quoted(quote) => {
+                if c == '\\' && chars.get(i + 1).is_some_and(|&n| n != '\n') {
+                    out.push_str("  ");
+                    i += 1;
+                } else if c == quote {
+                    state = State::Code;
+                    out.push(c);
+                } else {
+                    out.push(blank(c));
+                }
+            }
+            State::Html(depth) => {
+                state = match c {
+                    '<' => State::Html(depth + 1),
+                    '>' if depth == 1 => State::Code,
+                    '>' => State::Html(depth - 1),
+                    _ => State::Html(depth),
+                };
+                out.push(blank(c));
+            }
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Check that the brackets in `openers` (any of `{[(`) are matched in
+/// `masked`. `extra_opener(previous char, char)` marks other characters that
+/// open a `[` pair, such as Mermaid's `>` node shape.
+fn check_balanced(
+    masked: &str,
+    openers: &str,
+    extra_opener: impl Fn(Option<char>, char) -> bool,
+) -> Result<(), String> {
+    let closer = |open: char| match open {
+        '{' => '}',
+        '(' => ')',
+        _ => ']',
+    };
+    let mut stack: Vec<(char, usize)> = Vec::new();
+    let mut prev = None;
+    for (line, text) in masked.lines().enumerate() {
+        for c in text.chars() {
+            if openers.contains(c) {
+                stack.push((c, line + 1));
+            } else if extra_opener(prev, c) {
+                stack.push(('[', line + 1));
+            } else if openers.chars().any(|o| closer(o) == c) {
+                match stack.pop() {
+                    Some((open, _)) if closer(open) == c => {}
+                    Some((open, opened)) => {
+                        return Err(format!(
+                            "line {}: `{}` closes the `{}` opened on line {}",
+                            line + 1,
+                            c,
+                            open,
+                            opened
+                        ))
+                    }
+                    None => return Err(format!("line {}: unmatched `{}`", line + 1, c)),
+                }
+            }
+            prev = Some(c);
+        }
+        prev = None;
+    }
+    match stack.pop() {
+        Some((open, line)) => Err(format!("line {}: `{}` is never closed", line, open)),
+        None => Ok(()),
+    }
+}
+
+/// Check that every line starting with one of `openers` is matched by a line
+/// reading `end`.
+fn check_blocks(masked: &str, openers: &[&str]) -> Result<(), String> {
+    let mut open: Vec<(&str, usize)> = Vec::new();
+    for (line, text) in masked.lines().enumerate() {
+        let word = text.split_whitespace().next().unwrap_or("");
+        if openers.contains(&word) {
+            open.push((word, line + 1));
+        } else if word == "end" && open.pop().is_none() {
+            return Err(format!("line {}: `end` without an open block", line + 1));
+        }
+    }
+    match open.pop() {
+        Some((word, line)) => Err(format!(
+            "line {}: `{}` block is never closed with `end`",
+            line, word
+        )),
+        None => Ok(()),
+    }
+}
+
+/// 1-based line number of the byte offset `at` in `text`.
+fn line_of(text: &str, at: usize) -> usize {
+    text[..at.min(text.len())].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!(
+            "Mermaid".parse::<DiagramFormat>().unwrap(),
+            DiagramFormat::Mermaid
+        );
+        assert_eq!(
+            "puml".parse::<DiagramFormat>().unwrap(),
+            DiagramFormat::PlantUml
+        );
+        assert_eq!(
+            "graphviz".parse::<DiagramFormat>().unwrap(),
+            DiagramFormat::Dot
+        );
+        assert!("svg".parse::<DiagramFormat>().is_err());
+    }
+
+    #[test]
+    fn test_extract_source_prefers_tagged_block() {
+        let text = "<think>```mermaid\nwrong\n```</think>Here:\n```text\nnotes\n```\n\
+                    ```mermaid\nflowchart LR\n  A --> B\n```\nDone.";
+        assert_eq!(
+            extract_source(text, DiagramFormat::Mermaid),
+            "flowchart LR\n  A --> B"
+        );
+        assert_eq!(extract_source(text, DiagramFormat::D2), "notes");
+        assert_eq!(
+            extract_source("digraph { a -> b }", DiagramFormat::Dot),
+            "digraph { a -> b }"
+        );
+        assert_eq!(
+            extract_source("```dot\ndigraph {\n  a -> b", DiagramFormat::Dot),
+            "digraph {\n  a -> b"
+        );
+    }
+
+    #[test]
+    fn test_check_mermaid() {
+        let ok = "---\ntitle: Flow\n---\n%% comment [\nflowchart TD\n  \
+                  A[\"Start (here]\"] --> B{Valid?}\n  B -->|yes| C>Done]\n  \
+                  subgraph S\n    D((db))\n  end";
+        assert_eq!(check_syntax(DiagramFormat::Mermaid, ok), Ok(()));
+
+        let err =
+            check_syntax(DiagramFormat::Mermaid, "flowchart TD\n  A[Start --> B").unwrap_err();
+        assert_eq!(err, "line 2: `[` is never closed");
+        let err = check_syntax(DiagramFormat::Mermaid, "flow TD\n  A --> B").unwrap_err();
+        assert!(err.starts_with("line 1: expected a diagram type"));
+        let err = check_syntax(DiagramFormat::Mermaid, "graph LR\nsubgraph X\nA-->B").unwrap_err();
+        assert_eq!(err, "line 2: `subgraph` block is never closed with `end`");
+
+        let seq = "sequenceDiagram\n  A->>B: call (x\n  loop Every minute\n    B-->>A: pong\n  end";
+        assert_eq!(check_syntax(DiagramFormat::Mermaid, seq), Ok(()));
+        let er = "erDiagram\n  CUSTOMER ||--o{ ORDER : places";
+        assert_eq!(check_syntax(DiagramFormat::Mermaid, er), Ok(()));
+        let class = "classDiagram\n  class Animal {\n    +eat(food: Food{})\n  }";
+        assert_eq!(check_syntax(DiagramFormat::Mermaid, class), Ok(()));
+    }
+
+    #[test]
+    fn test_check_dot() {
+        let ok = "// services\ndigraph \"My graph\" {\n  rankdir=LR;\n  \
+                  a [label=\"x -- y {\"];\n  b [label=<<b>B</b>>];\n  a -> b; /* } */\n}\n";
+        assert_eq!(check_syntax(DiagramFormat::Dot, ok), Ok(()));
+        assert_eq!(
+            check_syntax(DiagramFormat::Dot, "strict graph { a -- b }"),
+            Ok(())
+        );
+
+        let err = check_syntax(DiagramFormat::Dot, "digraph {\n  a -- b\n}").unwrap_err();
+        assert_eq!(
+            err,
+            "line 2: `--` edges are not allowed in a digraph; use `->`"
+        );
+        let err = check_syntax(DiagramFormat::Dot, "digraph {\n  a [label=x\n}").unwrap_err();
+        assert_eq!(err, "line 3: `}` closes the `[` opened on line 2");
+        let err = check_syntax(DiagramFormat::Dot, "digraph {\n  a -> b\n").unwrap_err();
+        assert_eq!(err, "line 1: `{` is never closed");
+        let err = check_syntax(DiagramFormat::Dot, "flowchart {\n}").unwrap_err();
+        assert!(err.starts_with("line 1: expected `digraph` or `graph`"));
+        let err = check_syntax(DiagramFormat::Dot, "graph { a }\nb").unwrap_err();
+        assert_eq!(
+            err,
+            "line 2: unexpected text after the closing `}` of the graph"
+        );
+    }
+
+    #[test]
+    fn test_check_plantuml_and_d2() {
+        let puml = "' comment\n@startuml\nAlice -> Bob: hi\n@enduml\n";
+        assert_eq!(check_syntax(DiagramFormat::PlantUml, puml), Ok(()));
+        let err = check_syntax(DiagramFormat::PlantUml, "@startuml\nAlice -> Bob").unwrap_err();
+        assert_eq!(
+            err,
+            "line 2: expected `@enduml` to close the diagram, found `Alice -> Bob`"
+        );
+        assert!(check_syntax(DiagramFormat::PlantUml, "Alice -> Bob").is_err());
+        assert_eq!(
+            check_syntax(
+                DiagramFormat::PlantUml,
+                "@startmindmap\n* root\n@endmindmap"
+            ),
+            Ok(())
+        );
+
+        let d2 = "# infra\nserver: \"API {v2}\" {\n  shape: rectangle\n}\nserver -> db";
+        assert_eq!(check_syntax(DiagramFormat::D2, d2), Ok(()));
+        assert!(check_syntax(DiagramFormat::D2, "a: {\n  b -> c").is_err());
+        assert!(check_syntax(DiagramFormat::D2, "  ").is_err());
+    }
+}
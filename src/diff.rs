@@ -0,0 +1,179 @@
+//! Typed reports from [`ui_diff_report`](crate::tools::ui_diff_report).
+//!
+//! The report is filled in by the model through
+//! [`completion_json`](crate::VisionClient::completion_json), so the doc
+//! comments on these types are part of the schema it is shown. Gate CI on
+//! [`UiDiffReport::has_severity`] rather than the model's overall verdict to
+//! fail a build only on differences that matter.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::grounding::BoundingBox;
+
+/// Comparison of an expected and an actual UI screenshot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct UiDiffReport {
+    /// `fail` if any difference would stop this implementation from shipping.
+    pub verdict: DiffVerdict,
+    /// One or two sentences on how closely the screenshots match.
+    #[serde(default)]
+    pub summary: String,
+    /// Every difference found, most severe first; empty if they match.
+    #[serde(default)]
+    pub differences: Vec<UiDifference>,
+}
+
+impl UiDiffReport {
+    /// Whether the model judged the actual screenshot acceptable.
+    pub fn passed(&self) -> bool {
+        self.verdict == DiffVerdict::Pass
+    }
+
+    /// Severity of the worst difference, if there are any.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.differences.iter().map(|d| d.severity).max()
+    }
+
+    /// Whether any difference is at least as severe as `threshold`.
+    pub fn has_severity(&self, threshold: Severity) -> bool {
+        self.max_severity().is_some_and(|s| s >= threshold)
+    }
+
+    /// Differences at least as severe as `threshold`, in report order.
+    pub fn differences_at_least(&self, threshold: Severity) -> impl Iterator<Item = &UiDifference> {
+        self.differences
+            .iter()
+            .filter(move |d| d.severity >= threshold)
+    }
+}
+
+/// Overall outcome of a comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffVerdict {
+    Pass,
+    Fail,
+}
+
+/// A single difference between the screenshots.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct UiDifference {
+    pub category: DiffCategory,
+    pub severity: Severity,
+    /// What differs, with the expected and the actual appearance.
+    pub description: String,
+    /// Where the difference is, as [x1, y1, x2, y2] normalized to 0-1000 of the
+    /// ACTUAL screenshot (of the EXPECTED one for missing elements); null if it
+    /// has no single location.
+    #[serde(default, deserialize_with = "deserialize_region")]
+    #[schemars(with = "Option<[f64; 4]>")]
+    pub region: Option<BoundingBox>,
+}
+
+/// Kind of a [`UiDifference`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffCategory {
+    /// Position, alignment, spacing or size of elements.
+    Layout,
+    /// Font family, size, weight or line height.
+    Typography,
+    /// Colors, borders, shadows and other visual treatment.
+    Color,
+    /// Text, images or icons that differ.
+    Content,
+    /// An element of the expected design absent from the actual screenshot.
+    MissingElement,
+    /// An element in the actual screenshot absent from the expected design.
+    ExtraElement,
+    Other,
+}
+
+/// How much a difference matters, from least to most severe.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Barely noticeable; no effect on use or appearance.
+    #[serde(alias = "LOW")]
+    Low,
+    /// Noticeable, such as minor spacing or font size differences.
+    #[serde(alias = "MEDIUM")]
+    Medium,
+    /// Clearly wrong, such as misaligned components or wrong brand colors.
+    #[serde(alias = "HIGH")]
+    High,
+    /// Breaks the interface, such as a missing call to action.
+    #[serde(alias = "CRITICAL")]
+    Critical,
+}
+
+/// A region as `[x1, y1, x2, y2]` or `{"x1": ..}`, normalized with
+/// [`BoundingBox::new`].
+fn deserialize_region<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<BoundingBox>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Region {
+        Array([f64; 4]),
+        Object(BoundingBox),
+    }
+    Ok(
+        Option::<Region>::deserialize(deserializer)?.map(|region| match region {
+            Region::Array([x1, y1, x2, y2]) => BoundingBox::new(x1, y1, x2, y2),
+            Region::Object(b) => BoundingBox::new(b.x1, b.y1, b.x2, b.y2),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::parse_json;
+
+    #[test]
+    fn test_parse_report() {
+        let report: UiDiffReport = parse_json(
+            r#"{
+                "verdict": "fail",
+                "summary": "The submit button is missing.",
+                "differences": [
+                    {"category": "missing_element", "severity": "CRITICAL",
+                     "description": "No submit button", "region": [600, 900, 100, 800]},
+                    {"category": "typography", "severity": "low",
+                     "description": "Heading is bolder", "region": null},
+                    {"category": "color", "severity": "medium", "description": "Darker footer",
+                     "region": {"x1": 0, "y1": 950, "x2": 1000, "y2": 1000}}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert!(!report.passed());
+        assert_eq!(
+            report.differences[0].region,
+            Some(BoundingBox::new(100.0, 800.0, 600.0, 900.0))
+        );
+        assert_eq!(report.differences[1].region, None);
+        assert_eq!(report.differences[2].region.unwrap().y1, 950.0);
+        assert_eq!(report.max_severity(), Some(Severity::Critical));
+        assert!(report.has_severity(Severity::High));
+        assert_eq!(report.differences_at_least(Severity::Medium).count(), 2);
+    }
+
+    #[test]
+    fn test_passing_report_without_differences() {
+        let report: UiDiffReport = parse_json("{\"verdict\": \"pass\"}").unwrap();
+        assert!(report.passed());
+        assert_eq!(report.max_severity(), None);
+        assert!(!report.has_severity(Severity::Low));
+    }
+
+    #[test]
+    fn test_severity_order() {
+        assert!(Severity::Low < Severity::Medium);
+        assert!(Severity::High < Severity::Critical);
+    }
+}
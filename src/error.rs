@@ -0,0 +1,285 @@
+use serde::{Deserialize, Serialize};
+
+/// Result type used throughout the crate.
+pub type Result<T, E = VisionError> = std::result::Result<T, E>;
+
+/// Provider error code for requests blocked by the content safety filter.
+const CONTENT_FILTER_CODE: &str = "1301";
+
+/// Error details parsed from a provider error body
+/// (`{"error": {"code": "...", "message": "..."}}`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    /// Provider-specific error code (e.g. "1302" for rate limiting on z.ai).
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub code: Option<String>,
+    /// Human-readable error message.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+impl ApiError {
+    /// Parse the provider error JSON from a response body, if present.
+    pub fn parse(body: &str) -> Option<Self> {
+        #[derive(Deserialize)]
+        struct Envelope {
+            error: ApiError,
+        }
+        serde_json::from_str::<Envelope>(body).ok().map(|e| e.error)
+    }
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.code, &self.message) {
+            (Some(code), Some(message)) => write!(f, "[{}] {}", code, message),
+            (Some(code), None) => write!(f, "[{}]", code),
+            (None, Some(message)) => f.write_str(message),
+            (None, None) => f.write_str("unknown error"),
+        }
+    }
+}
+
+fn string_or_number<'de, D>(deserializer: D) -> std::result::Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(
+        match Option::<serde_json::Value>::deserialize(deserializer)? {
+            Some(serde_json::Value::String(s)) => Some(s),
+            Some(serde_json::Value::Number(n)) => Some(n.to_string()),
+            _ => None,
+        },
+    )
+}
+
+/// Errors returned by [`VisionClient`](crate::VisionClient) and the functions in
+/// [`tools`](crate::tools).
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum VisionError {
+    /// The HTTP client failed to send the request or read the response.
+    #[error("Failed to send vision API request: {0}")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+    /// The request timed out.
+    #[error("Vision API request timed out")]
+    Timeout,
+
+    /// The API returned a non-success status.
+    #[error("Vision API error HTTP {status}: {}", display_error(.error, .body))]
+    Http {
+        status: u16,
+        error: Option<ApiError>,
+        body: String,
+    },
+
+    /// The API rejected the request with HTTP 429.
+    #[error("Vision API rate limited (HTTP 429): {}", display_error(.error, .body))]
+    RateLimited {
+        error: Option<ApiError>,
+        body: String,
+    },
+
+    /// The request or response was blocked by the provider's content filter.
+    #[error("Vision API content filtered: {}", display_error(.error, .body))]
+    ContentFiltered {
+        status: Option<u16>,
+        error: Option<ApiError>,
+        body: String,
+    },
+
+    /// A media source is missing, unreadable, too large, or of an unsupported type.
+    #[error("{0}")]
+    InvalidMedia(String),
+
+    /// The client configuration is incomplete or invalid.
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+
+    /// A tool was called with invalid arguments.
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    /// The API response could not be parsed or lacked the expected content.
+    #[error("Failed to parse vision API response: {message}")]
+    Parse { message: String, body: String },
+}
+
+fn display_error(error: &Option<ApiError>, body: &str) -> String {
+    match error {
+        Some(e) => e.to_string(),
+        None => body.to_string(),
+    }
+}
+
+impl VisionError {
+    /// Classify a non-success HTTP response.
+    pub fn from_response(status: u16, body: String) -> Self {
+        let error = ApiError::parse(&body);
+        let code = error.as_ref().and_then(|e| e.code.as_deref());
+        if code == Some(CONTENT_FILTER_CODE) {
+            VisionError::ContentFiltered {
+                status: Some(status),
+                error,
+                body,
+            }
+        } else if status == 429 {
+            VisionError::RateLimited { error, body }
+        } else {
+            VisionError::Http {
+                status,
+                error,
+                body,
+            }
+        }
+    }
+
+    /// Wrap an error returned by an [`HttpClient`](crate::HttpClient).
+    ///
+    /// A `VisionError` returned by the HTTP client is passed through unchanged, and
+    /// an `std::io::Error` of kind `TimedOut` anywhere in the source chain becomes
+    /// [`VisionError::Timeout`].
+    pub fn from_transport(err: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let err = match err.downcast::<VisionError>() {
+            Ok(e) => return *e,
+            Err(err) => err,
+        };
+
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err.as_ref());
+        while let Some(e) = source {
+            if let Some(io) = e.downcast_ref::<std::io::Error>() {
+                if io.kind() == std::io::ErrorKind::TimedOut {
+                    return VisionError::Timeout;
+                }
+            }
+            source = e.source();
+        }
+        VisionError::Transport(err)
+    }
+
+    /// Returns `true` for transient failures worth retrying: transport errors,
+    /// timeouts, rate limiting and HTTP 5xx.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            VisionError::Transport(_) | VisionError::Timeout | VisionError::RateLimited { .. } => {
+                true
+            }
+            VisionError::Http { status, .. } => (500..600).contains(status),
+            _ => false,
+        }
+    }
+
+    /// HTTP status code, for errors caused by an HTTP response.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            VisionError::Http { status, .. } => Some(*status),
+            VisionError::RateLimited { .. } => Some(429),
+            VisionError::ContentFiltered { status, .. } => *status,
+            _ => None,
+        }
+    }
+
+    /// Provider error details parsed from the response body.
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            VisionError::Http { error, .. }
+            | VisionError::RateLimited { error, .. }
+            | VisionError::ContentFiltered { error, .. } => error.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Raw response body, for errors caused by an API response.
+    pub fn body(&self) -> Option<&str> {
+        match self {
+            VisionError::Http { body, .. }
+            | VisionError::RateLimited { body, .. }
+            | VisionError::ContentFiltered { body, .. }
+            | VisionError::Parse { body, .. } => Some(body),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_error_parse() {
+        let err =
+            ApiError::parse(r#"{"error":{"code":"1302","message":"Too many requests"}}"#).unwrap();
+        assert_eq!(err.code.as_deref(), Some("1302"));
+        assert_eq!(err.message.as_deref(), Some("Too many requests"));
+        assert_eq!(err.to_string(), "[1302] Too many requests");
+
+        let numeric = ApiError::parse(r#"{"error":{"code":1214,"message":"bad"}}"#).unwrap();
+        assert_eq!(numeric.code.as_deref(), Some("1214"));
+
+        assert!(ApiError::parse("Bad Gateway").is_none());
+    }
+
+    #[test]
+    fn test_from_response_classification() {
+        let err = VisionError::from_response(429, r#"{"error":{"code":"1302"}}"#.into());
+        assert!(matches!(err, VisionError::RateLimited { .. }));
+        assert!(err.is_retryable());
+        assert_eq!(err.status(), Some(429));
+
+        let err = VisionError::from_response(
+            400,
+            r#"{"error":{"code":"1301","message":"unsafe content"}}"#.into(),
+        );
+        assert!(matches!(err, VisionError::ContentFiltered { .. }));
+        assert!(!err.is_retryable());
+        assert_eq!(
+            err.api_error().unwrap().message.as_deref(),
+            Some("unsafe content")
+        );
+
+        let err = VisionError::from_response(503, "upstream unavailable".into());
+        assert!(matches!(err, VisionError::Http { status: 503, .. }));
+        assert!(err.is_retryable());
+        assert_eq!(err.body(), Some("upstream unavailable"));
+        assert_eq!(
+            err.to_string(),
+            "Vision API error HTTP 503: upstream unavailable"
+        );
+
+        let err = VisionError::from_response(401, r#"{"error":{"code":"1000"}}"#.into());
+        assert!(!err.is_retryable());
+        assert_eq!(err.api_error().unwrap().code.as_deref(), Some("1000"));
+    }
+
+    #[test]
+    fn test_from_transport() {
+        let timeout = std::io::Error::new(std::io::ErrorKind::TimedOut, "deadline");
+        assert!(matches!(
+            VisionError::from_transport(Box::new(timeout)),
+            VisionError::Timeout
+        ));
+
+        let passthrough = VisionError::Config("x".into());
+        assert!(matches!(
+            VisionError::from_transport(Box::new(passthrough)),
+            VisionError::Config(_)
+        ));
+
+        let refused = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let err = VisionError::from_transport(Box::new(refused));
+        assert!(matches!(err, VisionError::Transport(_)));
+        assert!(err.is_retryable());
+    }
+
+    #[test]
+    fn test_non_retryable_variants() {
+        assert!(!VisionError::InvalidMedia("x".into()).is_retryable());
+        assert!(!VisionError::Config("x".into()).is_retryable());
+        assert!(!VisionError::Parse {
+            message: "x".into(),
+            body: String::new()
+        }
+        .is_retryable());
+    }
+}
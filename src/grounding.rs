@@ -0,0 +1,286 @@
+//! Bounding boxes from grounding answers.
+//!
+//! GLM-4.6V marks the regions it refers to as
+//! `<|begin_of_box|>[x1,y1,x2,y2]<|end_of_box|>`, with coordinates normalized to
+//! 0-1000 relative to the width and height of the image it was sent. Because the
+//! coordinates are relative, they map onto the original image regardless of any
+//! resizing done by the client; see [`BoundingBox::to_pixels`].
+
+use serde::{Deserialize, Serialize};
+
+/// Marker opening a bounding box in model output.
+pub const BOX_BEGIN: &str = "<|begin_of_box|>";
+
+/// Marker closing a bounding box in model output.
+pub const BOX_END: &str = "<|end_of_box|>";
+
+/// Upper bound of the normalized coordinate range.
+const NORMALIZED_MAX: f64 = 1000.0;
+
+/// A box in normalized coordinates (0-1000 on each axis, origin top left).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BoundingBox {
+    /// Left edge.
+    pub x1: f64,
+    /// Top edge.
+    pub y1: f64,
+    /// Right edge.
+    pub x2: f64,
+    /// Bottom edge.
+    pub y2: f64,
+}
+
+impl BoundingBox {
+    /// Create a box from two corners, in any order, clamped to 0-1000.
+    pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+        let clamp = |v: f64| v.clamp(0.0, NORMALIZED_MAX);
+        Self {
+            x1: clamp(x1.min(x2)),
+            y1: clamp(y1.min(y2)),
+            x2: clamp(x1.max(x2)),
+            y2: clamp(y1.max(y2)),
+        }
+    }
+
+    /// Center of the box, in normalized coordinates.
+    pub fn center(&self) -> (f64, f64) {
+        ((self.x1 + self.x2) / 2.0, (self.y1 + self.y2) / 2.0)
+    }
+
+    /// The box in pixels of a `width` x `height` image, at least one pixel in
+    /// each direction.
+    ///
+    /// Pass the original dimensions to locate the box in the original image,
+    /// even if a downscaled copy was sent to the model.
+    pub fn to_pixels(&self, width: u32, height: u32) -> PixelBox {
+        let (x, right) = pixel_span(self.x1, self.x2, width);
+        let (y, bottom) = pixel_span(self.y1, self.y2, height);
+        PixelBox {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// The box in pixels of the original image described by `scale`.
+    #[cfg(feature = "image")]
+    pub fn to_original(&self, scale: &crate::preprocess::ImageScale) -> PixelBox {
+        self.to_pixels(scale.original_width, scale.original_height)
+    }
+}
+
+/// Start and end pixel of a normalized span along an axis of `len` pixels.
+fn pixel_span(start: f64, end: f64, len: u32) -> (u32, u32) {
+    let to_pixel = |v: f64| (v / NORMALIZED_MAX * len as f64) as u32;
+    let start = to_pixel(start.floor()).min(len.saturating_sub(1));
+    let end = to_pixel(end.ceil()).clamp(start + 1, len.max(start + 1));
+    (start, end)
+}
+
+/// A rectangle in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PixelBox {
+    /// Left edge.
+    pub x: u32,
+    /// Top edge.
+    pub y: u32,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+}
+
+/// A bounding box found in model output, with the text that introduced it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundedBox {
+    /// Text before the box on the same line (e.g. "Submit button"), if any.
+    pub label: Option<String>,
+    /// The box itself.
+    pub bbox: BoundingBox,
+}
+
+/// A UI element found by [`locate_elements`](crate::tools::locate_elements).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocatedElement {
+    /// The requested element name, or the model's label if it matched none.
+    pub name: String,
+    /// Location in normalized coordinates.
+    pub bbox: BoundingBox,
+    /// Location in pixels of the original image, when its size is known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pixels: Option<PixelBox>,
+}
+
+/// Parse every bounding box marked in `text`, in order.
+///
+/// A marker may hold one box (`[x1,y1,x2,y2]`) or several
+/// (`[[x1,y1,x2,y2],[...]]`); each gets the label in front of the marker.
+/// Markers whose content is not a multiple of four numbers are skipped.
+pub fn parse_boxes(text: &str) -> Vec<GroundedBox> {
+    let mut boxes = Vec::new();
+    let mut rest = text;
+    while let Some(begin) = rest.find(BOX_BEGIN) {
+        let before = &rest[..begin];
+        let after = &rest[begin + BOX_BEGIN.len()..];
+        let Some(end) = after.find(BOX_END) else {
+            break;
+        };
+        let label = before.rsplit('\n').next().and_then(clean_label);
+        let numbers = parse_numbers(&after[..end]);
+        if !numbers.is_empty() && numbers.len().is_multiple_of(4) {
+            boxes.extend(numbers.chunks(4).map(|c| GroundedBox {
+                label: label.clone(),
+                bbox: BoundingBox::new(c[0], c[1], c[2], c[3]),
+            }));
+        }
+        rest = &after[end + BOX_END.len()..];
+    }
+    boxes
+}
+
+/// Remove the box markers from `text`, keeping the coordinates between them.
+pub fn strip_box_markers(text: &str) -> String {
+    text.replace(BOX_BEGIN, "").replace(BOX_END, "")
+}
+
+/// All numbers in `s`, ignoring brackets, commas and other separators.
+fn parse_numbers(s: &str) -> Vec<f64> {
+    s.split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .filter(|t| !t.is_empty())
+        .filter_map(|t| t.parse().ok())
+        .collect()
+}
+
+/// Strip list markers, emphasis, quotes and trailing separators from a label.
+fn clean_label(text: &str) -> Option<String> {
+    let punctuation = |c: char| c.is_whitespace() || "-*•:=`'\"|".contains(c);
+    let mut label = text.trim();
+    let digits = label.len() - label.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 && label[digits..].starts_with(['.', ')']) {
+        label = &label[digits + 1..];
+    }
+    label = label.trim_matches(punctuation);
+    for suffix in [" at", " is"] {
+        label = label.strip_suffix(suffix).unwrap_or(label);
+    }
+    let label = label.trim_matches(punctuation);
+    (!label.is_empty()).then(|| label.to_string())
+}
+
+/// Match the boxes in a grounding answer to the requested element `names`.
+///
+/// Labels are compared case-insensitively, first exactly, then by containment.
+/// Boxes whose label matches no name keep their label (or `element N`).
+/// `original_size` is the width and height of the original image, if known.
+pub(crate) fn located_elements(
+    text: &str,
+    names: &[&str],
+    original_size: Option<(u32, u32)>,
+) -> Vec<LocatedElement> {
+    parse_boxes(text)
+        .into_iter()
+        .enumerate()
+        .map(|(i, found)| {
+            let label = found.label.as_deref().unwrap_or("").to_lowercase();
+            let exact = names.iter().find(|n| n.trim().to_lowercase() == label);
+            let partial = || {
+                names
+                    .iter()
+                    .filter(|n| {
+                        let n = n.trim().to_lowercase();
+                        !label.is_empty() && (label.contains(&n) || n.contains(&label))
+                    })
+                    .max_by_key(|n| n.len())
+            };
+            let name = match exact.or_else(partial) {
+                Some(name) => name.trim().to_string(),
+                None => found.label.unwrap_or_else(|| format!("element {}", i + 1)),
+            };
+            LocatedElement {
+                name,
+                bbox: found.bbox,
+                pixels: original_size.map(|(w, h)| found.bbox.to_pixels(w, h)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_boxes_with_labels() {
+        let text = "Here they are:\n\
+                    1. **Submit button**: <|begin_of_box|>[100,200,300,250]<|end_of_box|>\n\
+                    - `Search field` at <|begin_of_box|>[10, 20, 990, 80]<|end_of_box|>\n\
+                    <|begin_of_box|>[[0,0,10,10],[20,20,30,30]]<|end_of_box|>";
+        let boxes = parse_boxes(text);
+        assert_eq!(boxes.len(), 4);
+        assert_eq!(boxes[0].label.as_deref(), Some("Submit button"));
+        assert_eq!(boxes[0].bbox, BoundingBox::new(100.0, 200.0, 300.0, 250.0));
+        assert_eq!(boxes[1].label.as_deref(), Some("Search field"));
+        assert_eq!(boxes[2].label, None);
+        assert_eq!(boxes[3].bbox.x1, 20.0);
+    }
+
+    #[test]
+    fn test_parse_boxes_skips_malformed() {
+        assert!(parse_boxes("<|begin_of_box|>[1,2,3]<|end_of_box|>").is_empty());
+        assert!(parse_boxes("<|begin_of_box|>[1,2,3,4]").is_empty());
+        assert!(parse_boxes("no boxes here").is_empty());
+    }
+
+    #[test]
+    fn test_bounding_box_normalizes_corners() {
+        let b = BoundingBox::new(500.0, 800.0, 100.0, 1200.0);
+        assert_eq!((b.x1, b.y1, b.x2, b.y2), (100.0, 800.0, 500.0, 1000.0));
+        assert_eq!(b.center(), (300.0, 900.0));
+    }
+
+    #[test]
+    fn test_to_pixels() {
+        let b = BoundingBox::new(100.0, 200.0, 300.0, 250.0);
+        assert_eq!(
+            b.to_pixels(1440, 12000),
+            PixelBox {
+                x: 144,
+                y: 2400,
+                width: 288,
+                height: 600
+            }
+        );
+        let edge = BoundingBox::new(1000.0, 1000.0, 1000.0, 1000.0).to_pixels(10, 10);
+        assert_eq!((edge.x, edge.width), (9, 1));
+    }
+
+    #[test]
+    fn test_located_elements_matches_names() {
+        let text = "submit button: <|begin_of_box|>[100,200,300,250]<|end_of_box|>\n\
+                    The search field: <|begin_of_box|>[0,0,500,100]<|end_of_box|>\n\
+                    Logo: <|begin_of_box|>[0,0,50,50]<|end_of_box|>\n\
+                    Menu: not found";
+        let found = located_elements(text, &["Submit button", "Search field"], Some((1000, 500)));
+        let names: Vec<_> = found.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["Submit button", "Search field", "Logo"]);
+        assert_eq!(
+            found[0].pixels,
+            Some(PixelBox {
+                x: 100,
+                y: 100,
+                width: 200,
+                height: 25
+            })
+        );
+        assert!(located_elements(text, &[], None)[0].pixels.is_none());
+    }
+
+    #[test]
+    fn test_strip_box_markers() {
+        assert_eq!(
+            strip_box_markers("OK at <|begin_of_box|>[1,2,3,4]<|end_of_box|>."),
+            "OK at [1,2,3,4]."
+        );
+    }
+}
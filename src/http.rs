@@ -1,4 +1,12 @@
 use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+/// A boxed stream of response body chunks, as produced by [`HttpClient::post_stream`].
+pub type BodyStream =
+    Pin<Box<dyn Stream<Item = Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>> + Send>>;
 
 /// Response from an HTTP request.
 #[derive(Debug)]
@@ -16,6 +24,58 @@ impl HttpResponse {
     }
 }
 
+/// Response from a streaming HTTP request, with the body delivered incrementally.
+pub struct HttpStreamResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// Response body as a stream of byte chunks.
+    pub body: BodyStream,
+}
+
+impl HttpStreamResponse {
+    /// Returns `true` if the status code is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Drain the body stream into a string (lossy UTF-8).
+    pub async fn text(mut self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut buf = Vec::new();
+        while let Some(chunk) = std::future::poll_fn(|cx| self.body.as_mut().poll_next(cx)).await {
+            buf.extend_from_slice(&chunk?);
+        }
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+}
+
+impl std::fmt::Debug for HttpStreamResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpStreamResponse")
+            .field("status", &self.status)
+            .finish_non_exhaustive()
+    }
+}
+
+impl From<HttpResponse> for HttpStreamResponse {
+    fn from(response: HttpResponse) -> Self {
+        Self {
+            status: response.status,
+            body: Box::pin(SingleChunk(Some(response.body.into_bytes()))),
+        }
+    }
+}
+
+/// Body stream that yields one fully-buffered chunk.
+struct SingleChunk(Option<Vec<u8>>);
+
+impl Stream for SingleChunk {
+    type Item = Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.0.take().map(Ok))
+    }
+}
+
 /// Trait for making HTTP POST requests.
 ///
 /// Implement this with your preferred HTTP client (e.g. reqwest, ureq, hyper)
@@ -45,6 +105,38 @@ impl HttpResponse {
 ///     }
 /// }
 /// ```
+///
+/// # Streaming
+///
+/// [`post_stream`](HttpClient::post_stream) is used by
+/// [`VisionClient::completion_stream`](crate::VisionClient::completion_stream).
+/// The default implementation buffers the whole body via `post`, so deltas only
+/// arrive once the response is complete. Override it to deliver chunks as they
+/// are received:
+///
+/// ```ignore
+/// async fn post_stream(
+///     &self,
+///     url: &str,
+///     headers: &[(&str, &str)],
+///     body: &[u8],
+/// ) -> Result<glm_vision_rs::http::HttpStreamResponse, Box<dyn std::error::Error + Send + Sync>> {
+///     use futures_util::{StreamExt, TryStreamExt};
+///     let mut req = self.0.post(url);
+///     for &(k, v) in headers {
+///         req = req.header(k, v);
+///     }
+///     let resp = req.body(body.to_vec()).send().await?;
+///     Ok(glm_vision_rs::http::HttpStreamResponse {
+///         status: resp.status().as_u16(),
+///         body: resp
+///             .bytes_stream()
+///             .map_ok(|b| b.to_vec())
+///             .map_err(|e| e.into())
+///             .boxed(),
+///     })
+/// }
+/// ```
 pub trait HttpClient: Send + Sync {
     fn post(
         &self,
@@ -52,4 +144,30 @@ pub trait HttpClient: Send + Sync {
         headers: &[(&str, &str)],
         body: &[u8],
     ) -> impl Future<Output = Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>>> + Send;
+
+    fn post_stream(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> impl Future<Output = Result<HttpStreamResponse, Box<dyn std::error::Error + Send + Sync>>> + Send
+    {
+        async move { Ok(self.post(url, headers, body).await?.into()) }
+    }
+
+    /// Like [`post`](HttpClient::post), but takes ownership of the body.
+    ///
+    /// [`VisionClient`](crate::VisionClient) sends non-streaming requests through
+    /// this method so that request bodies carrying large media need not be copied
+    /// again. The default implementation borrows the body and calls `post`;
+    /// override it if your client can send an owned buffer without copying.
+    fn post_owned(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> impl Future<Output = Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>>> + Send
+    {
+        async move { self.post(url, headers, &body).await }
+    }
 }
@@ -1,11 +1,43 @@
+#[cfg(feature = "image")]
+pub mod annotate;
+#[cfg(any(feature = "reqwest", feature = "hyper", feature = "ureq"))]
+pub mod backends;
+pub mod chart;
 pub mod client;
+#[cfg(feature = "code")]
+pub mod code;
 pub mod config;
+pub mod diagram;
+pub mod diff;
+pub mod error;
+pub mod grounding;
 pub mod http;
+pub mod logs;
+#[cfg(feature = "mcp")]
+pub mod mcp;
+pub mod media;
+#[cfg(feature = "image")]
+pub mod preprocess;
 pub mod prompts;
+#[cfg(feature = "regression")]
+pub mod regression;
+pub mod replay;
+pub mod session;
+pub mod stream;
+pub mod structured;
+pub mod table;
 pub mod tools;
 pub mod types;
+#[cfg(feature = "video")]
+pub mod video;
+
+pub use schemars;
 
 pub use client::VisionClient;
-pub use config::{Provider, VisionConfig};
-pub use http::{HttpClient, HttpResponse};
-pub use types::ContentPart;
+pub use config::{ImageResize, ImageTiling, Provider, TileMode, VisionConfig};
+pub use error::{ApiError, VisionError};
+pub use http::{HttpClient, HttpResponse, HttpStreamResponse};
+pub use media::MediaSource;
+pub use session::VisionSession;
+pub use stream::VisionStream;
+pub use types::{ContentPart, VisionCompletion, VisionMessage, VisionUsage};
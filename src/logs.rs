@@ -0,0 +1,769 @@
+//! Log entries read from terminal screenshots by
+//! [`extract_logs`](crate::tools::extract_logs).
+//!
+//! The model transcribes each entry through
+//! [`completion_json`](crate::VisionClient::completion_json), keeping its
+//! first line as shown in [`LogEntry::raw`]. When the lines are in a format
+//! [`LogFormat::detect`] recognizes (Rust `tracing`, `env_logger`, JSON logs,
+//! syslog, or log4j/logback lines with Java stack traces), [`reparse`] reads
+//! the timestamp, level, target and fields from them locally, so every entry
+//! is split the same way before it goes to a log search. [`parse_logs`] does
+//! the same for log text that is already on hand.
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The log entries in an image, as answered by the model.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub(crate) struct LogEntries {
+    /// Every log entry in the image, top to bottom; empty if there are none.
+    pub entries: Vec<LogEntry>,
+}
+
+/// A log entry: its first line and the lines continuing it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogEntry {
+    /// Timestamp exactly as shown, if any.
+    #[serde(default)]
+    pub timestamp: Option<String>,
+    #[serde(default)]
+    pub level: Option<LogLevel>,
+    /// Logger, module, class or program that emitted the entry (e.g.
+    /// `my_app::db`, `com.example.OrderService`, `sshd[812]`), if shown.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// The message, without the timestamp, level, target and fields.
+    pub message: String,
+    /// Structured fields, such as `key=value` pairs after a message, the
+    /// other keys of a JSON line, or the thread or host.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, Value>,
+    /// Lines after the first that belong to the entry, such as stack frames,
+    /// "Caused by:" lines or wrapped message text, as shown.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub continuation: Vec<String>,
+    /// The first line of the entry exactly as shown, including the
+    /// timestamp and level.
+    #[serde(default)]
+    pub raw: String,
+}
+
+/// Severity of a log entry, from least to most severe.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    #[serde(alias = "TRACE", alias = "finest", alias = "FINEST")]
+    Trace,
+    #[serde(alias = "DEBUG", alias = "fine", alias = "FINE")]
+    Debug,
+    #[serde(alias = "INFO", alias = "notice", alias = "NOTICE")]
+    Info,
+    #[serde(alias = "WARN", alias = "warning", alias = "WARNING")]
+    Warn,
+    #[serde(alias = "ERROR", alias = "err", alias = "ERR", alias = "SEVERE")]
+    Error,
+    /// Fatal, critical, alert and emergency entries.
+    #[serde(alias = "FATAL", alias = "critical", alias = "CRITICAL")]
+    Fatal,
+}
+
+impl LogLevel {
+    /// Parse a level name as logging libraries print it, case-insensitively
+    /// (e.g. "WARN", "warning", "E", "crit").
+    pub fn parse(s: &str) -> Option<Self> {
+        let level = match s.trim().to_lowercase().as_str() {
+            "trace" | "t" | "finest" | "finer" => LogLevel::Trace,
+            "debug" | "d" | "fine" | "config" => LogLevel::Debug,
+            "info" | "i" | "information" | "notice" => LogLevel::Info,
+            "warn" | "w" | "warning" => LogLevel::Warn,
+            "error" | "e" | "err" | "severe" => LogLevel::Error,
+            "fatal" | "f" | "critical" | "crit" | "alert" | "emerg" | "emergency" | "panic" => {
+                LogLevel::Fatal
+            }
+            _ => return None,
+        };
+        Some(level)
+    }
+
+    /// The level of a syslog priority, from its severity (`priority % 8`).
+    pub fn from_syslog_priority(priority: u8) -> Self {
+        match priority % 8 {
+            0..=2 => LogLevel::Fatal,
+            3 => LogLevel::Error,
+            4 => LogLevel::Warn,
+            5 | 6 => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+
+    /// The level of a numeric bunyan or pino level (10 trace to 60 fatal).
+    fn from_number(n: f64) -> Option<Self> {
+        let level = match n as i64 {
+            10 => LogLevel::Trace,
+            20 => LogLevel::Debug,
+            30 => LogLevel::Info,
+            40 => LogLevel::Warn,
+            50 => LogLevel::Error,
+            60 => LogLevel::Fatal,
+            _ => return None,
+        };
+        Some(level)
+    }
+}
+
+/// A log line format [`LogFormat::detect`] recognizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// JSON objects, one per line (`tracing-subscriber` JSON, bunyan, pino,
+    /// logstash and similar).
+    Json,
+    /// RFC 3164 (`May  1 12:00:00 host sshd[812]: ..`) or RFC 5424 syslog.
+    Syslog,
+    /// `env_logger`: `[2024-05-01T12:00:00Z INFO  my_app::db] ..`.
+    EnvLogger,
+    /// `tracing-subscriber` fmt: `2024-05-01T12:00:00.123Z  INFO span{..}: my_app: ..`.
+    Tracing,
+    /// log4j, logback and Spring Boot lines: a timestamp, level, optional
+    /// `[thread]`, and logger, then ` - ` or ` : ` and the message.
+    Java,
+}
+
+const FORMATS: [LogFormat; 5] = [
+    LogFormat::Json,
+    LogFormat::Syslog,
+    LogFormat::EnvLogger,
+    LogFormat::Tracing,
+    LogFormat::Java,
+];
+
+impl LogFormat {
+    /// The format the most `lines` parse in, if any parse at all.
+    pub fn detect<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<Self> {
+        let lines: Vec<&str> = lines.into_iter().collect();
+        let mut best: Option<(LogFormat, usize)> = None;
+        for format in FORMATS {
+            let parsed = lines
+                .iter()
+                .filter(|l| format.parse_line(l).is_some())
+                .count();
+            if parsed > best.map_or(0, |(_, n)| n) {
+                best = Some((format, parsed));
+            }
+        }
+        best.map(|(format, _)| format)
+    }
+
+    /// Parse the first line of an entry in this format.
+    pub fn parse_line(&self, line: &str) -> Option<LogEntry> {
+        let line = line.trim_end();
+        let mut entry = match self {
+            LogFormat::Json => parse_json_line(line),
+            LogFormat::Syslog => parse_syslog(line),
+            LogFormat::EnvLogger => parse_env_logger(line),
+            LogFormat::Tracing => parse_tracing(line),
+            LogFormat::Java => parse_java(line),
+        }?;
+        entry.raw = line.to_string();
+        Some(entry)
+    }
+}
+
+/// Parse log text into entries, attaching the lines that do not start an
+/// entry (stack frames, wrapped text) to the entry before them.
+///
+/// Lines are split with the format [`LogFormat::detect`] recognizes; without
+/// one, each unindented line starts an entry holding only a message.
+pub fn parse_logs(text: &str) -> Vec<LogEntry> {
+    let format = LogFormat::detect(text.lines());
+    let mut entries: Vec<LogEntry> = Vec::new();
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        let parsed = match format {
+            Some(format) => format.parse_line(line),
+            None if !is_continuation(line) => Some(LogEntry {
+                message: line.trim().to_string(),
+                raw: line.trim_end().to_string(),
+                ..LogEntry::default()
+            }),
+            None => None,
+        };
+        match (parsed, entries.last_mut()) {
+            (Some(entry), _) => entries.push(entry),
+            (None, Some(last)) => last.continuation.push(line.trim_end().to_string()),
+            (None, None) => entries.push(LogEntry {
+                message: line.trim().to_string(),
+                raw: line.trim_end().to_string(),
+                ..LogEntry::default()
+            }),
+        }
+    }
+    entries
+}
+
+/// Re-read the timestamp, level, target, message and fields of `entries` from
+/// their [`raw`](LogEntry::raw) lines, if they are in a recognized format.
+///
+/// Entries whose line does not parse are left as they are; continuation
+/// lines are always kept. Returns the format used.
+pub fn reparse(entries: &mut [LogEntry]) -> Option<LogFormat> {
+    let format = LogFormat::detect(entries.iter().map(|e| e.raw.as_str()))?;
+    for entry in entries.iter_mut() {
+        if let Some(parsed) = format.parse_line(&entry.raw) {
+            let continuation = std::mem::take(&mut entry.continuation);
+            *entry = LogEntry {
+                continuation,
+                ..parsed
+            };
+        }
+    }
+    Some(format)
+}
+
+/// Whether a line continues the entry before it: indented, or a stack trace
+/// line.
+fn is_continuation(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    line.len() != trimmed.len()
+        || [
+            "at ",
+            "Caused by:",
+            "Suppressed:",
+            "... ",
+            "stack backtrace:",
+        ]
+        .iter()
+        .any(|prefix| trimmed.starts_with(prefix))
+}
+
+/// Keys of a JSON log line holding the timestamp, level, target and message,
+/// in order of preference.
+const JSON_TIMESTAMP: &[&str] = &["timestamp", "@timestamp", "time", "ts", "t"];
+const JSON_LEVEL: &[&str] = &["level", "lvl", "severity", "log.level", "levelname"];
+const JSON_TARGET: &[&str] = &[
+    "target",
+    "logger",
+    "logger_name",
+    "name",
+    "module",
+    "source",
+];
+const JSON_MESSAGE: &[&str] = &["message", "msg", "@message"];
+
+fn parse_json_line(line: &str) -> Option<LogEntry> {
+    if !line.starts_with('{') {
+        return None;
+    }
+    let mut object: serde_json::Map<String, Value> = serde_json::from_str(line).ok()?;
+    // tracing-subscriber nests the message and fields under "fields".
+    if let Some(Value::Object(fields)) = object.remove("fields") {
+        object.extend(fields);
+    }
+    let mut take = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| object.remove(*key).filter(|v| !v.is_null()))
+    };
+    let timestamp = take(JSON_TIMESTAMP);
+    let level = take(JSON_LEVEL);
+    let target = take(JSON_TARGET);
+    let message = take(JSON_MESSAGE);
+    if message.is_none() && level.is_none() {
+        return None;
+    }
+    Some(LogEntry {
+        timestamp: timestamp.map(value_text),
+        level: level.and_then(|level| match level {
+            Value::Number(n) => n.as_f64().and_then(LogLevel::from_number),
+            other => LogLevel::parse(&value_text(other)),
+        }),
+        target: target.map(value_text),
+        message: message.map(value_text).unwrap_or_default(),
+        fields: object.into_iter().collect(),
+        ..LogEntry::default()
+    })
+}
+
+/// A JSON value as text, without the quotes of a string.
+fn value_text(value: Value) -> String {
+    match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+fn parse_syslog(line: &str) -> Option<LogEntry> {
+    let (priority, rest) = match line.strip_prefix('<') {
+        Some(rest) => {
+            let (priority, rest) = rest.split_once('>')?;
+            (Some(priority.parse::<u8>().ok()?), rest)
+        }
+        None => (None, line),
+    };
+    let mut entry = match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest)?,
+        None => parse_rfc3164(rest)?,
+    };
+    entry.level = priority.map(LogLevel::from_syslog_priority);
+    Some(entry)
+}
+
+/// `May  1 12:00:00 host tag[pid]: message`, or with an ISO timestamp.
+fn parse_rfc3164(line: &str) -> Option<LogEntry> {
+    let (timestamp, rest) = if MONTHS.iter().any(|m| line.starts_with(m)) {
+        // "Mmm dd hh:mm:ss", with the day padded to two characters.
+        let timestamp = line.get(..15)?;
+        let time = timestamp.get(7..)?;
+        if !is_time(time) {
+            return None;
+        }
+        (timestamp, &line[15..])
+    } else {
+        let (timestamp, rest) = split_token(line)?;
+        if !is_date(timestamp) {
+            return None;
+        }
+        (timestamp, rest)
+    };
+    let (host, rest) = split_token(rest)?;
+    // A time or level here is a `tracing` or Java line, not a host name.
+    if is_time(host) || upper_level(host).is_some() {
+        return None;
+    }
+    let (tag, message) = rest.trim_start().split_once(':')?;
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(LogEntry {
+        timestamp: Some(timestamp.to_string()),
+        target: Some(tag.to_string()),
+        message: message.trim().to_string(),
+        fields: BTreeMap::from([("host".to_string(), Value::from(host))]),
+        ..LogEntry::default()
+    })
+}
+
+/// `timestamp host app procid msgid structured-data message`, after the
+/// version.
+fn parse_rfc5424(line: &str) -> Option<LogEntry> {
+    let (timestamp, rest) = split_token(line)?;
+    let (host, rest) = split_token(rest)?;
+    let (app, rest) = split_token(rest)?;
+    let (procid, rest) = split_token(rest)?;
+    let (msgid, rest) = split_token(rest)?;
+    let rest = rest.trim_start();
+    let (data, message) = match rest.strip_prefix('-') {
+        Some(message) => (None, message),
+        None if rest.starts_with('[') => {
+            let end = structured_data_end(rest)?;
+            (Some(&rest[..end]), &rest[end..])
+        }
+        None => return None,
+    };
+
+    let mut fields = BTreeMap::new();
+    let mut add = |key: &str, value: &str| {
+        if value != "-" {
+            fields.insert(key.to_string(), Value::from(value));
+        }
+    };
+    add("host", host);
+    add("msgid", msgid);
+    add("structured_data", data.unwrap_or("-"));
+    let target = match (app, procid) {
+        ("-", _) => None,
+        (app, "-") => Some(app.to_string()),
+        (app, procid) => Some(format!("{}[{}]", app, procid)),
+    };
+    Some(LogEntry {
+        timestamp: (timestamp != "-").then(|| timestamp.to_string()),
+        target,
+        message: message.trim().trim_start_matches('\u{feff}').to_string(),
+        fields,
+        ..LogEntry::default()
+    })
+}
+
+/// The end of the `[id k="v"]..` elements at the start of `text`.
+fn structured_data_end(text: &str) -> Option<usize> {
+    let mut in_element = false;
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' if in_element => in_quotes = !in_quotes,
+            '[' if !in_element => in_element = true,
+            ']' if in_element && !in_quotes => in_element = false,
+            _ if !in_element => return Some(i),
+            _ => {}
+        }
+    }
+    (!in_element).then_some(text.len())
+}
+
+/// `[timestamp LEVEL target] message`; the timestamp and target are optional.
+fn parse_env_logger(line: &str) -> Option<LogEntry> {
+    let (header, message) = line.strip_prefix('[')?.split_once(']')?;
+    let mut tokens = header.split_whitespace();
+    let mut token = tokens.next()?;
+    let mut timestamp = None;
+    if is_date(token) || is_time(token) {
+        timestamp = Some(token.to_string());
+        token = tokens.next()?;
+    }
+    let level = upper_level(token)?;
+    let target = tokens.next().map(str::to_string);
+    if tokens.next().is_some() {
+        return None;
+    }
+    Some(LogEntry {
+        timestamp,
+        level: Some(level),
+        target,
+        message: message.trim().to_string(),
+        ..LogEntry::default()
+    })
+}
+
+/// `timestamp LEVEL [span{..}:..: ][target: ]message [key=value ..]`.
+fn parse_tracing(line: &str) -> Option<LogEntry> {
+    let (timestamp, rest) = split_token(line)?;
+    if !is_date(timestamp) && !is_time(timestamp) {
+        return None;
+    }
+    let (level, mut rest) = split_token(rest)?;
+    let level = upper_level(level)?;
+    rest = rest.trim_start();
+
+    let mut fields = BTreeMap::new();
+    let mut spans = Vec::new();
+    let mut target = None;
+    while let Some((head, tail)) = rest.split_once(": ") {
+        if is_rust_path(head) {
+            target = Some(head.to_string());
+            rest = tail;
+            break;
+        } else if head.ends_with('}') && head.contains('{') {
+            spans.push(head);
+            rest = tail;
+        } else {
+            break;
+        }
+    }
+    if !spans.is_empty() {
+        fields.insert("spans".to_string(), Value::from(spans.join(":")));
+    }
+    let (message, trailing) = split_trailing_fields(rest);
+    fields.extend(trailing);
+    Some(LogEntry {
+        timestamp: Some(timestamp.to_string()),
+        level: Some(level),
+        target,
+        message: message.to_string(),
+        fields,
+        ..LogEntry::default()
+    })
+}
+
+/// `date time LEVEL [thread] logger - message`, in the orders log4j,
+/// logback and Spring Boot print them.
+fn parse_java(line: &str) -> Option<LogEntry> {
+    let (head, message) = [" - ", " : "]
+        .iter()
+        .filter_map(|sep| line.split_once(sep))
+        .min_by_key(|(head, _)| head.len())?;
+
+    let mut timestamp: Vec<&str> = Vec::new();
+    let mut level = None;
+    let mut thread = None;
+    let mut logger = None;
+    let mut rest = head.trim();
+    while let Some(token) = next_java_token(rest) {
+        rest = rest[token.len()..].trim_start();
+        if level.is_none() && logger.is_none() && (is_date(token) || is_time(token)) {
+            timestamp.push(token);
+        } else if let Some(name) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            thread = Some(name);
+        } else if let (None, Some(l)) = (level, upper_level(token)) {
+            level = Some(l);
+        } else if token == "---" || token.chars().all(|c| c.is_ascii_digit()) {
+            // Spring Boot's process id and separator.
+        } else if logger.is_none() && is_java_name(token) {
+            logger = Some(token);
+        } else {
+            return None;
+        }
+    }
+    if timestamp.is_empty() || level.is_none() || logger.is_none() {
+        return None;
+    }
+    let mut fields = BTreeMap::new();
+    if let Some(thread) = thread {
+        fields.insert("thread".to_string(), Value::from(thread.trim()));
+    }
+    Some(LogEntry {
+        timestamp: Some(timestamp.join(" ")),
+        level,
+        target: logger.map(str::to_string),
+        message: message.trim().to_string(),
+        fields,
+        ..LogEntry::default()
+    })
+}
+
+/// The next token of a Java log line header: a `[thread name]` in brackets,
+/// which may contain spaces, or a word.
+fn next_java_token(text: &str) -> Option<&str> {
+    if text.is_empty() {
+        return None;
+    }
+    if text.starts_with('[') {
+        let end = text.find(']')?;
+        return Some(&text[..=end]);
+    }
+    Some(text.split_whitespace().next().unwrap_or(text))
+}
+
+/// The first whitespace-separated token of `text` and the text after it.
+fn split_token(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim_start();
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.split_at(text.find(char::is_whitespace).unwrap_or(text.len())))
+}
+
+/// A level as printed in upper case by Rust and Java loggers.
+fn upper_level(token: &str) -> Option<LogLevel> {
+    match token {
+        "TRACE" | "DEBUG" | "INFO" | "WARN" | "WARNING" | "ERROR" | "FATAL" | "SEVERE" => {
+            LogLevel::parse(token)
+        }
+        _ => None,
+    }
+}
+
+/// A date, possibly with a time: `2024-05-01`, `2024-05-01T12:00:00Z`.
+fn is_date(token: &str) -> bool {
+    let b = token.as_bytes();
+    b.len() >= 10
+        && b[..4].iter().all(u8::is_ascii_digit)
+        && matches!(b[4], b'-' | b'/')
+        && b[5..7].iter().all(u8::is_ascii_digit)
+        && b[7] == b[4]
+        && b[8..10].iter().all(u8::is_ascii_digit)
+}
+
+/// A time of day: `12:00:00`, `12:00:00.123`, `12:00:00,123`.
+fn is_time(token: &str) -> bool {
+    let b = token.as_bytes();
+    b.len() >= 8
+        && b[..2].iter().all(u8::is_ascii_digit)
+        && b[2] == b':'
+        && b[3..5].iter().all(u8::is_ascii_digit)
+        && b[5] == b':'
+        && b[6..8].iter().all(u8::is_ascii_digit)
+}
+
+/// A Rust module path such as `my_app::db`.
+fn is_rust_path(text: &str) -> bool {
+    !text.is_empty()
+        && text.split("::").all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// A Java class or logger name such as `com.example.App` or `c.e.App`.
+fn is_java_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic())
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '$'))
+}
+
+/// Split `key=value` pairs off the end of a message, as `tracing` prints
+/// fields; values may be double-quoted.
+fn split_trailing_fields(message: &str) -> (&str, BTreeMap<String, Value>) {
+    let mut tokens: Vec<(usize, &str)> = Vec::new();
+    let mut start = None;
+    let mut in_quotes = false;
+    for (i, c) in message.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => {
+                if let Some(s) = start.take() {
+                    tokens.push((s, &message[s..i]));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s) = start {
+        tokens.push((s, &message[s..]));
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut end = message.len();
+    for &(start, token) in tokens.iter().rev() {
+        let Some((key, value)) = token.split_once('=') else {
+            break;
+        };
+        let is_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'));
+        if !is_key {
+            break;
+        }
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        fields.insert(key.to_string(), Value::from(value));
+        end = start;
+    }
+    (message[..end].trim_end(), fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::parse_json;
+
+    #[test]
+    fn test_parse_tracing_and_env_logger() {
+        let entry = LogFormat::Tracing
+            .parse_line(
+                "2024-05-01T12:00:00.123456Z  WARN request{id=7}: my_app::db: slow query \
+                 took_ms=812 sql=\"SELECT 1\"",
+            )
+            .unwrap();
+        assert_eq!(
+            entry.timestamp.as_deref(),
+            Some("2024-05-01T12:00:00.123456Z")
+        );
+        assert_eq!(entry.level, Some(LogLevel::Warn));
+        assert_eq!(entry.target.as_deref(), Some("my_app::db"));
+        assert_eq!(entry.message, "slow query");
+        assert_eq!(entry.fields["took_ms"], "812");
+        assert_eq!(entry.fields["sql"], "SELECT 1");
+        assert_eq!(entry.fields["spans"], "request{id=7}");
+
+        let entry = LogFormat::EnvLogger
+            .parse_line("[2024-05-01T12:00:00Z ERROR my_app::http] bind failed: in use")
+            .unwrap();
+        assert_eq!(entry.level, Some(LogLevel::Error));
+        assert_eq!(entry.target.as_deref(), Some("my_app::http"));
+        assert_eq!(entry.message, "bind failed: in use");
+        assert!(LogFormat::EnvLogger.parse_line("[INFO] started").is_some());
+        assert!(LogFormat::EnvLogger.parse_line("[see below] x").is_none());
+    }
+
+    #[test]
+    fn test_parse_json_and_syslog() {
+        let entry = LogFormat::Json
+            .parse_line(
+                r#"{"timestamp":"2024-05-01T12:00:00Z","level":"INFO","fields":{"message":"ready","port":8080},"target":"my_app"}"#,
+            )
+            .unwrap();
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert_eq!(entry.message, "ready");
+        assert_eq!(entry.fields["port"], 8080);
+        let pino = LogFormat::Json
+            .parse_line(r#"{"level":50,"time":1714564800000,"msg":"boom"}"#)
+            .unwrap();
+        assert_eq!(pino.level, Some(LogLevel::Error));
+        assert_eq!(pino.timestamp.as_deref(), Some("1714564800000"));
+
+        let entry = LogFormat::Syslog
+            .parse_line("<34>May  1 12:00:00 web1 sshd[812]: Failed password for root")
+            .unwrap();
+        assert_eq!(entry.timestamp.as_deref(), Some("May  1 12:00:00"));
+        assert_eq!(entry.level, Some(LogLevel::Fatal));
+        assert_eq!(entry.target.as_deref(), Some("sshd[812]"));
+        assert_eq!(entry.fields["host"], "web1");
+        let entry = LogFormat::Syslog
+            .parse_line(
+                "<165>1 2003-10-11T22:14:15.003Z mymachine evntslog - ID47 \
+                 [exampleSDID@32473 iut=\"3\"] An application event",
+            )
+            .unwrap();
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert_eq!(entry.target.as_deref(), Some("evntslog"));
+        assert_eq!(entry.message, "An application event");
+        assert_eq!(entry.fields["msgid"], "ID47");
+    }
+
+    #[test]
+    fn test_parse_logs_groups_java_stack_traces() {
+        let text = "\
+2024-05-01 12:00:00,123 INFO  [main] com.example.App - Starting
+2024-05-01 12:00:01,456 ERROR [http-nio-8080-exec-1] com.example.OrderService - Order failed
+java.lang.IllegalStateException: boom
+\tat com.example.OrderService.place(OrderService.java:42)
+Caused by: java.io.IOException: closed
+\t... 12 more
+";
+        assert_eq!(LogFormat::detect(text.lines()), Some(LogFormat::Java));
+        let entries = parse_logs(text);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[1].timestamp.as_deref(),
+            Some("2024-05-01 12:00:01,456")
+        );
+        assert_eq!(
+            entries[1].target.as_deref(),
+            Some("com.example.OrderService")
+        );
+        assert_eq!(entries[1].fields["thread"], "http-nio-8080-exec-1");
+        assert_eq!(entries[1].message, "Order failed");
+        assert_eq!(entries[1].continuation.len(), 4);
+
+        let spring = LogFormat::Java
+            .parse_line(
+                "2024-05-01T12:00:00.123Z  WARN 4242 --- [main] o.s.b.w.e.tomcat.TomcatWebServer \
+                 : Port in use",
+            )
+            .unwrap();
+        assert_eq!(spring.level, Some(LogLevel::Warn));
+        assert_eq!(spring.message, "Port in use");
+
+        let plain = parse_logs("starting\n  detail\nstopped");
+        assert_eq!(plain.len(), 2);
+        assert_eq!(plain[0].continuation, ["  detail"]);
+    }
+
+    #[test]
+    fn test_reparse_model_entries() {
+        let answer: LogEntries = parse_json(
+            r#"{"entries": [
+                {"level": "WARNING", "message": "INFO my_app: up", "continuation": ["  at x"],
+                 "raw": "2024-05-01T12:00:00Z  INFO my_app: up"},
+                {"message": "a wrapped line", "raw": "a wrapped line"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut entries = answer.entries;
+        assert_eq!(reparse(&mut entries), Some(LogFormat::Tracing));
+        assert_eq!(entries[0].level, Some(LogLevel::Info));
+        assert_eq!(entries[0].message, "up");
+        assert_eq!(entries[0].continuation, ["  at x"]);
+        assert_eq!(entries[1].message, "a wrapped line");
+        assert_eq!(LogLevel::parse("crit"), Some(LogLevel::Fatal));
+        assert!(LogLevel::Warn < LogLevel::Error);
+    }
+}
@@ -0,0 +1,555 @@
+//! Model Context Protocol (MCP) server exposing the text-returning tools in
+//! [`tools`].
+//!
+//! Speaks JSON-RPC 2.0 over newline-delimited stdio, as expected by MCP clients
+//! such as Claude Desktop or Cursor. The `glm-vision-mcp` binary wires this up
+//! with a [`VisionConfig`](crate::VisionConfig) read from the environment.
+
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::client::VisionClient;
+use crate::error::{Result, VisionError};
+use crate::http::HttpClient;
+use crate::tools;
+
+/// Latest MCP protocol revision implemented by this server.
+pub const PROTOCOL_VERSION: &str = "2025-06-18";
+
+/// Protocol revisions this server can speak, oldest first.
+const SUPPORTED_VERSIONS: &[&str] = &["2024-11-05", "2025-03-26", "2025-06-18"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Returns the MCP tool definitions (`name`, `description`, `inputSchema`) for
+/// the tools served over MCP: `analyze_image`, `extract_text`,
+/// `diagnose_error`, `understand_diagram`, `analyze_data_viz`,
+/// `ui_diff_check`, `ui_to_artifact` and `analyze_video`.
+///
+/// The typed tools in [`tools`] (`extract_tables`, `extract_chart_data`,
+/// `extract_logs`, `extract_code`, `diagram_to_source`, `locate_elements` and
+/// `ui_diff_report`) are not exposed.
+pub fn tool_definitions() -> Vec<Value> {
+    let image_source = json!({
+        "type": "string",
+        "description": "Local file path, HTTP(S) URL or base64 data URL of the image"
+    });
+    let prompt = |description: &str| json!({ "type": "string", "description": description });
+
+    vec![
+        tool(
+            "analyze_image",
+            "General-purpose image description and analysis.",
+            json!({
+                "image_source": image_source,
+                "prompt": prompt("What to analyze or describe in the image")
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "extract_text",
+            "Extract text, code, logs or documentation from a screenshot.",
+            json!({
+                "image_source": image_source,
+                "prompt": prompt("Instructions for the extraction"),
+                "programming_language": {
+                    "type": "string",
+                    "description": "Optional programming language of code in the screenshot"
+                }
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "diagnose_error",
+            "Diagnose an error screenshot with root cause and fix suggestions.",
+            json!({
+                "image_source": image_source,
+                "prompt": prompt("What to diagnose about the error"),
+                "context": {
+                    "type": "string",
+                    "description": "Optional context, e.g. what was being done when the error occurred"
+                }
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "understand_diagram",
+            "Analyze UML, flowcharts, ER, sequence and architecture diagrams.",
+            json!({
+                "image_source": image_source,
+                "prompt": prompt("What to explain about the diagram"),
+                "diagram_type": {
+                    "type": "string",
+                    "description": "Optional diagram type hint, e.g. \"UML class\", \"sequence\", \"ER\""
+                }
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "analyze_data_viz",
+            "Analyze charts, graphs and dashboards.",
+            json!({
+                "image_source": image_source,
+                "prompt": prompt("What to analyze in the visualization"),
+                "analysis_focus": {
+                    "type": "string",
+                    "description": "Optional focus, e.g. \"trends\", \"anomalies\", \"comparison\""
+                }
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "ui_diff_check",
+            "Compare two UI screenshots (expected vs actual) for visual regression.",
+            json!({
+                "expected": {
+                    "type": "string",
+                    "description": "Local file path, HTTP(S) URL or base64 data URL of the expected/reference screenshot"
+                },
+                "actual": {
+                    "type": "string",
+                    "description": "Local file path, HTTP(S) URL or base64 data URL of the actual/current screenshot"
+                },
+                "prompt": prompt("What to compare")
+            }),
+            &["expected", "actual", "prompt"],
+        ),
+        tool(
+            "ui_to_artifact",
+            "Convert a UI screenshot into code, an LLM prompt, a technical spec or a description.",
+            json!({
+                "image_source": image_source,
+                "output_type": {
+                    "type": "string",
+                    "enum": ["code", "prompt", "spec", "description"],
+                    "description": "Artifact to generate (default: code)"
+                },
+                "prompt": prompt("Instructions for the generated artifact")
+            }),
+            &["image_source", "prompt"],
+        ),
+        tool(
+            "analyze_video",
+            "Analyze video content.",
+            json!({
+                "video_source": {
+                    "type": "string",
+                    "description": "Local file path, HTTP(S) URL or base64 data URL of the video"
+                },
+                "prompt": prompt("What to analyze in the video")
+            }),
+            &["video_source", "prompt"],
+        ),
+    ]
+}
+
+fn tool(name: &str, description: &str, properties: Value, required: &[&str]) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": {
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false
+        }
+    })
+}
+
+fn required_str<'a>(args: &'a Value, key: &str) -> Result<&'a str> {
+    args.get(key)
+        .and_then(Value::as_str)
+        .ok_or_else(|| VisionError::InvalidRequest(format!("Missing required argument '{}'", key)))
+}
+
+fn optional_str<'a>(args: &'a Value, key: &str) -> Option<&'a str> {
+    args.get(key).and_then(Value::as_str)
+}
+
+/// Dispatch a `tools/call` by name with JSON arguments.
+pub async fn call_tool(
+    client: &VisionClient<impl HttpClient>,
+    name: &str,
+    args: &Value,
+) -> Result<String> {
+    match name {
+        "analyze_image" => {
+            tools::analyze_image(
+                client,
+                required_str(args, "image_source")?,
+                required_str(args, "prompt")?,
+            )
+            .await
+        }
+        "extract_text" => {
+            tools::extract_text(
+                client,
+                required_str(args, "image_source")?,
+                required_str(args, "prompt")?,
+                optional_str(args, "programming_language"),
+            )
+            .await
+        }
+        "diagnose_error" => {
+            tools::diagnose_error(
+                client,
+                required_str(args, "image_source")?,
+                required_str(args, "prompt")?,
+                optional_str(args, "context"),
+            )
+            .await
+        }
+        "understand_diagram" => {
+            tools::understand_diagram(
+                client,
+                required_str(args, "image_source")?,
+                required_str(args, "prompt")?,
+                optional_str(args, "diagram_type"),
+            )
+            .await
+        }
+        "analyze_data_viz" => {
+            tools::analyze_data_viz(
+                client,
+                required_str(args, "image_source")?,
+                required_str(args, "prompt")?,
+                optional_str(args, "analysis_focus"),
+            )
+            .await
+        }
+        "ui_diff_check" => {
+            tools::ui_diff_check(
+                client,
+                required_str(args, "expected")?,
+                required_str(args, "actual")?,
+                required_str(args, "prompt")?,
+            )
+            .await
+        }
+        "ui_to_artifact" => {
+            tools::ui_to_artifact(
+                client,
+                required_str(args, "image_source")?,
+                optional_str(args, "output_type"),
+                required_str(args, "prompt")?,
+            )
+            .await
+        }
+        "analyze_video" => {
+            tools::analyze_video(
+                client,
+                required_str(args, "video_source")?,
+                required_str(args, "prompt")?,
+            )
+            .await
+        }
+        _ => Err(VisionError::InvalidRequest(format!(
+            "Unknown tool '{}'",
+            name
+        ))),
+    }
+}
+
+fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() }
+    })
+}
+
+/// Handle one JSON-RPC message, returning the response to send (if any).
+///
+/// Notifications (messages without an `id`) never produce a response.
+pub async fn handle_message(
+    client: &VisionClient<impl HttpClient>,
+    message: Value,
+) -> Option<Value> {
+    let id = message.get("id").cloned();
+    let Some(method) = message.get("method").and_then(Value::as_str) else {
+        return id.map(|id| error_response(id, INVALID_REQUEST, "Missing method"));
+    };
+    // Notifications (e.g. notifications/initialized) need no reply.
+    let id = id?;
+    let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+    Some(match method {
+        "initialize" => {
+            let requested = params.get("protocolVersion").and_then(Value::as_str);
+            let version = requested
+                .filter(|v| SUPPORTED_VERSIONS.contains(v))
+                .unwrap_or(PROTOCOL_VERSION);
+            response(
+                id,
+                json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": { "listChanged": false } },
+                    "serverInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION")
+                    }
+                }),
+            )
+        }
+        "ping" => response(id, json!({})),
+        "tools/list" => response(id, json!({ "tools": tool_definitions() })),
+        "tools/call" => {
+            let Some(name) = params.get("name").and_then(Value::as_str) else {
+                return Some(error_response(id, INVALID_PARAMS, "Missing tool name"));
+            };
+            let args = params.get("arguments").cloned().unwrap_or(json!({}));
+            // Tool failures are reported in the result so the model can see them.
+            let (text, is_error) = match call_tool(client, name, &args).await {
+                Ok(text) => (text, false),
+                Err(e) => (e.to_string(), true),
+            };
+            response(
+                id,
+                json!({
+                    "content": [{ "type": "text", "text": text }],
+                    "isError": is_error
+                }),
+            )
+        }
+        _ => error_response(
+            id,
+            METHOD_NOT_FOUND,
+            format!("Method not found: {}", method),
+        ),
+    })
+}
+
+/// Serve MCP requests from `reader` until EOF, writing responses to `writer`.
+///
+/// Each request is handled on its own task, so long-running tool calls do not
+/// block `ping` or other requests. Responses may therefore arrive out of order.
+pub async fn serve<H, R, W>(
+    client: Arc<VisionClient<H>>,
+    reader: R,
+    mut writer: W,
+) -> std::io::Result<()>
+where
+    H: HttpClient + 'static,
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let mut line = message.to_string();
+            line.push('\n');
+            writer.write_all(line.as_bytes()).await?;
+            writer.flush().await?;
+        }
+        Ok::<_, std::io::Error>(())
+    });
+
+    let mut lines = reader.lines();
+    let mut handlers = tokio::task::JoinSet::new();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let message: Value = match serde_json::from_str(&line) {
+            Ok(m) => m,
+            Err(e) => {
+                let _ = tx.send(error_response(Value::Null, PARSE_ERROR, e.to_string()));
+                continue;
+            }
+        };
+        let client = Arc::clone(&client);
+        let tx = tx.clone();
+        handlers.spawn(async move {
+            if let Some(reply) = handle_message(&client, message).await {
+                let _ = tx.send(reply);
+            }
+        });
+    }
+
+    while handlers.join_next().await.is_some() {}
+    drop(tx);
+    writer_task.await.map_err(std::io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VisionConfig;
+    use crate::http::HttpResponse;
+
+    struct EchoHttp;
+
+    impl HttpClient for EchoHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            body: &[u8],
+        ) -> std::result::Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            let request: Value = serde_json::from_slice(body)?;
+            let prompt = request["messages"][1]["content"]
+                .as_array()
+                .and_then(|parts| parts.last())
+                .and_then(|p| p["text"].as_str())
+                .unwrap_or_default()
+                .to_string();
+            Ok(HttpResponse {
+                status: 200,
+                body: json!({ "choices": [{ "message": { "content": prompt } }] }).to_string(),
+            })
+        }
+    }
+
+    fn test_client() -> VisionClient<EchoHttp> {
+        VisionClient::new(
+            VisionConfig::new("k").with_base_url("https://api.test/v4/"),
+            EchoHttp,
+        )
+    }
+
+    #[test]
+    fn test_tool_definitions() {
+        let defs = tool_definitions();
+        let names: Vec<&str> = defs.iter().map(|d| d["name"].as_str().unwrap()).collect();
+        assert_eq!(
+            names,
+            [
+                "analyze_image",
+                "extract_text",
+                "diagnose_error",
+                "understand_diagram",
+                "analyze_data_viz",
+                "ui_diff_check",
+                "ui_to_artifact",
+                "analyze_video"
+            ]
+        );
+        for def in &defs {
+            let schema = &def["inputSchema"];
+            assert_eq!(schema["type"], "object");
+            for required in schema["required"].as_array().unwrap() {
+                let key = required.as_str().unwrap();
+                assert!(schema["properties"].get(key).is_some(), "{key} missing");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list() {
+        let client = test_client();
+        let init = handle_message(
+            &client,
+            json!({"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2024-11-05"}}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(init["result"]["protocolVersion"], "2024-11-05");
+        assert!(init["result"]["capabilities"]["tools"].is_object());
+
+        let notification = json!({"jsonrpc":"2.0","method":"notifications/initialized"});
+        assert!(handle_message(&client, notification).await.is_none());
+
+        let list = handle_message(
+            &client,
+            json!({"jsonrpc":"2.0","id":"a","method":"tools/list"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(list["id"], "a");
+        assert_eq!(list["result"]["tools"].as_array().unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_tools_call() {
+        let client = test_client();
+        let reply = handle_message(
+            &client,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "tools/call",
+                "params": {
+                    "name": "diagnose_error",
+                    "arguments": {
+                        "image_source": "https://example.com/err.png",
+                        "prompt": "Why?",
+                        "context": "cargo build"
+                    }
+                }
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(reply["result"]["isError"], false);
+        assert_eq!(
+            reply["result"]["content"][0]["text"],
+            "Context: cargo build\n\nWhy?"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tools_call_errors() {
+        let client = test_client();
+        let missing = handle_message(
+            &client,
+            json!({"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"analyze_image","arguments":{"prompt":"x"}}}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(missing["result"]["isError"], true);
+        assert!(missing["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("image_source"));
+
+        let unknown = handle_message(
+            &client,
+            json!({"jsonrpc":"2.0","id":4,"method":"resources/list"}),
+        )
+        .await
+        .unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_serve_over_stdio_pipes() {
+        let input = concat!(
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"ping\"}\n",
+            "not json\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"notifications/initialized\"}\n",
+        );
+        let (client_end, server_end) = tokio::io::duplex(4096);
+        serve(
+            Arc::new(test_client()),
+            tokio::io::BufReader::new(input.as_bytes()),
+            server_end,
+        )
+        .await
+        .unwrap();
+
+        let mut output = String::new();
+        let mut reader = tokio::io::BufReader::new(client_end);
+        tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut output)
+            .await
+            .unwrap();
+        let replies: Vec<Value> = output
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert!(replies
+            .iter()
+            .any(|r| r["id"] == 1 && r["result"].is_object()));
+        assert!(replies.iter().any(|r| r["error"]["code"] == PARSE_ERROR));
+    }
+}
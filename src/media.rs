@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+
+use tokio::io::AsyncRead;
+
+use crate::types::is_url;
+
+/// An image or video input for [`VisionClient::process_image`](crate::VisionClient::process_image),
+/// [`VisionClient::process_video`](crate::VisionClient::process_video) and the
+/// functions in [`tools`](crate::tools).
+///
+/// Strings convert automatically: `http(s)://` URLs become [`Url`](MediaSource::Url),
+/// `data:` URLs become [`DataUrl`](MediaSource::DataUrl), and anything else is a
+/// [`Path`](MediaSource::Path). In-memory and reader sources go through the same
+/// type detection and size limits as local files.
+///
+/// ```ignore
+/// let png: Vec<u8> = browser.screenshot().await?;
+/// tools::diagnose_error(&client, MediaSource::bytes(png), "What went wrong?", None).await?;
+///
+/// let object = s3.get_object(...).await?.body.into_async_read();
+/// tools::analyze_image(&client, MediaSource::reader(object), "Describe this.").await?;
+/// ```
+pub enum MediaSource {
+    /// A local file.
+    Path(PathBuf),
+    /// An http(s) URL, passed to the API unchanged.
+    Url(String),
+    /// Encoded media bytes, with an optional declared MIME type.
+    Bytes { data: Vec<u8>, mime: Option<String> },
+    /// A reader yielding encoded media bytes, with an optional declared MIME type.
+    ///
+    /// Reading stops with an error once it passes `max_image_size_mb` (or
+    /// `max_video_size_mb` for videos), or
+    /// [`MAX_IMAGE_INPUT_MB`](crate::client::MAX_IMAGE_INPUT_MB) (or
+    /// [`MAX_VIDEO_INPUT_MB`](crate::client::MAX_VIDEO_INPUT_MB)) when the
+    /// media is decoded to be converted, resized or sampled.
+    Reader {
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        mime: Option<String>,
+    },
+    /// A `data:<mime>;base64,<payload>` URL.
+    DataUrl(String),
+}
+
+impl MediaSource {
+    /// Media held in memory; the type is detected from the content.
+    pub fn bytes(data: impl Into<Vec<u8>>) -> Self {
+        MediaSource::Bytes {
+            data: data.into(),
+            mime: None,
+        }
+    }
+
+    /// Media held in memory with a declared MIME type, checked against the content.
+    pub fn bytes_with_mime(data: impl Into<Vec<u8>>, mime: impl Into<String>) -> Self {
+        MediaSource::Bytes {
+            data: data.into(),
+            mime: Some(mime.into()),
+        }
+    }
+
+    /// Media read from an async reader; the type is detected from the content.
+    pub fn reader(reader: impl AsyncRead + Send + Unpin + 'static) -> Self {
+        MediaSource::Reader {
+            reader: Box::new(reader),
+            mime: None,
+        }
+    }
+
+    /// Short description used in error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            MediaSource::Path(path) => path.display().to_string(),
+            MediaSource::Url(url) => url.clone(),
+            MediaSource::Bytes { data, .. } => format!("<{} bytes>", data.len()),
+            MediaSource::Reader { .. } => "<reader>".to_string(),
+            MediaSource::DataUrl(_) => "<data URL>".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Debug for MediaSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MediaSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            MediaSource::Url(url) => f.debug_tuple("Url").field(url).finish(),
+            MediaSource::Bytes { data, mime } => f
+                .debug_struct("Bytes")
+                .field("len", &data.len())
+                .field("mime", mime)
+                .finish(),
+            MediaSource::Reader { mime, .. } => f
+                .debug_struct("Reader")
+                .field("mime", mime)
+                .finish_non_exhaustive(),
+            MediaSource::DataUrl(url) => f.debug_tuple("DataUrl").field(&url.len()).finish(),
+        }
+    }
+}
+
+impl From<&str> for MediaSource {
+    fn from(source: &str) -> Self {
+        if is_url(source) {
+            MediaSource::Url(source.to_string())
+        } else if source.starts_with("data:") {
+            MediaSource::DataUrl(source.to_string())
+        } else {
+            MediaSource::Path(PathBuf::from(source))
+        }
+    }
+}
+
+impl From<&String> for MediaSource {
+    fn from(source: &String) -> Self {
+        source.as_str().into()
+    }
+}
+
+impl From<String> for MediaSource {
+    fn from(source: String) -> Self {
+        source.as_str().into()
+    }
+}
+
+impl From<&Path> for MediaSource {
+    fn from(path: &Path) -> Self {
+        MediaSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<PathBuf> for MediaSource {
+    fn from(path: PathBuf) -> Self {
+        MediaSource::Path(path)
+    }
+}
+
+impl From<Vec<u8>> for MediaSource {
+    fn from(data: Vec<u8>) -> Self {
+        MediaSource::bytes(data)
+    }
+}
+
+/// Split a base64 data URL into its MIME type and decoded payload.
+pub(crate) fn decode_data_url(url: &str) -> Option<(&str, Vec<u8>)> {
+    let rest = url.strip_prefix("data:")?;
+    let (meta, payload) = rest.split_once(',')?;
+    let mime = meta.strip_suffix(";base64")?;
+    let data =
+        base64::Engine::decode(&base64::engine::general_purpose::STANDARD, payload.trim()).ok()?;
+    Some((mime, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_str_classification() {
+        assert!(matches!(
+            MediaSource::from("https://example.com/a.png"),
+            MediaSource::Url(_)
+        ));
+        assert!(matches!(
+            MediaSource::from("data:image/png;base64,AAAA"),
+            MediaSource::DataUrl(_)
+        ));
+        assert!(matches!(
+            MediaSource::from("shots/a.png"),
+            MediaSource::Path(_)
+        ));
+        assert!(matches!(
+            MediaSource::from(vec![1u8, 2, 3]),
+            MediaSource::Bytes { mime: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_decode_data_url() {
+        let (mime, data) = decode_data_url("data:image/png;base64,aGVsbG8=").unwrap();
+        assert_eq!(mime, "image/png");
+        assert_eq!(data, b"hello");
+        assert!(decode_data_url("data:text/plain,hello").is_none());
+        assert!(decode_data_url("data:image/png;base64,!!!").is_none());
+    }
+}
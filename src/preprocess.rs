@@ -0,0 +1,480 @@
+//! Downscaling, recompression and format conversion of local images (feature `image`).
+//!
+//! Used by [`VisionClient::process_image`](crate::VisionClient::process_image) to
+//! convert WebP, GIF, BMP and TIFF files and, when
+//! [`VisionConfig::image_resize`](crate::VisionConfig::image_resize) is set, to
+//! shrink oversized images; by
+//! [`VisionClient::process_image_frames`](crate::VisionClient::process_image_frames)
+//! to expand animated GIFs; by
+//! [`VisionClient::process_image_tiles`](crate::VisionClient::process_image_tiles)
+//! to split large screenshots into overlapping tiles; and by
+//! [`VisionClient::prepare_image`](crate::VisionClient::prepare_image), which also
+//! reports the [`ImageScale`] needed to map coordinates in the model's answer back
+//! to the original image.
+
+use std::io::Cursor;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilter, PngEncoder};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, DynamicImage, ImageReader};
+
+use crate::config::{ImageResize, ImageTiling};
+use crate::error::{Result, VisionError};
+use crate::types::is_supported_image;
+
+/// Factor applied to the dimensions when no encoding fits at the current size.
+const SHRINK_STEP: f64 = 0.75;
+
+/// Step between the JPEG qualities tried at each size.
+const QUALITY_STEP: u8 = 10;
+
+/// Smallest edge length the pipeline will shrink an image to.
+const MIN_EDGE: u32 = 64;
+
+/// Tiles grow by `1 / TILE_GROWTH` of their size when there are too many.
+const TILE_GROWTH: u32 = 4;
+
+/// How an image was resized, for mapping coordinates back to the original.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageScale {
+    /// Width of the original image in pixels.
+    pub original_width: u32,
+    /// Height of the original image in pixels.
+    pub original_height: u32,
+    /// Width of the image sent to the API.
+    pub width: u32,
+    /// Height of the image sent to the API.
+    pub height: u32,
+}
+
+impl ImageScale {
+    /// Scale factor from original to sent image (1.0 when unchanged).
+    pub fn factor(&self) -> f64 {
+        self.width as f64 / self.original_width as f64
+    }
+
+    /// Returns `true` if the image was sent at its original size.
+    pub fn is_unscaled(&self) -> bool {
+        self.width == self.original_width && self.height == self.original_height
+    }
+
+    /// Map a pixel coordinate in the sent image back to the original image.
+    pub fn to_original(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            x * self.original_width as f64 / self.width as f64,
+            y * self.original_height as f64 / self.height as f64,
+        )
+    }
+}
+
+/// An image that fits the configured limits.
+#[derive(Debug, Clone)]
+pub struct FittedImage {
+    /// Encoded image bytes (the original bytes if no change was needed).
+    pub data: Vec<u8>,
+    /// MIME type of `data`.
+    pub mime: &'static str,
+    /// Dimensions before and after resizing.
+    pub scale: ImageScale,
+}
+
+/// Downscale and re-encode `data` until it fits within `options` and `max_bytes`.
+///
+/// `mime` is the type of `data`. PNG and JPEG images already within every limit
+/// are returned unchanged. Otherwise anything but JPEG is first encoded as an
+/// optimized PNG, then as JPEG from `jpeg_quality` down to `min_jpeg_quality`; if nothing fits, the
+/// image is shrunk further and the encodings are retried.
+pub fn fit_image(
+    data: &[u8],
+    mime: &'static str,
+    max_bytes: u64,
+    options: &ImageResize,
+) -> Result<FittedImage> {
+    let (width, height) = image_dimensions(data)?;
+    let scale = target_scale(width, height, options);
+    if scale >= 1.0 && data.len() as u64 <= max_bytes && is_supported_image(mime) {
+        return Ok(FittedImage {
+            data: data.to_vec(),
+            mime,
+            scale: ImageScale {
+                original_width: width,
+                original_height: height,
+                width,
+                height,
+            },
+        });
+    }
+
+    let image = decode_image(data)?;
+    fit_decoded(&image, mime, max_bytes, options)
+}
+
+/// Downscale and encode an already decoded image until it fits within `options`
+/// and `max_bytes`.
+///
+/// Like [`fit_image`], but always re-encodes: as PNG unless `mime` is
+/// `image/jpeg`, falling back to JPEG.
+pub fn fit_decoded(
+    image: &DynamicImage,
+    mime: &str,
+    max_bytes: u64,
+    options: &ImageResize,
+) -> Result<FittedImage> {
+    let (width, height) = (image.width(), image.height());
+    let mut scale = target_scale(width, height, options);
+    let min_quality = options.min_jpeg_quality.clamp(1, 100);
+    let max_quality = options.jpeg_quality.clamp(min_quality, 100);
+    loop {
+        let (w, h) = scaled_dimensions(width, height, scale);
+        let resized;
+        let current = if (w, h) == (width, height) {
+            image
+        } else {
+            resized = image.resize_exact(w, h, FilterType::Lanczos3);
+            &resized
+        };
+        let fitted = |data: Vec<u8>, mime| FittedImage {
+            data,
+            mime,
+            scale: ImageScale {
+                original_width: width,
+                original_height: height,
+                width: w,
+                height: h,
+            },
+        };
+
+        if mime != "image/jpeg" {
+            let png = encode_png(current)?;
+            if png.len() as u64 <= max_bytes {
+                return Ok(fitted(png, "image/png"));
+            }
+        }
+        let mut quality = max_quality;
+        loop {
+            let jpeg = encode_jpeg(current, quality)?;
+            if jpeg.len() as u64 <= max_bytes {
+                return Ok(fitted(jpeg, "image/jpeg"));
+            }
+            if quality == min_quality {
+                break;
+            }
+            quality = quality.saturating_sub(QUALITY_STEP).max(min_quality);
+        }
+
+        if w.max(h) <= MIN_EDGE {
+            return Err(VisionError::InvalidMedia(format!(
+                "Image cannot be compressed below {} bytes",
+                max_bytes
+            )));
+        }
+        scale = scale.min(1.0) * SHRINK_STEP;
+    }
+}
+
+/// A tile of a larger image, in pixels of the original image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRegion {
+    /// Row of the tile in the grid, from the top.
+    pub row: usize,
+    /// Column of the tile in the grid, from the left.
+    pub column: usize,
+    /// Left edge of the tile.
+    pub x: u32,
+    /// Top edge of the tile.
+    pub y: u32,
+    /// Width of the tile.
+    pub width: u32,
+    /// Height of the tile.
+    pub height: u32,
+}
+
+impl TileRegion {
+    /// The part of this region inside a `width` x `height` image, if any.
+    pub fn clip(&self, width: u32, height: u32) -> Option<TileRegion> {
+        if self.x >= width || self.y >= height {
+            return None;
+        }
+        Some(TileRegion {
+            width: self.width.min(width - self.x),
+            height: self.height.min(height - self.y),
+            ..*self
+        })
+    }
+}
+
+/// Split a `width` x `height` image into overlapping tiles, in reading order.
+///
+/// Tiles are spread evenly from the top left to the bottom right edge, with
+/// neighbours sharing at least `overlap` pixels. Tiles are enlarged, on the axis with the
+/// most of them, until there are at most `max_tiles`.
+pub fn tile_layout(width: u32, height: u32, tiling: &ImageTiling) -> Vec<TileRegion> {
+    let max_tiles = tiling.max_tiles.max(1);
+    let mut tile_width = tiling.tile_width.max(1);
+    let mut tile_height = tiling.tile_height.max(1);
+    loop {
+        let xs = tile_starts(width, tile_width, tiling.overlap);
+        let ys = tile_starts(height, tile_height, tiling.overlap);
+        if xs.len() * ys.len() <= max_tiles {
+            let (tile_width, tile_height) = (tile_width.min(width), tile_height.min(height));
+            return ys
+                .iter()
+                .enumerate()
+                .flat_map(|(row, &y)| {
+                    xs.iter().enumerate().map(move |(column, &x)| TileRegion {
+                        row,
+                        column,
+                        x,
+                        y,
+                        width: tile_width,
+                        height: tile_height,
+                    })
+                })
+                .collect();
+        }
+        if xs.len() >= ys.len() {
+            tile_width += tile_width.div_ceil(TILE_GROWTH);
+        } else {
+            tile_height += tile_height.div_ceil(TILE_GROWTH);
+        }
+    }
+}
+
+/// Offsets of the tiles along one axis of length `len`, spread evenly so
+/// neighbours share at least `overlap` pixels (at most half a tile).
+fn tile_starts(len: u32, tile: u32, overlap: u32) -> Vec<u32> {
+    if len <= tile {
+        return vec![0];
+    }
+    let (span, stride) = ((len - tile) as u64, (tile - overlap.min(tile / 2)) as u64);
+    let gaps = span.div_ceil(stride);
+    (0..=gaps).map(|i| (i * span / gaps) as u32).collect()
+}
+
+/// Dimensions of an encoded image, read from its header.
+pub fn image_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    reader(data)?
+        .into_dimensions()
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to read image: {}", e)))
+}
+
+/// Decode an encoded image.
+pub fn decode_image(data: &[u8]) -> Result<DynamicImage> {
+    reader(data)?
+        .decode()
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to decode image: {}", e)))
+}
+
+/// Decode an animated GIF into at most `max_frames` evenly spaced frames, each
+/// encoded as PNG.
+///
+/// The GIF is decoded twice, holding one frame at a time: once to count the
+/// frames, then again to keep only the sampled ones.
+pub fn gif_frames(data: &[u8], max_frames: usize) -> Result<Vec<Vec<u8>>> {
+    let frames_of = || {
+        GifDecoder::new(Cursor::new(data))
+            .map(|decoder| decoder.into_frames())
+            .map_err(|e| VisionError::InvalidMedia(format!("Failed to decode GIF: {}", e)))
+    };
+    let decode_error = |e| VisionError::InvalidMedia(format!("Failed to decode GIF: {}", e));
+
+    let mut count = 0;
+    for frame in frames_of()? {
+        frame.map_err(decode_error)?;
+        count += 1;
+    }
+
+    let mut wanted = sample_indices(count, max_frames).into_iter().peekable();
+    let mut frames = Vec::new();
+    for (i, frame) in frames_of()?.enumerate() {
+        if wanted.peek().is_none() {
+            break;
+        }
+        let frame = frame.map_err(decode_error)?;
+        if wanted.next_if_eq(&i).is_some() {
+            frames.push(encode_png(&DynamicImage::ImageRgba8(frame.into_buffer()))?);
+        }
+    }
+    Ok(frames)
+}
+
+/// Indices of `count` items thinned to at most `max` evenly spaced ones,
+/// always including the first and last.
+pub(crate) fn sample_indices(count: usize, max: usize) -> Vec<usize> {
+    if count <= max {
+        return (0..count).collect();
+    }
+    match max {
+        0 => Vec::new(),
+        1 => vec![0],
+        _ => (0..max).map(|i| i * (count - 1) / (max - 1)).collect(),
+    }
+}
+
+fn reader(data: &[u8]) -> Result<ImageReader<Cursor<&[u8]>>> {
+    ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to read image: {}", e)))
+}
+
+/// Largest scale (at most 1.0) that satisfies `max_edge` and `max_pixels`.
+fn target_scale(width: u32, height: u32, options: &ImageResize) -> f64 {
+    let mut scale: f64 = 1.0;
+    if let Some(max_edge) = options.max_edge {
+        scale = scale.min(max_edge as f64 / width.max(height) as f64);
+    }
+    if let Some(max_pixels) = options.max_pixels {
+        let pixels = width as f64 * height as f64;
+        scale = scale.min((max_pixels as f64 / pixels).sqrt());
+    }
+    scale
+}
+
+fn scaled_dimensions(width: u32, height: u32, scale: f64) -> (u32, u32) {
+    if scale >= 1.0 {
+        return (width, height);
+    }
+    let w = ((width as f64 * scale).floor() as u32).max(1);
+    let h = ((height as f64 * scale).floor() as u32).max(1);
+    (w, h)
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let encoder =
+        PngEncoder::new_with_quality(&mut buf, CompressionType::Best, PngFilter::Adaptive);
+    image
+        .write_with_encoder(encoder)
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to encode PNG: {}", e)))?;
+    Ok(buf)
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut buf, quality);
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(encoder)
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to encode JPEG: {}", e)))?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A noisy image that compresses poorly, so size limits actually bite.
+    fn noisy_png(width: u32, height: u32) -> Vec<u8> {
+        let mut seed: u32 = 0x1234_5678;
+        let image = RgbImage::from_fn(width, height, |_, _| {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let [r, g, b, _] = seed.to_le_bytes();
+            Rgb([r, g, b])
+        });
+        encode_png(&DynamicImage::ImageRgb8(image)).unwrap()
+    }
+
+    #[test]
+    fn test_fit_image_unchanged_when_within_limits() {
+        let png = noisy_png(32, 16);
+        let fitted = fit_image(&png, "image/png", u64::MAX, &ImageResize::default()).unwrap();
+        assert_eq!(fitted.data, png);
+        assert!(fitted.scale.is_unscaled());
+        assert_eq!(fitted.scale.factor(), 1.0);
+    }
+
+    #[test]
+    fn test_fit_image_downscales_to_max_edge() {
+        let png = noisy_png(400, 200);
+        let options = ImageResize::default().with_max_edge(100);
+        let fitted = fit_image(&png, "image/png", u64::MAX, &options).unwrap();
+        assert_eq!(fitted.mime, "image/png");
+        assert_eq!((fitted.scale.width, fitted.scale.height), (100, 50));
+        assert_eq!(fitted.scale.to_original(50.0, 25.0), (200.0, 100.0));
+        let (w, h) = reader(&fitted.data).unwrap().into_dimensions().unwrap();
+        assert_eq!((w, h), (100, 50));
+    }
+
+    #[test]
+    fn test_fit_image_recompresses_to_size_limit() {
+        let png = noisy_png(300, 300);
+        let max_bytes = 20 * 1024;
+        assert!(png.len() as u64 > max_bytes);
+        let fitted = fit_image(&png, "image/png", max_bytes, &ImageResize::default()).unwrap();
+        assert_eq!(fitted.mime, "image/jpeg");
+        assert!(fitted.data.len() as u64 <= max_bytes);
+        assert!(fitted.scale.factor() <= 1.0);
+    }
+
+    #[test]
+    fn test_sample_indices() {
+        assert_eq!(sample_indices(3, 5), vec![0, 1, 2]);
+        assert_eq!(sample_indices(10, 4), vec![0, 3, 6, 9]);
+        assert_eq!(sample_indices(10, 1), vec![0]);
+        assert!(sample_indices(10, 0).is_empty());
+    }
+
+    #[test]
+    fn test_tile_layout_tall_screenshot() {
+        let tiling = ImageTiling::default();
+        let tiles = tile_layout(1440, 12000, &tiling);
+        assert_eq!(tiles.len(), 9);
+        assert!(tiles.iter().all(|t| t.column == 0 && t.width == 1440));
+        assert!(tiles.iter().all(|t| t.height == 1536));
+        assert_eq!(tiles[1].y, 1308);
+        assert_eq!(tiles[8].y + tiles[8].height, 12000);
+        for pair in tiles.windows(2) {
+            assert!(pair[1].y <= pair[0].y + pair[0].height - tiling.overlap);
+        }
+    }
+
+    #[test]
+    fn test_tile_layout_small_and_grid() {
+        let tiling = ImageTiling::default();
+        assert_eq!(tile_layout(800, 600, &tiling).len(), 1);
+
+        let tiles = tile_layout(2800, 2000, &tiling);
+        assert_eq!(tiles.len(), 4);
+        assert_eq!((tiles[3].row, tiles[3].column), (1, 1));
+        assert_eq!((tiles[3].x, tiles[3].y), (2800 - 1536, 2000 - 1536));
+    }
+
+    #[test]
+    fn test_tile_layout_grows_to_max_tiles() {
+        let tiling = ImageTiling::default().with_max_tiles(4);
+        let tiles = tile_layout(1440, 12000, &tiling);
+        assert!(tiles.len() <= 4);
+        assert!(tiles[0].height > 1536);
+        assert_eq!(tiles.last().map(|t| t.y + t.height), Some(12000));
+    }
+
+    #[test]
+    fn test_tile_region_clip() {
+        let region = TileRegion {
+            row: 1,
+            column: 0,
+            x: 0,
+            y: 1000,
+            width: 500,
+            height: 800,
+        };
+        assert_eq!(
+            region.clip(400, 1500).map(|r| (r.width, r.height)),
+            Some((400, 500))
+        );
+        assert_eq!(region.clip(500, 1000), None);
+    }
+
+    #[test]
+    fn test_fit_image_max_pixels() {
+        let options = ImageResize {
+            max_edge: None,
+            ..ImageResize::default().with_max_pixels(10_000)
+        };
+        assert_eq!(target_scale(200, 200, &options), 0.5);
+    }
+}
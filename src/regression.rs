@@ -0,0 +1,939 @@
+//! Visual regression suites over many screenshot pairs (feature `regression`).
+//!
+//! A [`RegressionSuite`] lists expected/actual screenshot pairs, from a JSON
+//! manifest or two directories of matching file names, and compares them with
+//! [`ui_diff_report`](crate::tools::ui_diff_report), a bounded number at a time.
+//! Reports can be cached by the content hashes of both screenshots, so pairs
+//! that did not change since the last run cost no request. The resulting
+//! [`RegressionRun`] renders as an HTML page with side-by-side screenshots and
+//! findings, and as JUnit XML for CI.
+//!
+//! A manifest is a JSON file with paths relative to it:
+//!
+//! ```json
+//! {
+//!   "cases": [
+//!     {"name": "login", "expected": "baseline/login.png", "actual": "out/login.png"}
+//!   ]
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::client::VisionClient;
+use crate::diff::{DiffCategory, Severity, UiDiffReport};
+use crate::http::HttpClient;
+use crate::tools;
+
+/// Prompt used when the suite sets none.
+pub const DEFAULT_PROMPT: &str = "List all visual differences between these two screenshots.";
+
+/// Version of the cache file format; caches of other versions are ignored.
+const CACHE_VERSION: u32 = 1;
+
+/// Name of the test suite in JUnit reports.
+const SUITE_NAME: &str = "visual-regression";
+
+/// An expected/actual screenshot pair.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegressionCase {
+    /// Name shown in reports; defaults to the expected file name in manifests.
+    #[serde(default)]
+    pub name: String,
+    pub expected: PathBuf,
+    pub actual: PathBuf,
+}
+
+impl RegressionCase {
+    /// Create a case comparing `actual` against `expected`.
+    pub fn new(
+        name: impl Into<String>,
+        expected: impl Into<PathBuf>,
+        actual: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            expected: expected.into(),
+            actual: actual.into(),
+        }
+    }
+}
+
+/// A set of screenshot pairs to compare, with how to compare them.
+#[derive(Debug, Clone)]
+pub struct RegressionSuite {
+    pub cases: Vec<RegressionCase>,
+    /// Prompt passed to [`ui_diff_report`](crate::tools::ui_diff_report).
+    pub prompt: String,
+    /// Maximum number of comparisons in flight (default 4).
+    pub concurrency: usize,
+    /// Least severe difference that fails a case (default [`Severity::High`]).
+    pub fail_on: Severity,
+    /// JSON file caching reports by screenshot content, if any.
+    pub cache: Option<PathBuf>,
+}
+
+impl RegressionSuite {
+    /// Create a suite of `cases` with the default settings.
+    pub fn new(cases: Vec<RegressionCase>) -> Self {
+        Self {
+            cases,
+            prompt: DEFAULT_PROMPT.to_string(),
+            concurrency: 4,
+            fail_on: Severity::High,
+            cache: None,
+        }
+    }
+
+    /// Pair the files of two directories by file name.
+    ///
+    /// Hidden files and subdirectories are skipped. A file present in only one
+    /// directory still becomes a case, which fails as missing its counterpart.
+    pub fn from_dirs(
+        expected_dir: impl AsRef<Path>,
+        actual_dir: impl AsRef<Path>,
+    ) -> std::io::Result<Self> {
+        let (expected_dir, actual_dir) = (expected_dir.as_ref(), actual_dir.as_ref());
+        let mut names = list_files(expected_dir)?;
+        names.extend(list_files(actual_dir)?);
+        let cases = names
+            .into_iter()
+            .map(|name| {
+                RegressionCase::new(&name, expected_dir.join(&name), actual_dir.join(&name))
+            })
+            .collect();
+        Ok(Self::new(cases))
+    }
+
+    /// Load cases from a JSON manifest (see the [module docs](self)).
+    ///
+    /// Relative paths are resolved against the manifest's directory.
+    pub fn from_manifest(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        #[derive(Deserialize)]
+        struct Manifest {
+            cases: Vec<RegressionCase>,
+        }
+
+        let path = path.as_ref();
+        let manifest: Manifest = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        let cases = manifest
+            .cases
+            .into_iter()
+            .map(|mut case| {
+                case.expected = base.join(&case.expected);
+                case.actual = base.join(&case.actual);
+                if case.name.is_empty() {
+                    case.name = file_name(&case.expected);
+                }
+                case
+            })
+            .collect();
+        Ok(Self::new(cases))
+    }
+
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Set the maximum number of comparisons in flight (at least 1).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_fail_on(mut self, severity: Severity) -> Self {
+        self.fail_on = severity;
+        self
+    }
+
+    /// Cache reports in `path`, keyed by the content of both screenshots, the
+    /// model and the prompt. The file is created if missing.
+    pub fn with_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache = Some(path.into());
+        self
+    }
+
+    /// Compare every case.
+    ///
+    /// Failed comparisons are recorded in their [`CaseResult`] rather than
+    /// stopping the run; only reading or writing the cache fails the call. An
+    /// unreadable cache file is treated as empty.
+    pub async fn run(
+        &self,
+        client: &VisionClient<impl HttpClient>,
+    ) -> std::io::Result<RegressionRun> {
+        let started = Instant::now();
+        let cache = match &self.cache {
+            Some(path) => VerdictCache::load(path),
+            None => VerdictCache::default(),
+        };
+        let context = format!("{}\n{}", client.config().model, self.prompt);
+
+        let results: Vec<(CaseResult, Option<String>)> = stream::iter(&self.cases)
+            .map(|case| self.run_case(client, case, &cache, &context))
+            .buffered(self.concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut cache = cache;
+        let mut cases = Vec::with_capacity(results.len());
+        for (result, key) in results {
+            if let (Some(key), CaseOutcome::Compared(report)) = (key, &result.outcome) {
+                cache.entries.insert(key, report.clone());
+            }
+            cases.push(result);
+        }
+        if let Some(path) = &self.cache {
+            cache.save(path)?;
+        }
+        Ok(RegressionRun {
+            results: cases,
+            fail_on: self.fail_on,
+            duration: started.elapsed(),
+        })
+    }
+
+    /// Compare one case, returning its cache key when a fresh report should be
+    /// stored.
+    async fn run_case(
+        &self,
+        client: &VisionClient<impl HttpClient>,
+        case: &RegressionCase,
+        cache: &VerdictCache,
+        context: &str,
+    ) -> (CaseResult, Option<String>) {
+        let started = Instant::now();
+        let result = |outcome, cached| CaseResult {
+            case: case.clone(),
+            outcome,
+            cached,
+            duration: started.elapsed(),
+        };
+
+        for (label, path) in [("expected", &case.expected), ("actual", &case.actual)] {
+            if !path.is_file() {
+                let message = format!("Missing {} screenshot {}", label, path.display());
+                return (result(CaseOutcome::Error(message), false), None);
+            }
+        }
+
+        let key = match self.cache {
+            Some(_) => cache_key(case, context).await,
+            None => None,
+        };
+        if let Some(report) = key.as_ref().and_then(|k| cache.entries.get(k)) {
+            return (result(CaseOutcome::Compared(report.clone()), true), None);
+        }
+
+        let outcome = match tools::ui_diff_report(
+            client,
+            case.expected.as_path(),
+            case.actual.as_path(),
+            &self.prompt,
+        )
+        .await
+        {
+            Ok(report) => CaseOutcome::Compared(report),
+            Err(e) => CaseOutcome::Error(e.to_string()),
+        };
+        (result(outcome, false), key)
+    }
+}
+
+/// How a case ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseOutcome {
+    /// The screenshots were compared.
+    Compared(UiDiffReport),
+    /// The comparison could not be made (missing file, API error, ...).
+    Error(String),
+}
+
+/// The result of one case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub case: RegressionCase,
+    pub outcome: CaseOutcome,
+    /// Whether the report came from the cache.
+    pub cached: bool,
+    pub duration: Duration,
+}
+
+impl CaseResult {
+    /// Whether the case has no difference at least as severe as `fail_on` and
+    /// no error.
+    pub fn passed(&self, fail_on: Severity) -> bool {
+        match &self.outcome {
+            CaseOutcome::Compared(report) => !report.has_severity(fail_on),
+            CaseOutcome::Error(_) => false,
+        }
+    }
+
+    /// The report, if the screenshots were compared.
+    pub fn report(&self) -> Option<&UiDiffReport> {
+        match &self.outcome {
+            CaseOutcome::Compared(report) => Some(report),
+            CaseOutcome::Error(_) => None,
+        }
+    }
+}
+
+/// The results of [`RegressionSuite::run`], in case order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegressionRun {
+    pub results: Vec<CaseResult>,
+    /// Least severe difference that fails a case.
+    pub fail_on: Severity,
+    pub duration: Duration,
+}
+
+impl RegressionRun {
+    /// Whether every case passed.
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.passed(self.fail_on))
+    }
+
+    /// Number of compared cases with a difference at least as severe as
+    /// [`fail_on`](Self::fail_on).
+    pub fn failures(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| r.report().is_some() && !r.passed(self.fail_on))
+            .count()
+    }
+
+    /// Number of cases that could not be compared.
+    pub fn errors(&self) -> usize {
+        self.results
+            .iter()
+            .filter(|r| matches!(r.outcome, CaseOutcome::Error(_)))
+            .count()
+    }
+
+    /// Render a self-contained HTML report.
+    ///
+    /// Screenshots are linked, not embedded: paths are made relative to
+    /// `report_dir` (the directory the report will be written to) when given,
+    /// and used as they are otherwise.
+    pub fn to_html(&self, report_dir: Option<&Path>) -> String {
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>Visual regression report</title>\n<style>{}</style>\n</head>\n<body>\n\
+             <h1>Visual regression report</h1>\n\
+             <p class=\"totals\">{} cases: {} passed, {} failed, {} errors \
+             (failing at {} severity) in {:.1}s</p>\n",
+            HTML_STYLE,
+            self.results.len(),
+            self.results.len() - self.failures() - self.errors(),
+            self.failures(),
+            self.errors(),
+            severity_name(self.fail_on),
+            self.duration.as_secs_f64()
+        );
+        for result in &self.results {
+            let (class, status) = match &result.outcome {
+                CaseOutcome::Error(_) => ("error", "ERROR"),
+                _ if result.passed(self.fail_on) => ("pass", "PASS"),
+                _ => ("fail", "FAIL"),
+            };
+            let _ = write!(
+                html,
+                "<section class=\"case {}\">\n<h2><span class=\"status\">{}</span> {}{}</h2>\n",
+                class,
+                status,
+                escape(&result.case.name),
+                if result.cached {
+                    " <small>(cached)</small>"
+                } else {
+                    ""
+                }
+            );
+            let report = result.report();
+            if let Some(report) = report.filter(|r| !r.summary.is_empty()) {
+                let _ = writeln!(html, "<p>{}</p>", escape(&report.summary));
+            }
+            if let CaseOutcome::Error(message) = &result.outcome {
+                let _ = writeln!(html, "<p class=\"message\">{}</p>", escape(message));
+            }
+
+            html.push_str("<div class=\"pair\">\n");
+            for (label, path) in [
+                ("Expected", &result.case.expected),
+                ("Actual", &result.case.actual),
+            ] {
+                let _ = write!(
+                    html,
+                    "<figure>\n<figcaption>{}</figcaption>\n<div class=\"shot\">\
+                     <img src=\"{}\" alt=\"{} screenshot\" loading=\"lazy\">",
+                    label,
+                    escape(&href(path, report_dir)),
+                    label
+                );
+                // Boxes refer to the actual screenshot, except those of
+                // missing elements, which refer to the expected one.
+                if let Some(report) = report {
+                    for (i, difference) in report.differences.iter().enumerate() {
+                        let on_expected = difference.category == DiffCategory::MissingElement;
+                        if on_expected != (label == "Expected") {
+                            continue;
+                        }
+                        if let Some(b) = difference.region {
+                            let _ = write!(
+                                html,
+                                "<span class=\"box {}\" style=\"left:{:.1}%;top:{:.1}%;width:{:.1}%;height:{:.1}%\">{}</span>",
+                                severity_name(difference.severity),
+                                b.x1 / 10.0,
+                                b.y1 / 10.0,
+                                (b.x2 - b.x1) / 10.0,
+                                (b.y2 - b.y1) / 10.0,
+                                i + 1
+                            );
+                        }
+                    }
+                }
+                html.push_str("</div>\n</figure>\n");
+            }
+            html.push_str("</div>\n");
+
+            if let Some(report) = report.filter(|r| !r.differences.is_empty()) {
+                html.push_str(
+                    "<table>\n<tr><th>#</th><th>Severity</th><th>Category</th><th>Description</th></tr>\n",
+                );
+                for (i, difference) in report.differences.iter().enumerate() {
+                    let _ = writeln!(
+                        html,
+                        "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        severity_name(difference.severity),
+                        i + 1,
+                        severity_name(difference.severity),
+                        enum_name(&difference.category).replace('_', " "),
+                        escape(&difference.description)
+                    );
+                }
+                html.push_str("</table>\n");
+            }
+            html.push_str("</section>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Render a JUnit XML report with one test case per screenshot pair.
+    ///
+    /// Differences at least as severe as [`fail_on`](Self::fail_on) are
+    /// failures and comparisons that could not be made are errors; each
+    /// test case's output lists all differences.
+    pub fn to_junit(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let totals = format!(
+            "name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\"",
+            SUITE_NAME,
+            self.results.len(),
+            self.failures(),
+            self.errors(),
+            self.duration.as_secs_f64()
+        );
+        let _ = writeln!(xml, "<testsuites {}>\n  <testsuite {}>", totals, totals);
+        for result in &self.results {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                SUITE_NAME,
+                escape(&result.case.name),
+                result.duration.as_secs_f64()
+            );
+            match &result.outcome {
+                CaseOutcome::Error(message) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <error message=\"{}\"/>\n    </testcase>",
+                        escape(message)
+                    );
+                }
+                CaseOutcome::Compared(report) => {
+                    let failing: Vec<_> = report.differences_at_least(self.fail_on).collect();
+                    if failing.is_empty() && report.differences.is_empty() {
+                        xml.push_str("/>\n");
+                        continue;
+                    }
+                    xml.push_str(">\n");
+                    if !failing.is_empty() {
+                        let _ = writeln!(
+                            xml,
+                            "      <failure type=\"{}\" message=\"{} difference(s) of {} severity or worse\">{}</failure>",
+                            severity_name(self.fail_on),
+                            failing.len(),
+                            severity_name(self.fail_on),
+                            escape(&describe_differences(failing))
+                        );
+                    }
+                    let mut output = report.summary.clone();
+                    if !output.is_empty() {
+                        output.push('\n');
+                    }
+                    output.push_str(&describe_differences(report.differences.iter()));
+                    let _ = writeln!(
+                        xml,
+                        "      <system-out>{}</system-out>\n    </testcase>",
+                        escape(&output)
+                    );
+                }
+            }
+        }
+        xml.push_str("  </testsuite>\n</testsuites>\n");
+        xml
+    }
+
+    /// Write [`to_html`](Self::to_html) to `path`, linking screenshots
+    /// relative to it.
+    pub fn write_html(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new(""));
+        std::fs::write(path, self.to_html(Some(dir)))
+    }
+
+    /// Write [`to_junit`](Self::to_junit) to `path`.
+    pub fn write_junit(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_junit())
+    }
+}
+
+/// Reports of earlier runs, by [`cache_key`].
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VerdictCache {
+    version: u32,
+    entries: BTreeMap<String, UiDiffReport>,
+}
+
+impl VerdictCache {
+    fn load(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|raw| serde_json::from_str::<VerdictCache>(&raw).ok())
+            .filter(|cache| cache.version == CACHE_VERSION)
+            .unwrap_or_default()
+    }
+
+    fn save(&mut self, path: &Path) -> std::io::Result<()> {
+        self.version = CACHE_VERSION;
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// SHA-256 of both screenshots and `context` (the model and prompt), or
+/// `None` if a screenshot cannot be read.
+async fn cache_key(case: &RegressionCase, context: &str) -> Option<String> {
+    let paths = [case.expected.clone(), case.actual.clone()];
+    let context = context.to_string();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        for path in &paths {
+            hasher.update(Sha256::digest(std::fs::read(path).ok()?));
+        }
+        hasher.update(context.as_bytes());
+        Some(
+            hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect(),
+        )
+    })
+    .await
+    .ok()
+    .flatten()
+}
+
+/// Names of the regular, non-hidden files in `dir`.
+fn list_files(dir: &Path) -> std::io::Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with('.') && entry.path().is_file() {
+            names.insert(name);
+        }
+    }
+    Ok(names)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+/// One line per difference: `[severity] category: description`.
+fn describe_differences<'a>(
+    differences: impl IntoIterator<Item = &'a crate::diff::UiDifference>,
+) -> String {
+    differences
+        .into_iter()
+        .map(|d| {
+            format!(
+                "[{}] {}: {}",
+                severity_name(d.severity),
+                enum_name(&d.category),
+                d.description
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn severity_name(severity: Severity) -> String {
+    enum_name(&severity)
+}
+
+/// The serialized name of a unit enum variant.
+fn enum_name(value: &impl Serialize) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(s)) => s,
+        _ => String::new(),
+    }
+}
+
+/// Escape text for HTML and XML content and attribute values.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            // Control characters other than whitespace are invalid in XML 1.0.
+            c if c.is_control() && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Link to `path` from a page in `base`, percent-encoding characters that
+/// would end or alter a URL path.
+fn href(path: &Path, base: Option<&Path>) -> String {
+    let path = match base {
+        Some(base) => relative_path(path, base),
+        None => path.to_path_buf(),
+    };
+    let mut out = String::new();
+    for c in path.to_string_lossy().chars() {
+        match c {
+            '\\' => out.push('/'),
+            '%' | ' ' | '#' | '?' | '"' => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    let _ = write!(out, "%{:02X}", b);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `path` relative to the directory `base`, going through `..` as needed.
+///
+/// Both are made absolute first; if they are on different roots (such as
+/// Windows drives), `path` is returned absolute.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let absolute = |p: &Path| match std::path::absolute(p) {
+        Ok(p) => normalize(&p),
+        Err(_) => p.to_path_buf(),
+    };
+    let (path, base) = (absolute(path), absolute(base));
+    let mut path_parts = path.components().peekable();
+    let mut base_parts = base.components().peekable();
+    if path_parts.peek() != base_parts.peek() {
+        return path;
+    }
+    while path_parts.peek().is_some() && path_parts.peek() == base_parts.peek() {
+        path_parts.next();
+        base_parts.next();
+    }
+    let mut relative: PathBuf = base_parts.map(|_| Component::ParentDir).collect();
+    relative.extend(path_parts);
+    relative
+}
+
+/// Resolve `.` and `..` components without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+const HTML_STYLE: &str = "\
+body{font-family:system-ui,sans-serif;margin:2em;color:#222}\
+.totals{font-size:1.1em}\
+.case{border-top:1px solid #ccc;padding-top:1em;margin-top:1.5em}\
+.status{display:inline-block;padding:0 .4em;border-radius:4px;color:#fff;font-size:.8em}\
+.pass .status{background:#2e7d32}.fail .status{background:#c62828}.error .status{background:#6d4c41}\
+.message{color:#c62828;white-space:pre-wrap}\
+.pair{display:flex;gap:1em;align-items:flex-start}\
+figure{flex:1;margin:0;min-width:0}figcaption{font-weight:bold;margin-bottom:.3em}\
+.shot{position:relative;display:inline-block;max-width:100%}\
+.shot img{display:block;max-width:100%;border:1px solid #ddd}\
+.box{position:absolute;box-sizing:border-box;border:2px solid;color:#fff;font-size:11px;line-height:1}\
+.box.low{border-color:#1565c0;background:rgba(21,101,192,.1)}\
+.box.medium{border-color:#ef6c00;background:rgba(239,108,0,.1)}\
+.box.high,.box.critical{border-color:#c62828;background:rgba(198,40,40,.12)}\
+table{border-collapse:collapse;margin-top:1em;width:100%}\
+th,td{border:1px solid #ddd;padding:.3em .5em;text-align:left;vertical-align:top}\
+tr.high td:nth-child(2),tr.critical td:nth-child(2){color:#c62828;font-weight:bold}";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VisionConfig;
+    use crate::diff::{DiffVerdict, UiDifference};
+    use crate::grounding::BoundingBox;
+    use crate::http::HttpResponse;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with the same report, counting requests.
+    struct ReportHttp {
+        report: &'static str,
+        requests: AtomicUsize,
+    }
+
+    impl HttpClient for ReportHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            _body: &[u8],
+        ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let body = serde_json::json!({
+                "choices": [{"finish_reason": "stop", "message": {"content": self.report}}]
+            });
+            Ok(HttpResponse {
+                status: 200,
+                body: body.to_string(),
+            })
+        }
+    }
+
+    const FAILING: &str = r#"{"verdict": "fail", "summary": "Button moved.", "differences": [
+        {"category": "layout", "severity": "high", "description": "Button is 20px lower",
+         "region": [100, 500, 300, 600]},
+        {"category": "color", "severity": "low", "description": "Footer <b>darker</b>"}]}"#;
+
+    fn client(report: &'static str) -> VisionClient<ReportHttp> {
+        let config = VisionConfig::new("test-key").with_base_url("https://example.com/v1");
+        VisionClient::new(
+            config,
+            ReportHttp {
+                report,
+                requests: AtomicUsize::new(0),
+            },
+        )
+    }
+
+    fn png() -> Vec<u8> {
+        b"\x89PNG\r\n\x1a\nfake-png-data".to_vec()
+    }
+
+    #[test]
+    fn test_from_dirs_pairs_by_name() {
+        let root = tempfile::tempdir().unwrap();
+        let (expected, actual) = (root.path().join("expected"), root.path().join("actual"));
+        std::fs::create_dir_all(expected.join("nested")).unwrap();
+        std::fs::create_dir_all(&actual).unwrap();
+        for name in ["login.png", "home.png", ".DS_Store"] {
+            std::fs::write(expected.join(name), b"x").unwrap();
+        }
+        for name in ["login.png", "settings.png"] {
+            std::fs::write(actual.join(name), b"x").unwrap();
+        }
+        let suite = RegressionSuite::from_dirs(&expected, &actual).unwrap();
+        let names: Vec<_> = suite.cases.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["home.png", "login.png", "settings.png"]);
+        assert_eq!(suite.cases[1].actual, actual.join("login.png"));
+    }
+
+    #[test]
+    fn test_from_manifest_resolves_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let manifest = root.path().join("suite.json");
+        std::fs::write(
+            &manifest,
+            r#"{"cases": [
+                {"name": "login", "expected": "base/login.png", "actual": "out/login.png"},
+                {"expected": "base/home.png", "actual": "/abs/home.png"}
+            ]}"#,
+        )
+        .unwrap();
+        let suite = RegressionSuite::from_manifest(&manifest).unwrap();
+        assert_eq!(suite.cases[0].name, "login");
+        assert_eq!(suite.cases[0].expected, root.path().join("base/login.png"));
+        assert_eq!(suite.cases[1].name, "home.png");
+        assert_eq!(suite.cases[1].actual, PathBuf::from("/abs/home.png"));
+    }
+
+    #[tokio::test]
+    async fn test_run_caches_reports() {
+        let root = tempfile::tempdir().unwrap();
+        let (expected, actual) = (root.path().join("expected"), root.path().join("actual"));
+        std::fs::create_dir_all(&expected).unwrap();
+        std::fs::create_dir_all(&actual).unwrap();
+        for name in ["a.png", "b.png"] {
+            std::fs::write(expected.join(name), png()).unwrap();
+            std::fs::write(actual.join(name), png()).unwrap();
+        }
+        std::fs::write(expected.join("c.png"), png()).unwrap();
+        let cache = root.path().join("cache.json");
+        let suite = RegressionSuite::from_dirs(&expected, &actual)
+            .unwrap()
+            .with_concurrency(2)
+            .with_cache(&cache);
+
+        let client = client(FAILING);
+        let run = suite.run(&client).await.unwrap();
+        assert_eq!(client.http().requests.load(Ordering::SeqCst), 2);
+        assert_eq!((run.failures(), run.errors()), (2, 1));
+        assert!(!run.passed());
+        assert!(!run.results[0].cached);
+        assert!(matches!(&run.results[2].outcome, CaseOutcome::Error(m) if m.contains("actual")));
+
+        let run = suite.run(&client).await.unwrap();
+        assert_eq!(client.http().requests.load(Ordering::SeqCst), 2);
+        assert!(run.results[0].cached);
+
+        let lenient = suite.clone().with_fail_on(Severity::Critical);
+        assert_eq!(lenient.run(&client).await.unwrap().failures(), 0);
+
+        // A changed prompt misses the cache.
+        suite.with_prompt("Only layout").run(&client).await.unwrap();
+        assert_eq!(client.http().requests.load(Ordering::SeqCst), 4);
+    }
+
+    fn sample_run() -> RegressionRun {
+        let report: UiDiffReport = crate::structured::parse_json(FAILING).unwrap();
+        let passing = UiDiffReport {
+            verdict: DiffVerdict::Pass,
+            summary: String::new(),
+            differences: vec![UiDifference {
+                category: DiffCategory::Content,
+                severity: Severity::Low,
+                description: "Typo".to_string(),
+                region: Some(BoundingBox::new(0.0, 0.0, 100.0, 100.0)),
+            }],
+        };
+        let case = |name: &str| RegressionCase::new(name, "shots/a b.png", "/tmp/out/a.png");
+        let result = |name, outcome| CaseResult {
+            case: case(name),
+            outcome,
+            cached: false,
+            duration: Duration::from_millis(1500),
+        };
+        RegressionRun {
+            results: vec![
+                result("login & signup", CaseOutcome::Compared(report)),
+                result("home", CaseOutcome::Compared(passing)),
+                result("broken", CaseOutcome::Error("Missing actual".to_string())),
+            ],
+            fail_on: Severity::High,
+            duration: Duration::from_secs(3),
+        }
+    }
+
+    #[test]
+    fn test_to_junit() {
+        let xml = sample_run().to_junit();
+        assert!(xml.contains(
+            "<testsuite name=\"visual-regression\" tests=\"3\" failures=\"1\" errors=\"1\" time=\"3.000\">"
+        ));
+        assert!(xml.contains("name=\"login &amp; signup\" time=\"1.500\""));
+        assert!(xml.contains(
+            "<failure type=\"high\" message=\"1 difference(s) of high severity or worse\">[high] layout: Button is 20px lower</failure>"
+        ));
+        assert!(xml.contains("[low] color: Footer &lt;b&gt;darker&lt;/b&gt;"));
+        assert!(xml.contains("<error message=\"Missing actual\"/>"));
+        assert_eq!(xml.matches("<failure").count(), 1);
+    }
+
+    #[test]
+    fn test_to_html() {
+        let html = sample_run().to_html(None);
+        assert!(html.contains("3 cases: 1 passed, 1 failed, 1 errors"));
+        assert!(html.contains("<span class=\"status\">FAIL</span> login &amp; signup"));
+        assert!(html.contains("<span class=\"status\">PASS</span> home"));
+        assert!(html.contains("src=\"shots/a%20b.png\""));
+        assert!(html.contains("left:10.0%;top:50.0%;width:20.0%;height:10.0%"));
+        assert!(html.contains("<td>high</td><td>layout</td>"));
+        assert!(html.contains("Footer &lt;b&gt;darker&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn test_to_html_draws_missing_elements_on_expected() {
+        let report = UiDiffReport {
+            verdict: DiffVerdict::Fail,
+            summary: String::new(),
+            differences: vec![
+                UiDifference {
+                    category: DiffCategory::MissingElement,
+                    severity: Severity::Critical,
+                    description: "No submit button".to_string(),
+                    region: Some(BoundingBox::new(100.0, 800.0, 600.0, 900.0)),
+                },
+                UiDifference {
+                    category: DiffCategory::Layout,
+                    severity: Severity::Medium,
+                    description: "Header shifted".to_string(),
+                    region: Some(BoundingBox::new(0.0, 0.0, 1000.0, 100.0)),
+                },
+            ],
+        };
+        let run = RegressionRun {
+            results: vec![CaseResult {
+                case: RegressionCase::new("form", "expected.png", "actual.png"),
+                outcome: CaseOutcome::Compared(report),
+                cached: false,
+                duration: Duration::ZERO,
+            }],
+            fail_on: Severity::High,
+            duration: Duration::ZERO,
+        };
+        let html = run.to_html(None);
+        let (expected, actual) = html.split_once("<figcaption>Actual").unwrap();
+        assert!(expected.contains("left:10.0%;top:80.0%;width:50.0%;height:10.0%\">1</span>"));
+        assert!(!expected.contains(">2</span>"));
+        assert!(actual.contains("left:0.0%;top:0.0%;width:100.0%;height:10.0%\">2</span>"));
+        assert!(!actual.contains(">1</span>"));
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("/a/b/c.png"), Path::new("/a/d")),
+            PathBuf::from("../b/c.png")
+        );
+        assert_eq!(
+            relative_path(Path::new("/a/./b.png"), Path::new("/a")),
+            PathBuf::from("b.png")
+        );
+    }
+}
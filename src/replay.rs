@@ -0,0 +1,453 @@
+//! Record/replay [`HttpClient`] wrappers for deterministic offline tests.
+//!
+//! [`RecordingHttpClient`] wraps any client and captures request/response pairs
+//! into a [`Cassette`]; [`ReplayHttpClient`] serves them back without network
+//! access. Recorded requests have the `Authorization` header redacted and
+//! base64 data URLs replaced by a SHA-256 digest, so cassettes are safe to
+//! commit and stay small.
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::http::{HttpClient, HttpResponse};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Headers whose values are never written to a cassette.
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "x-api-key", "cookie"];
+
+/// A recorded HTTP request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// Normalized body (see [`normalize_body`]).
+    pub body: Value,
+}
+
+/// A recorded HTTP response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    /// Response body; stored as JSON when it parses as JSON, as a string otherwise.
+    pub body: Value,
+}
+
+impl RecordedResponse {
+    fn from_http(response: &HttpResponse) -> Self {
+        let body = serde_json::from_str(&response.body)
+            .unwrap_or_else(|_| Value::String(response.body.clone()));
+        Self {
+            status: response.status,
+            body,
+        }
+    }
+
+    fn to_http(&self) -> HttpResponse {
+        let body = match &self.body {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        HttpResponse {
+            status: self.status,
+            body,
+        }
+    }
+}
+
+/// A request/response pair.
+///
+/// `request` is `None` for response-only fixtures (such as the files in
+/// `examples/responses/`), which match any request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Interaction {
+    #[serde(default)]
+    pub request: Option<RecordedRequest>,
+    pub response: RecordedResponse,
+}
+
+/// An ordered list of recorded interactions, stored as JSON.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cassette {
+    pub interactions: Vec<Interaction>,
+}
+
+impl Cassette {
+    /// Load a cassette from a JSON file.
+    ///
+    /// A file that is a bare chat completion response (like those in
+    /// `examples/responses/`) is loaded as a single response-only interaction.
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let raw = std::fs::read_to_string(path)?;
+        let value: Value = serde_json::from_str(&raw)?;
+        if value.get("interactions").is_some() {
+            Ok(serde_json::from_value(value)?)
+        } else {
+            Ok(Self::from_response_body(200, value))
+        }
+    }
+
+    /// Build a cassette that answers any request with the given response body.
+    pub fn from_response_body(status: u16, body: Value) -> Self {
+        Self {
+            interactions: vec![Interaction {
+                request: None,
+                response: RecordedResponse { status, body },
+            }],
+        }
+    }
+
+    /// Write the cassette as pretty-printed JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+    }
+
+    /// Append the interactions of another cassette.
+    pub fn extend(&mut self, other: Cassette) {
+        self.interactions.extend(other.interactions);
+    }
+}
+
+/// Normalize a request body for storage and matching.
+///
+/// JSON bodies are re-serialized with sorted keys and every `data:...;base64,...`
+/// string is replaced by `data:...;sha256=<hex digest>`. Non-JSON bodies are kept
+/// as (lossy UTF-8) strings.
+pub fn normalize_body(body: &[u8]) -> Value {
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut value) => {
+            hash_data_urls(&mut value);
+            value
+        }
+        Err(_) => Value::String(String::from_utf8_lossy(body).into_owned()),
+    }
+}
+
+fn hash_data_urls(value: &mut Value) {
+    match value {
+        Value::String(s) => {
+            if let Some(hashed) = hash_data_url(s) {
+                *s = hashed;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(hash_data_urls),
+        Value::Object(map) => map.values_mut().for_each(hash_data_urls),
+        _ => {}
+    }
+}
+
+fn hash_data_url(s: &str) -> Option<String> {
+    let rest = s.strip_prefix("data:")?;
+    let (mime, payload) = rest.split_once(";base64,")?;
+    // Hash the decoded media so digests match e.g. `sha256sum image.png`.
+    let digest = match base64::Engine::decode(&base64::engine::general_purpose::STANDARD, payload) {
+        Ok(bytes) => Sha256::digest(&bytes),
+        Err(_) => Sha256::digest(payload.as_bytes()),
+    };
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    Some(format!("data:{};sha256={}", mime, hex))
+}
+
+fn redact_headers(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|&(k, v)| {
+            let v = if SENSITIVE_HEADERS.contains(&k.to_lowercase().as_str()) {
+                REDACTED
+            } else {
+                v
+            };
+            (k.to_string(), v.to_string())
+        })
+        .collect()
+}
+
+/// [`HttpClient`] wrapper that records every request/response pair.
+///
+/// Streaming requests are recorded through `post`, so the recorded response
+/// holds the full SSE body.
+pub struct RecordingHttpClient<H: HttpClient> {
+    inner: H,
+    cassette: Mutex<Cassette>,
+}
+
+impl<H: HttpClient> RecordingHttpClient<H> {
+    /// Wrap `inner`, starting with an empty cassette.
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            cassette: Mutex::new(Cassette::default()),
+        }
+    }
+
+    /// Returns a copy of everything recorded so far.
+    pub fn cassette(&self) -> Cassette {
+        self.cassette.lock().unwrap().clone()
+    }
+
+    /// Write everything recorded so far to `path`.
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        self.cassette.lock().unwrap().save(path)
+    }
+
+    /// Unwrap the inner client.
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl<H: HttpClient> HttpClient for RecordingHttpClient<H> {
+    async fn post(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        self.post_owned(url, headers, body.to_vec()).await
+    }
+
+    async fn post_owned(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let request = RecordedRequest {
+            method: "POST".to_string(),
+            url: url.to_string(),
+            headers: redact_headers(headers),
+            body: normalize_body(&body),
+        };
+        let response = self.inner.post_owned(url, headers, body).await?;
+        let interaction = Interaction {
+            request: Some(request),
+            response: RecordedResponse::from_http(&response),
+        };
+        self.cassette.lock().unwrap().interactions.push(interaction);
+        Ok(response)
+    }
+}
+
+/// Error returned by [`ReplayHttpClient`] when no interaction matches a request.
+#[derive(Debug, thiserror::Error)]
+#[error("no recorded interaction matches POST {url}")]
+pub struct NoMatchingInteraction {
+    pub url: String,
+    /// Normalized body of the unmatched request.
+    pub body: Value,
+}
+
+/// [`HttpClient`] that serves responses from a [`Cassette`].
+///
+/// Requests are matched by method, URL and normalized body. Each interaction
+/// is served once in recording order; when all matching interactions have been
+/// used, the last one is repeated. Response-only interactions match any request
+/// and are consumed in order.
+pub struct ReplayHttpClient {
+    interactions: Vec<Interaction>,
+    used: Mutex<Vec<bool>>,
+}
+
+impl ReplayHttpClient {
+    pub fn new(cassette: Cassette) -> Self {
+        let used = vec![false; cassette.interactions.len()];
+        Self {
+            interactions: cassette.interactions,
+            used: Mutex::new(used),
+        }
+    }
+
+    /// Load a cassette (or a bare response file) from `path`.
+    pub fn from_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(Cassette::load(path)?))
+    }
+
+    /// Returns `true` once every interaction has been served at least once.
+    pub fn is_exhausted(&self) -> bool {
+        self.used.lock().unwrap().iter().all(|&u| u)
+    }
+
+    fn find(&self, url: &str, body: &Value) -> Option<HttpResponse> {
+        let mut used = self.used.lock().unwrap();
+        let matches = |i: &Interaction| {
+            i.request
+                .as_ref()
+                .is_some_and(|r| r.method == "POST" && r.url == url && &r.body == body)
+        };
+
+        let exact: Vec<usize> = (0..self.interactions.len())
+            .filter(|&i| matches(&self.interactions[i]))
+            .collect();
+        let index = exact
+            .iter()
+            .copied()
+            .find(|&i| !used[i])
+            .or_else(|| exact.last().copied())
+            .or_else(|| {
+                (0..self.interactions.len())
+                    .find(|&i| self.interactions[i].request.is_none() && !used[i])
+            })?;
+
+        used[index] = true;
+        Some(self.interactions[index].response.to_http())
+    }
+}
+
+impl HttpClient for ReplayHttpClient {
+    async fn post(
+        &self,
+        url: &str,
+        _headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let body = normalize_body(body);
+        match self.find(url, &body) {
+            Some(response) => Ok(response),
+            None => Err(Box::new(NoMatchingInteraction {
+                url: url.to_string(),
+                body,
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VisionConfig;
+    use crate::error::VisionError;
+    use crate::types::{ContentPart, UrlHolder};
+    use crate::VisionClient;
+
+    struct StaticHttp(&'static str);
+
+    impl HttpClient for StaticHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            _body: &[u8],
+        ) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            Ok(HttpResponse {
+                status: 200,
+                body: self.0.to_string(),
+            })
+        }
+    }
+
+    fn config() -> VisionConfig {
+        VisionConfig::new("secret-key").with_base_url("https://api.test/v4/")
+    }
+
+    fn image() -> ContentPart {
+        ContentPart::ImageUrl {
+            image_url: UrlHolder {
+                url: "data:image/png;base64,aGVsbG8=".into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_normalize_body_hashes_data_urls() {
+        let body = br#"{"b":1,"a":{"url":"data:image/png;base64,aGVsbG8="}}"#;
+        let normalized = normalize_body(body);
+        let url = normalized["a"]["url"].as_str().unwrap();
+        assert_eq!(
+            url,
+            "data:image/png;sha256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(
+            normalize_body(b"not json"),
+            Value::String("not json".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassette.json");
+
+        let client = VisionClient::new(
+            config(),
+            RecordingHttpClient::new(StaticHttp(
+                r#"{"choices":[{"message":{"content":"recorded"}}]}"#,
+            )),
+        );
+        let answer = client
+            .completion("sys", vec![image()], "What is this?")
+            .await
+            .unwrap();
+        assert_eq!(answer, "recorded");
+        client.http().save(&path).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(!saved.contains("secret-key"));
+        assert!(!saved.contains("aGVsbG8="));
+        assert!(saved.contains(REDACTED));
+
+        let replay = VisionClient::new(config(), ReplayHttpClient::from_file(&path).unwrap());
+        let answer = replay
+            .completion("sys", vec![image()], "What is this?")
+            .await
+            .unwrap();
+        assert_eq!(answer, "recorded");
+        assert!(replay.http().is_exhausted());
+
+        let err = replay
+            .completion("sys", vec![image()], "Something else?")
+            .await
+            .unwrap_err();
+        match err {
+            VisionError::Transport(e) => assert!(e.is::<NoMatchingInteraction>()),
+            other => panic!("expected transport error, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_example_response_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("examples/responses/20260213_130013_extract_text.json");
+        let client = VisionClient::new(config(), ReplayHttpClient::from_file(path).unwrap());
+        let completion = client
+            .completion_detailed("sys", vec![image()], "anything")
+            .await
+            .unwrap();
+        assert!(!completion.content.is_empty());
+        assert!(completion.usage.is_some());
+
+        // Response-only fixtures are consumed once.
+        assert!(client.completion("sys", vec![], "again").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_replay_repeats_last_match() {
+        let mut cassette = Cassette::default();
+        for content in ["first", "second"] {
+            cassette.interactions.push(Interaction {
+                request: Some(RecordedRequest {
+                    method: "POST".into(),
+                    url: "https://x/".into(),
+                    headers: vec![],
+                    body: normalize_body(b"{}"),
+                }),
+                response: RecordedResponse {
+                    status: 200,
+                    body: Value::String(content.into()),
+                },
+            });
+        }
+        let replay = ReplayHttpClient::new(cassette);
+        let mut bodies = Vec::new();
+        for _ in 0..3 {
+            bodies.push(replay.post("https://x/", &[], b"{}").await.unwrap().body);
+        }
+        assert_eq!(bodies, ["first", "second", "second"]);
+    }
+}
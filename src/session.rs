@@ -0,0 +1,231 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::VisionClient;
+use crate::error::Result;
+use crate::http::HttpClient;
+use crate::types::{ContentPart, VisionCompletion, VisionContent, VisionMessage};
+
+const DEFAULT_RETRIES: u32 = 2;
+
+/// A multi-turn vision conversation.
+///
+/// The session keeps the system prompt and every user/assistant turn, including
+/// processed media parts, so follow-up questions can refer to earlier images
+/// without re-processing or re-describing them. Sessions are `Clone` (see
+/// [`fork`](VisionSession::fork)) and can be persisted with serde.
+///
+/// ```ignore
+/// let mut session = VisionSession::new(glm_vision_rs::prompts::ERROR_DIAGNOSIS);
+/// let image = client.process_image("/path/to/error.png").await?;
+/// let diagnosis = session.ask(&client, vec![image], "What is this error?").await?;
+/// let detail = session.ask(&client, vec![], "Expand on the second fix.").await?;
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisionSession {
+    system_prompt: String,
+    #[serde(default)]
+    messages: Vec<VisionMessage>,
+}
+
+impl VisionSession {
+    /// Start an empty session with the given system prompt.
+    pub fn new(system_prompt: impl Into<String>) -> Self {
+        Self {
+            system_prompt: system_prompt.into(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Returns the system prompt.
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    /// Returns the user and assistant turns so far (excluding the system prompt).
+    pub fn messages(&self) -> &[VisionMessage] {
+        &self.messages
+    }
+
+    /// Returns the number of recorded user/assistant messages.
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns `true` if no turns have been recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Returns the most recent assistant reply, if any.
+    pub fn last_reply(&self) -> Option<&str> {
+        self.messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "assistant")
+            .and_then(|m| match &m.content {
+                VisionContent::Text(text) => Some(text.as_str()),
+                VisionContent::Parts(_) => None,
+            })
+    }
+
+    /// Record a completed exchange without calling the API.
+    ///
+    /// Use this to continue from a result obtained elsewhere, e.g. a
+    /// [`tools::diagnose_error`](crate::tools::diagnose_error) answer.
+    pub fn record(&mut self, content_parts: Vec<ContentPart>, prompt: &str, reply: &str) {
+        self.messages.push(user_message(content_parts, prompt));
+        self.messages.push(VisionMessage::assistant(reply));
+    }
+
+    /// Returns the full message list to send: system prompt, history, then `next`.
+    fn request_messages(&self, next: VisionMessage) -> Vec<VisionMessage> {
+        let mut messages = Vec::with_capacity(self.messages.len() + 2);
+        messages.push(VisionMessage::system(self.system_prompt.as_str()));
+        messages.extend(self.messages.iter().cloned());
+        messages.push(next);
+        messages
+    }
+
+    /// Ask a question, optionally attaching new media, and record the exchange.
+    ///
+    /// On error the session is left unchanged so the question can be retried.
+    pub async fn ask(
+        &mut self,
+        client: &VisionClient<impl HttpClient>,
+        content_parts: Vec<ContentPart>,
+        prompt: &str,
+    ) -> Result<VisionCompletion> {
+        let user = user_message(content_parts, prompt);
+        let messages = self.request_messages(user.clone());
+        let completion = client.chat_with_retry(&messages, DEFAULT_RETRIES).await?;
+        self.messages.push(user);
+        self.messages
+            .push(VisionMessage::assistant(completion.content.as_str()));
+        Ok(completion)
+    }
+
+    /// Create an independent copy of this session to explore a different line of questioning.
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Drop the most recent exchange (user question and assistant reply).
+    pub fn undo(&mut self) -> bool {
+        if self.messages.len() < 2 {
+            return false;
+        }
+        self.messages.truncate(self.messages.len() - 2);
+        true
+    }
+
+    /// Remove all recorded turns, keeping the system prompt.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+    }
+}
+
+fn user_message(mut content_parts: Vec<ContentPart>, prompt: &str) -> VisionMessage {
+    content_parts.push(ContentPart::Text {
+        text: prompt.to_string(),
+    });
+    VisionMessage::user(content_parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VisionConfig;
+    use crate::http::HttpResponse;
+    use crate::types::UrlHolder;
+    use std::sync::Mutex;
+
+    struct RecordingHttp {
+        sent: Mutex<Vec<serde_json::Value>>,
+    }
+
+    impl HttpClient for RecordingHttp {
+        async fn post(
+            &self,
+            _url: &str,
+            _headers: &[(&str, &str)],
+            body: &[u8],
+        ) -> std::result::Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+            let mut sent = self.sent.lock().unwrap();
+            sent.push(serde_json::from_slice(body)?);
+            Ok(HttpResponse {
+                status: 200,
+                body: format!(
+                    r#"{{"choices":[{{"message":{{"content":"reply {}"}}}}]}}"#,
+                    sent.len()
+                ),
+            })
+        }
+    }
+
+    fn image() -> ContentPart {
+        ContentPart::ImageUrl {
+            image_url: UrlHolder {
+                url: "https://example.com/error.png".into(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ask_accumulates_history() {
+        let client = VisionClient::new(
+            VisionConfig::new("k").with_base_url("https://api.test/v4/"),
+            RecordingHttp {
+                sent: Mutex::new(Vec::new()),
+            },
+        );
+        let mut session = VisionSession::new("You diagnose errors.");
+
+        let first = session
+            .ask(&client, vec![image()], "What is this?")
+            .await
+            .unwrap();
+        assert_eq!(first.content, "reply 1");
+        let second = session.ask(&client, vec![], "Why?").await.unwrap();
+        assert_eq!(second.content, "reply 2");
+        assert_eq!(session.len(), 4);
+        assert_eq!(session.last_reply(), Some("reply 2"));
+
+        let sent = client.http().sent.lock().unwrap();
+        let messages = sent[1]["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["role"], "system");
+        assert_eq!(messages[1]["content"][0]["type"], "image_url");
+        assert_eq!(messages[2]["role"], "assistant");
+        assert_eq!(messages[2]["content"], "reply 1");
+        assert_eq!(messages[3]["content"][0]["text"], "Why?");
+    }
+
+    #[test]
+    fn test_fork_is_independent() {
+        let mut session = VisionSession::new("sys");
+        session.record(vec![image()], "What is this?", "An error.");
+        let mut fork = session.fork();
+        fork.record(vec![], "Fix?", "Restart.");
+        assert_eq!(session.len(), 2);
+        assert_eq!(fork.len(), 4);
+        assert!(fork.undo());
+        assert_eq!(fork.len(), 2);
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let mut session = VisionSession::new("sys");
+        session.record(vec![image()], "What is this?", "An error.");
+        let json = serde_json::to_string(&session).unwrap();
+        let restored: VisionSession = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.system_prompt(), "sys");
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.last_reply(), Some("An error."));
+        match &restored.messages()[0].content {
+            VisionContent::Parts(parts) => {
+                assert!(matches!(parts[0], ContentPart::ImageUrl { .. }))
+            }
+            VisionContent::Text(_) => panic!("expected parts"),
+        }
+    }
+}
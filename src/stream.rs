@@ -0,0 +1,249 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::error::{Result, VisionError};
+use crate::http::BodyStream;
+use crate::types::{VisionChatChunk, VisionChunkChoice};
+
+/// Payload sent by the API to mark the end of a streamed completion.
+const DONE_SENTINEL: &str = "[DONE]";
+
+/// Incremental parser for `text/event-stream` bodies.
+///
+/// Bytes are fed in arbitrary chunks; complete events are returned as the
+/// joined contents of their `data:` fields. Comments and other fields
+/// (`event:`, `id:`, `retry:`) are ignored.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of bytes and return the data of every event it completes.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        while let Some(pos) = self.buf.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            self.process_line(line, &mut events);
+        }
+        events
+    }
+
+    /// Flush any trailing event that was not terminated by a blank line.
+    pub fn finish(&mut self) -> Vec<String> {
+        let mut events = Vec::new();
+        if !self.buf.is_empty() {
+            let rest = std::mem::take(&mut self.buf);
+            let line = String::from_utf8_lossy(&rest);
+            self.process_line(line.trim_end_matches('\r'), &mut events);
+        }
+        self.process_line("", &mut events);
+        events
+    }
+
+    fn process_line(&mut self, line: &str, events: &mut Vec<String>) {
+        if line.is_empty() {
+            if !self.data.is_empty() {
+                events.push(self.data.join("\n"));
+                self.data.clear();
+            }
+            return;
+        }
+        if line.starts_with(':') {
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        if field == "data" {
+            self.data.push(value.to_string());
+        }
+    }
+}
+
+/// Stream of completion deltas returned by
+/// [`VisionClient::completion_stream`](crate::VisionClient::completion_stream).
+///
+/// Each item is the first choice of one SSE chunk; the stream ends after the
+/// `[DONE]` sentinel or when the response body is exhausted.
+pub struct VisionStream {
+    body: BodyStream,
+    parser: SseParser,
+    pending: VecDeque<String>,
+    eof: bool,
+    done: bool,
+}
+
+impl VisionStream {
+    /// Wrap a raw SSE response body.
+    pub fn new(body: BodyStream) -> Self {
+        Self {
+            body,
+            parser: SseParser::new(),
+            pending: VecDeque::new(),
+            eof: false,
+            done: false,
+        }
+    }
+
+    /// Returns the next delta, or `None` once the stream is finished.
+    pub async fn next(&mut self) -> Option<Result<VisionChunkChoice>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx)).await
+    }
+}
+
+impl Stream for VisionStream {
+    type Item = Result<VisionChunkChoice>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            if let Some(data) = this.pending.pop_front() {
+                if data.trim() == DONE_SENTINEL {
+                    this.done = true;
+                    return Poll::Ready(None);
+                }
+                match serde_json::from_str::<VisionChatChunk>(&data) {
+                    // Chunks without choices (e.g. a trailing usage-only chunk) carry no delta.
+                    Ok(chunk) => match chunk.choices.into_iter().next() {
+                        Some(choice) => return Poll::Ready(Some(Ok(choice))),
+                        None => continue,
+                    },
+                    Err(e) => {
+                        this.done = true;
+                        return Poll::Ready(Some(Err(VisionError::Parse {
+                            message: format!("invalid stream chunk: {e}"),
+                            body: data,
+                        })));
+                    }
+                }
+            }
+
+            if this.eof {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+
+            match this.body.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(bytes))) => this.pending.extend(this.parser.feed(&bytes)),
+                Poll::Ready(Some(Err(e))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(VisionError::from_transport(e))));
+                }
+                Poll::Ready(None) => {
+                    this.eof = true;
+                    this.pending.extend(this.parser.finish());
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{HttpResponse, HttpStreamResponse};
+
+    fn chunked_body(chunks: &[&str]) -> BodyStream {
+        struct Chunks(VecDeque<Vec<u8>>);
+        impl Stream for Chunks {
+            type Item = Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>>;
+            fn poll_next(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+            ) -> Poll<Option<Self::Item>> {
+                Poll::Ready(self.0.pop_front().map(Ok))
+            }
+        }
+        Box::pin(Chunks(
+            chunks.iter().map(|c| c.as_bytes().to_vec()).collect(),
+        ))
+    }
+
+    #[test]
+    fn test_sse_parser_split_chunks() {
+        let mut parser = SseParser::new();
+        assert!(parser.feed(b"data: {\"a\"").is_empty());
+        assert!(parser.feed(b":1}\r\n").is_empty());
+        let events = parser.feed(b"\r\ndata: [DONE]\n\n");
+        assert_eq!(events, vec!["{\"a\":1}", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_sse_parser_ignores_comments_and_fields() {
+        let mut parser = SseParser::new();
+        let events = parser.feed(b": keep-alive\nevent: message\nid: 7\ndata: x\ndata: y\n\n");
+        assert_eq!(events, vec!["x\ny"]);
+    }
+
+    #[test]
+    fn test_sse_parser_finish_flushes_unterminated_event() {
+        let mut parser = SseParser::new();
+        assert!(parser.feed(b"data: tail").is_empty());
+        assert_eq!(parser.finish(), vec!["tail"]);
+    }
+
+    #[tokio::test]
+    async fn test_vision_stream_yields_deltas_until_done() {
+        let body = chunked_body(&[
+            "data: {\"choices\":[{\"delta\":{\"reasoning_content\":\"Think\"}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hel\"}}]}\n\ndata: {\"choices\":[{\"delta\":",
+            "{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: {\"choices\":[],\"usage\":{\"total_tokens\":5}}\n\n",
+            "data: [DONE]\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"ignored\"}}]}\n\n",
+        ]);
+        let mut stream = VisionStream::new(body);
+
+        let first = stream.next().await.unwrap().unwrap();
+        assert_eq!(first.delta.reasoning_content.as_deref(), Some("Think"));
+
+        let mut content = String::new();
+        let mut finish_reason = None;
+        while let Some(choice) = stream.next().await {
+            let choice = choice.unwrap();
+            content.push_str(choice.delta.content.as_deref().unwrap_or(""));
+            finish_reason = choice.finish_reason.or(finish_reason);
+        }
+        assert_eq!(content, "Hello");
+        assert_eq!(finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_vision_stream_from_buffered_response() {
+        let response = HttpResponse {
+            status: 200,
+            body: "data: {\"choices\":[{\"delta\":{\"content\":\"ok\"}}]}\n\ndata: [DONE]\n\n"
+                .into(),
+        };
+        let mut stream = VisionStream::new(HttpStreamResponse::from(response).body);
+        let choice = stream.next().await.unwrap().unwrap();
+        assert_eq!(choice.delta.content.as_deref(), Some("ok"));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_vision_stream_invalid_chunk() {
+        let mut stream = VisionStream::new(chunked_body(&["data: not-json\n\n"]));
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, VisionError::Parse { ref body, .. } if body == "not-json"));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,156 @@
+//! Structured JSON answers: schema instructions, answer cleanup and validation.
+//!
+//! Used by [`VisionClient::completion_json`](crate::VisionClient::completion_json),
+//! which adds [`schema_instructions`] to the system prompt, requests
+//! `response_format: {"type": "json_object"}`, and checks each answer with
+//! [`parse_json`], re-asking the model with [`repair_prompt`] when it fails.
+//! The types to fill in derive [`JsonSchema`] (re-exported as
+//! `glm_vision_rs::schemars`) as well as `Deserialize`.
+
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+
+use crate::error::{Result, VisionError};
+use crate::grounding::strip_box_markers;
+
+/// Closing tag of reasoning some models leave in the answer text.
+const THINK_END: &str = "</think>";
+
+/// Instructions to answer with JSON matching the schema of `T`, to append to a
+/// system prompt.
+pub fn schema_instructions<T: JsonSchema>() -> String {
+    let schema = schemars::schema_for!(T);
+    let schema = serde_json::to_string_pretty(&schema).unwrap_or_else(|_| "{}".to_string());
+    format!(
+        "<json_output>\n\
+         Respond with a single JSON object that conforms to this JSON Schema. This replaces any \
+         other output format described above. Output only the JSON, without code fences or \
+         commentary.\n\
+         {}\n\
+         </json_output>",
+        schema
+    )
+}
+
+/// The JSON in a model answer, without reasoning, box markers, code fences or
+/// surrounding prose.
+pub fn extract_json(text: &str) -> String {
+    let text = match text.rfind(THINK_END) {
+        Some(end) => &text[end + THINK_END.len()..],
+        None => text,
+    };
+    let text = strip_box_markers(text);
+    let text = text.trim();
+    if let Some(body) = fenced_block(text) {
+        return body.trim().to_string();
+    }
+    match (text.find(['{', '[']), text.rfind(['}', ']'])) {
+        (Some(start), Some(end)) if end > start => text[start..=end].to_string(),
+        _ => text.to_string(),
+    }
+}
+
+/// The body of the first ``` fenced block in `text`.
+fn fenced_block(text: &str) -> Option<&str> {
+    let start = text.find("```")?;
+    let after = &text[start + 3..];
+    let body = &after[after.find('\n')? + 1..];
+    Some(&body[..body.find("```")?])
+}
+
+/// Extract the JSON from a model answer and deserialize it into `T`.
+///
+/// Fails with [`VisionError::Parse`] naming the offending field (e.g.
+/// `differences[2].severity: unknown variant`), with the answer as the body.
+pub fn parse_json<T: DeserializeOwned>(text: &str) -> Result<T> {
+    let json = extract_json(text);
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    let parsed = serde_path_to_error::deserialize(&mut deserializer)
+        .map_err(|e| {
+            let path = e.path().to_string();
+            match path.as_str() {
+                "." => e.into_inner().to_string(),
+                _ => format!("{}: {}", path, e.into_inner()),
+            }
+        })
+        .and_then(|value| {
+            deserializer.end().map_err(|e| e.to_string())?;
+            Ok(value)
+        });
+    parsed.map_err(|message| VisionError::Parse {
+        message,
+        body: text.to_string(),
+    })
+}
+
+/// Follow-up message asking the model to fix an answer that failed with
+/// `error`.
+pub fn repair_prompt(error: &str) -> String {
+    format!(
+        "Your previous answer is not valid JSON for the requested schema: {}\n\
+         Reply again with only the corrected JSON object.",
+        error
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct Finding {
+        title: String,
+        severity: Severity,
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Severity {
+        Low,
+        High,
+    }
+
+    #[test]
+    fn test_schema_instructions_include_fields() {
+        let instructions = schema_instructions::<Finding>();
+        assert!(instructions.contains("\"title\""));
+        assert!(instructions.contains("\"high\""));
+        assert!(instructions.starts_with("<json_output>"));
+    }
+
+    #[test]
+    fn test_extract_json() {
+        assert_eq!(extract_json("{\"a\":1}"), "{\"a\":1}");
+        assert_eq!(
+            extract_json("Here you go:\n```json\n{\"a\": 1}\n```\nDone."),
+            "{\"a\": 1}"
+        );
+        assert_eq!(
+            extract_json("<think>maybe {\"a\":0}</think>\nSure! {\"a\":2} Hope it helps."),
+            "{\"a\":2}"
+        );
+        assert_eq!(
+            extract_json("<|begin_of_box|>{\"a\":3}<|end_of_box|>"),
+            "{\"a\":3}"
+        );
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let finding: Finding =
+            parse_json("```json\n{\"title\": \"Misaligned\", \"severity\": \"high\"}\n```")
+                .unwrap();
+        assert_eq!(finding.severity, Severity::High);
+
+        let err = parse_json::<Finding>("{\"title\": \"x\", \"severity\": \"huge\"}").unwrap_err();
+        match err {
+            VisionError::Parse { message, body } => {
+                assert!(message.starts_with("severity: unknown variant `huge`"));
+                assert!(body.contains("huge"));
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(parse_json::<Finding>("not json at all").is_err());
+    }
+}
@@ -0,0 +1,328 @@
+//! Tables read from screenshots by [`extract_tables`](crate::tools::extract_tables).
+//!
+//! A [`Table`] keeps the structure of the original: header rows, cells
+//! spanning several rows or columns, and line breaks within cells. Cells are
+//! listed as in HTML, once per row they start in; [`Table::grid`] lays them
+//! out on a rectangular grid for export.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::chart::csv_row;
+
+/// Largest column span laid out, as in HTML, so a hallucinated span cannot
+/// allocate an enormous grid.
+const MAX_COL_SPAN: usize = 1000;
+
+/// The tables found in an image, as answered by the model.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+pub(crate) struct Tables {
+    /// Every table in the image, top to bottom; empty if there are none.
+    pub tables: Vec<Table>,
+}
+
+/// A table with its header rows and cell spans.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Table {
+    /// Caption or title shown with the table, if any.
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Number of leading rows that are column headers.
+    #[serde(default)]
+    pub header_rows: usize,
+    /// Rows from top to bottom. Each row lists, left to right, only the cells
+    /// that start in it: a cell spanning several rows appears in its first row
+    /// only.
+    pub rows: Vec<Vec<Cell>>,
+}
+
+/// A table cell.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Cell {
+    /// Cell text, with line breaks inside the cell kept as "\n".
+    #[serde(default)]
+    pub text: String,
+    /// Number of rows the cell covers.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub row_span: usize,
+    /// Number of columns the cell covers.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub col_span: usize,
+}
+
+impl Cell {
+    /// A cell covering a single row and column.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            row_span: 1,
+            col_span: 1,
+        }
+    }
+
+    /// Set how many rows and columns the cell covers (at least 1 each).
+    pub fn with_span(mut self, row_span: usize, col_span: usize) -> Self {
+        self.row_span = row_span.max(1);
+        self.col_span = col_span.max(1);
+        self
+    }
+}
+
+fn one() -> usize {
+    1
+}
+
+fn is_one(n: &usize) -> bool {
+    *n == 1
+}
+
+/// A position on the grid of a [`Table`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridCell<'a> {
+    /// The top-left position of a cell.
+    Start(&'a Cell),
+    /// A position covered by the span of the cell starting at (row, column).
+    Spanned { row: usize, column: usize },
+    /// A position no cell covers (a short row).
+    Empty,
+}
+
+impl Table {
+    /// Lay the cells out on a rectangular grid, as a browser lays out an HTML
+    /// table: each cell takes the first free column of its row, and spans
+    /// reserve the positions below and to the right of it.
+    ///
+    /// The grid is as wide as the widest row and has one row per table row.
+    /// Row spans stop at the last row and column spans at 1000 columns.
+    pub fn grid(&self) -> Vec<Vec<GridCell<'_>>> {
+        let mut grid: Vec<Vec<GridCell<'_>>> = Vec::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            let mut column = 0;
+            for cell in row {
+                ensure_row(&mut grid, r);
+                while !matches!(grid[r].get(column), None | Some(GridCell::Empty)) {
+                    column += 1;
+                }
+                let rows = cell.row_span.clamp(1, self.rows.len() - r);
+                let columns = cell.col_span.clamp(1, MAX_COL_SPAN);
+                for dr in 0..rows {
+                    ensure_row(&mut grid, r + dr);
+                    let target = &mut grid[r + dr];
+                    if target.len() < column + columns {
+                        target.resize(column + columns, GridCell::Empty);
+                    }
+                    for dc in 0..columns {
+                        target[column + dc] = if dr == 0 && dc == 0 {
+                            GridCell::Start(cell)
+                        } else {
+                            GridCell::Spanned { row: r, column }
+                        };
+                    }
+                }
+                column += columns;
+            }
+            ensure_row(&mut grid, r);
+        }
+        let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut grid {
+            row.resize(width, GridCell::Empty);
+        }
+        grid
+    }
+
+    /// The grid as text, with each spanned position left empty (like merged
+    /// cells in a spreadsheet) or, with `fill_spans`, repeating the text of
+    /// the cell covering it.
+    pub fn text_grid(&self, fill_spans: bool) -> Vec<Vec<String>> {
+        let grid = self.grid();
+        grid.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| match *cell {
+                        GridCell::Start(cell) => cell.text.clone(),
+                        GridCell::Spanned { row, column } if fill_spans => {
+                            match grid[row][column] {
+                                GridCell::Start(cell) => cell.text.clone(),
+                                _ => String::new(),
+                            }
+                        }
+                        _ => String::new(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The table as CSV, one record per grid row, with merged positions left
+    /// empty. Fields are quoted as in RFC 4180 when needed, so multi-line cells
+    /// stay in one field.
+    pub fn to_csv(&self) -> String {
+        self.text_grid(false)
+            .iter()
+            .map(|row| csv_row(row))
+            .collect()
+    }
+
+    /// The table as a GitHub-flavored markdown table.
+    ///
+    /// Markdown tables have one header row and no spans: several header rows
+    /// are joined per column (e.g. "Q1 / Revenue"), spanned body positions
+    /// repeat the text of their cell, and line breaks become `<br>`.
+    pub fn to_markdown(&self) -> String {
+        let grid = self.text_grid(true);
+        let width = grid.first().map_or(0, Vec::len);
+        if width == 0 {
+            return String::new();
+        }
+        let header_rows = self.header_rows.min(grid.len());
+        let header: Vec<String> = (0..width)
+            .map(|c| {
+                let mut parts: Vec<&str> = Vec::new();
+                for row in &grid[..header_rows] {
+                    let text = row[c].trim();
+                    if !text.is_empty() && parts.last() != Some(&text) {
+                        parts.push(text);
+                    }
+                }
+                parts.join(" / ")
+            })
+            .collect();
+
+        let mut markdown = String::new();
+        if let Some(title) = self.title.as_deref().filter(|t| !t.trim().is_empty()) {
+            markdown.push_str(&format!("**{}**\n\n", title.trim()));
+        }
+        markdown.push_str(&markdown_row(&header));
+        markdown.push_str(&markdown_row(&vec!["---".to_string(); width]));
+        for row in &grid[header_rows..] {
+            markdown.push_str(&markdown_row(row));
+        }
+        markdown
+    }
+
+    /// The table as pretty-printed JSON.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+fn ensure_row<T>(grid: &mut Vec<Vec<T>>, row: usize) {
+    if grid.len() <= row {
+        grid.resize_with(row + 1, Vec::new);
+    }
+}
+
+fn markdown_row(cells: &[String]) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|c| {
+            c.trim()
+                .replace('|', "\\|")
+                .replace("\r\n", "<br>")
+                .replace('\n', "<br>")
+        })
+        .collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structured::parse_json;
+
+    /// | Region (2 rows) | Sales (2 columns)  |
+    /// |                 | Q1       | Q2      |
+    /// | North           | 10       | 12      |
+    /// | South, "East"   | 7\nest.  | (missing)
+    fn sales() -> Table {
+        Table {
+            title: Some("Sales".to_string()),
+            header_rows: 2,
+            rows: vec![
+                vec![
+                    Cell::new("Region").with_span(2, 1),
+                    Cell::new("Sales").with_span(1, 2),
+                ],
+                vec![Cell::new("Q1"), Cell::new("Q2")],
+                vec![Cell::new("North"), Cell::new("10"), Cell::new("12")],
+                vec![Cell::new("South, \"East\""), Cell::new("7\nest.")],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_grid_places_spans() {
+        let table = sales();
+        let grid = table.grid();
+        assert_eq!(grid.len(), 4);
+        assert!(grid.iter().all(|row| row.len() == 3));
+        assert_eq!(grid[1][0], GridCell::Spanned { row: 0, column: 0 });
+        assert_eq!(grid[0][2], GridCell::Spanned { row: 0, column: 1 });
+        assert_eq!(grid[1][1], GridCell::Start(&table.rows[1][0]));
+        assert_eq!(grid[3][2], GridCell::Empty);
+    }
+
+    #[test]
+    fn test_grid_clamps_spans() {
+        let table = Table {
+            title: None,
+            header_rows: 0,
+            rows: vec![
+                vec![Cell::new("tall").with_span(usize::MAX, 1), Cell::new("a")],
+                vec![Cell::new("wide").with_span(1, 4_000_000_000)],
+            ],
+        };
+        let grid = table.grid();
+        assert_eq!(grid.len(), 2);
+        assert!(grid.iter().all(|row| row.len() == MAX_COL_SPAN + 1));
+        assert_eq!(grid[1][0], GridCell::Spanned { row: 0, column: 0 });
+        assert_eq!(grid[1][1], GridCell::Start(&table.rows[1][0]));
+        assert_eq!(grid[0][2], GridCell::Empty);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        assert_eq!(
+            sales().to_csv(),
+            "Region,Sales,\r\n\
+             ,Q1,Q2\r\n\
+             North,10,12\r\n\
+             \"South, \"\"East\"\"\",\"7\nest.\",\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        assert_eq!(
+            sales().to_markdown(),
+            "**Sales**\n\n\
+             | Region | Sales / Q1 | Sales / Q2 |\n\
+             | --- | --- | --- |\n\
+             | North | 10 | 12 |\n\
+             | South, \"East\" | 7<br>est. |  |\n"
+        );
+        let plain = Table {
+            title: None,
+            header_rows: 0,
+            rows: vec![vec![Cell::new("a|b")]],
+        };
+        assert_eq!(plain.to_markdown(), "|  |\n| --- |\n| a\\|b |\n");
+    }
+
+    #[test]
+    fn test_parse_tables() {
+        let tables: Tables = parse_json(
+            r#"{"tables": [{"header_rows": 1, "rows": [
+                [{"text": "Name"}, {"text": "Role", "col_span": 2}],
+                [{"text": "Ana"}, {"text": "Admin"}, {"text": "Owner"}]
+            ]}]}"#,
+        )
+        .unwrap();
+        let table = &tables.tables[0];
+        assert_eq!(table.rows[0][1].col_span, 2);
+        assert_eq!(table.rows[1][0].row_span, 1);
+        let again: Table = serde_json::from_str(&table.to_json()).unwrap();
+        assert_eq!(&again, table);
+        assert!(!table.to_json().contains("row_span"));
+    }
+}
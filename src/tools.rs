@@ -1,11 +1,30 @@
-use anyhow::{bail, Result};
-
+use crate::chart::ChartData;
 use crate::client::VisionClient;
+#[cfg(feature = "code")]
+use crate::code::{self, CodeBlock};
+use crate::diagram::{self, DiagramFormat};
+use crate::diff::UiDiffReport;
+use crate::error::{Result, VisionError};
+use crate::grounding::{self, LocatedElement};
 use crate::http::HttpClient;
+use crate::logs::{self, LogEntries, LogEntry};
+use crate::media::MediaSource;
 use crate::prompts;
+use crate::table::{Table, Tables};
+use crate::types::{ContentPart, VisionCompletion, VisionMessage, VisionUsage};
+#[cfg(feature = "image")]
+use crate::{
+    config::{ImageTiling, TileMode},
+    diff::{DiffCategory, DiffVerdict, Severity, UiDifference},
+    grounding::BoundingBox,
+    preprocess::TileRegion,
+};
 
 const DEFAULT_RETRIES: u32 = 2;
 
+/// Times an answer that fails a local check is sent back for correction.
+const SOURCE_REPAIRS: u32 = 2;
+
 /// Convert a UI screenshot into code, prompt, spec, or description.
 ///
 /// `output_type` selects the system prompt variant:
@@ -15,40 +34,77 @@ const DEFAULT_RETRIES: u32 = 2;
 /// - `Some("description")` — generate a text description
 pub async fn ui_to_artifact(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     output_type: Option<&str>,
     prompt: &str,
 ) -> Result<String> {
+    ui_to_artifact_detailed(client, image_source, output_type, prompt)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`ui_to_artifact`] but returns the full [`VisionCompletion`].
+pub async fn ui_to_artifact_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    output_type: Option<&str>,
+    prompt: &str,
+) -> Result<VisionCompletion> {
     let ot = output_type.unwrap_or("code");
     let system_prompt = prompts::ui_to_artifact_prompt(ot).ok_or_else(|| {
-        anyhow::anyhow!(
+        VisionError::InvalidRequest(format!(
             "Invalid output_type '{}'. Must be one of: code, prompt, spec, description",
             ot
-        )
+        ))
     })?;
 
-    let image = client.process_image(image_source)?;
+    let image = client.process_image(image_source).await?;
     client
-        .completion_with_retry(system_prompt, vec![image], prompt, DEFAULT_RETRIES)
+        .completion_detailed_with_retry(system_prompt, vec![image], prompt, DEFAULT_RETRIES)
         .await
 }
 
 /// Extract text from a screenshot (code, logs, configuration, documentation).
 ///
 /// Optionally specify `programming_language` to enhance extraction accuracy.
+///
+/// With [`VisionConfig::tiling`](crate::VisionConfig::tiling) set (feature
+/// `image`), large local screenshots are split into overlapping tiles; in
+/// sequential mode the per-tile text is stitched back together, dropping the
+/// lines repeated where tiles overlap.
 pub async fn extract_text(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     prompt: &str,
     programming_language: Option<&str>,
 ) -> Result<String> {
-    let image = client.process_image(image_source)?;
+    extract_text_detailed(client, image_source, prompt, programming_language)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`extract_text`] but returns the full [`VisionCompletion`].
+pub async fn extract_text_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    prompt: &str,
+    programming_language: Option<&str>,
+) -> Result<VisionCompletion> {
+    let image_source = image_source.into();
     let enhanced_prompt = match programming_language {
         Some(lang) => format!("Programming language context: {}\n\n{}", lang, prompt),
         None => prompt.to_string(),
     };
+    #[cfg(feature = "image")]
+    let tiling = tiling_for(client, &[&image_source]);
+    #[cfg(feature = "image")]
+    if let Some(tiling) = tiling {
+        return extract_text_tiled(client, image_source, &enhanced_prompt, tiling).await;
+    }
+
+    let image = client.process_image(image_source).await?;
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::TEXT_EXTRACTION,
             vec![image],
             &enhanced_prompt,
@@ -57,22 +113,184 @@ pub async fn extract_text(
         .await
 }
 
+/// Transcribe the code in a screenshot as typed [`CodeBlock`]s, one per
+/// snippet, with the language and without line numbers.
+///
+/// Optionally specify `programming_language` as a hint. Rust, JSON, TOML and
+/// YAML blocks are parsed locally (see [`code::Syntax`]); if one fails, the
+/// error and its line are sent back to the model, up to twice, to correct
+/// transcription mistakes. An error still left is kept in
+/// [`CodeBlock::syntax_error`], since the original code may itself be invalid.
+///
+/// Requires the `code` feature.
+#[cfg(feature = "code")]
+pub async fn extract_code(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    programming_language: Option<&str>,
+) -> Result<Vec<CodeBlock>> {
+    extract_code_detailed(client, image_source, programming_language)
+        .await
+        .map(|(blocks, _)| blocks)
+}
+
+/// Same as [`extract_code`] but also returns the [`VisionCompletion`] of the
+/// accepted answer, with the token usage of every attempt.
+#[cfg(feature = "code")]
+pub async fn extract_code_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    programming_language: Option<&str>,
+) -> Result<(Vec<CodeBlock>, VisionCompletion)> {
+    let image = client.process_image(image_source).await?;
+    let mut prompt = "Transcribe the code in this screenshot.".to_string();
+    if let Some(lang) = programming_language {
+        prompt = format!("Programming language context: {}\n\n{}", lang, prompt);
+    }
+    let mut messages = vec![
+        VisionMessage::system(prompts::CODE_EXTRACTION),
+        VisionMessage::user(vec![image, ContentPart::Text { text: prompt }]),
+    ];
+
+    let mut usage: Option<VisionUsage> = None;
+    let mut repairs = 0;
+    loop {
+        let mut completion = client.chat_with_retry(&messages, DEFAULT_RETRIES).await?;
+        if let Some(u) = &completion.usage {
+            usage.get_or_insert_with(VisionUsage::default).merge(u);
+        }
+        let mut blocks = code::extract_blocks(&completion.content);
+        for block in &mut blocks {
+            block.syntax_error = block.check().err();
+        }
+        let invalid: Vec<String> = blocks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, block)| {
+                let error = block.syntax_error.as_ref()?;
+                let line = block.code.lines().nth(error.line - 1).unwrap_or("");
+                Some(format!(
+                    "- Block {} ({}), {}\n  Line {}: {}",
+                    i + 1,
+                    block.language.as_deref().unwrap_or("unknown"),
+                    error,
+                    error.line,
+                    line.trim()
+                ))
+            })
+            .collect();
+        if invalid.is_empty() || repairs >= SOURCE_REPAIRS {
+            completion.usage = usage;
+            return Ok((blocks, completion));
+        }
+        messages.push(VisionMessage::assistant(completion.content));
+        messages.push(VisionMessage::user(vec![ContentPart::Text {
+            text: format!(
+                "These code blocks do not parse:\n{}\n\
+                 Look at these lines in the image again for misread or missing characters, \
+                 then reply again with all the code blocks, corrected, in the same format.",
+                invalid.join("\n")
+            ),
+        }]));
+        repairs += 1;
+    }
+}
+
+/// Extract the log entries in a terminal screenshot as [`LogEntry`]s with
+/// their timestamp, level, target, message, fields and continuation lines
+/// (such as stack frames).
+///
+/// When the lines are in a format [`logs::LogFormat::detect`] recognizes, the
+/// fields of each entry are read from its line locally with
+/// [`logs::reparse`], so they are split consistently.
+pub async fn extract_logs(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<Vec<LogEntry>> {
+    extract_logs_detailed(client, image_source)
+        .await
+        .map(|(entries, _)| entries)
+}
+
+/// Same as [`extract_logs`] but also returns the full [`VisionCompletion`].
+pub async fn extract_logs_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<(Vec<LogEntry>, VisionCompletion)> {
+    let image = client.process_image(image_source).await?;
+    let (found, completion) = client
+        .completion_json_detailed_with_retry::<LogEntries>(
+            prompts::LOG_EXTRACTION,
+            vec![image],
+            "Extract the log entries in this screenshot.",
+            DEFAULT_RETRIES,
+        )
+        .await?;
+    let mut entries = found.entries;
+    logs::reparse(&mut entries);
+    Ok((entries, completion))
+}
+
+/// Extract every table in a screenshot with its structure: header rows, cell
+/// spans and multi-line cells.
+///
+/// Tables are returned top to bottom; an image without tables gives an empty
+/// list. See [`Table::to_csv`], [`Table::to_markdown`] and [`Table::to_json`]
+/// for export.
+pub async fn extract_tables(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<Vec<Table>> {
+    extract_tables_detailed(client, image_source)
+        .await
+        .map(|(tables, _)| tables)
+}
+
+/// Same as [`extract_tables`] but also returns the full [`VisionCompletion`].
+pub async fn extract_tables_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<(Vec<Table>, VisionCompletion)> {
+    let image = client.process_image(image_source).await?;
+    let (found, completion) = client
+        .completion_json_detailed_with_retry::<Tables>(
+            prompts::TABLE_EXTRACTION,
+            vec![image],
+            "Extract every table in this screenshot.",
+            DEFAULT_RETRIES,
+        )
+        .await?;
+    Ok((found.tables, completion))
+}
+
 /// Diagnose an error from a screenshot.
 ///
 /// Optionally provide `context` (e.g., what the user was doing when the error occurred).
 pub async fn diagnose_error(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     prompt: &str,
     context: Option<&str>,
 ) -> Result<String> {
-    let image = client.process_image(image_source)?;
+    diagnose_error_detailed(client, image_source, prompt, context)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`diagnose_error`] but returns the full [`VisionCompletion`].
+pub async fn diagnose_error_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    prompt: &str,
+    context: Option<&str>,
+) -> Result<VisionCompletion> {
+    let image = client.process_image(image_source).await?;
     let enhanced_prompt = match context {
         Some(ctx) => format!("Context: {}\n\n{}", ctx, prompt),
         None => prompt.to_string(),
     };
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::ERROR_DIAGNOSIS,
             vec![image],
             &enhanced_prompt,
@@ -86,17 +304,29 @@ pub async fn diagnose_error(
 /// Optionally specify `diagram_type` (e.g., "UML class", "sequence", "ER", "flowchart").
 pub async fn understand_diagram(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     prompt: &str,
     diagram_type: Option<&str>,
 ) -> Result<String> {
-    let image = client.process_image(image_source)?;
+    understand_diagram_detailed(client, image_source, prompt, diagram_type)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`understand_diagram`] but returns the full [`VisionCompletion`].
+pub async fn understand_diagram_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    prompt: &str,
+    diagram_type: Option<&str>,
+) -> Result<VisionCompletion> {
+    let image = client.process_image(image_source).await?;
     let enhanced_prompt = match diagram_type {
         Some(dt) => format!("Diagram type: {}\n\n{}", dt, prompt),
         None => prompt.to_string(),
     };
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::DIAGRAM_UNDERSTANDING,
             vec![image],
             &enhanced_prompt,
@@ -105,22 +335,110 @@ pub async fn understand_diagram(
         .await
 }
 
+/// Convert a diagram image into editable source in `format` (Mermaid,
+/// PlantUML, Graphviz DOT or D2).
+///
+/// Optionally specify `diagram_type` (e.g., "sequence", "ER", "flowchart").
+/// The source is taken from the fenced block of the answer and checked with
+/// [`diagram::check_syntax`]; if the check fails, the error is sent back to
+/// the model, up to twice, before failing with [`VisionError::Parse`].
+pub async fn diagram_to_source(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    format: DiagramFormat,
+    diagram_type: Option<&str>,
+) -> Result<String> {
+    diagram_to_source_detailed(client, image_source, format, diagram_type)
+        .await
+        .map(|(source, _)| source)
+}
+
+/// Same as [`diagram_to_source`] but also returns the [`VisionCompletion`] of
+/// the accepted answer, with the token usage of every attempt.
+pub async fn diagram_to_source_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    format: DiagramFormat,
+    diagram_type: Option<&str>,
+) -> Result<(String, VisionCompletion)> {
+    let image = client.process_image(image_source).await?;
+    let mut prompt = format!(
+        "Convert this diagram to {}. Answer with a single ```{} block.",
+        format.name(),
+        format.fence_tag()
+    );
+    if let Some(dt) = diagram_type {
+        prompt = format!("Diagram type: {}\n\n{}", dt, prompt);
+    }
+    let mut messages = vec![
+        VisionMessage::system(prompts::DIAGRAM_TO_SOURCE),
+        VisionMessage::user(vec![image, ContentPart::Text { text: prompt }]),
+    ];
+
+    let mut usage: Option<VisionUsage> = None;
+    let mut repairs = 0;
+    loop {
+        let mut completion = client.chat_with_retry(&messages, DEFAULT_RETRIES).await?;
+        if let Some(u) = &completion.usage {
+            usage.get_or_insert_with(VisionUsage::default).merge(u);
+        }
+        let source = diagram::extract_source(&completion.content, format);
+        match diagram::check_syntax(format, &source) {
+            Ok(()) => {
+                completion.usage = usage;
+                return Ok((source, completion));
+            }
+            Err(error) if repairs < SOURCE_REPAIRS => {
+                messages.push(VisionMessage::assistant(completion.content));
+                messages.push(VisionMessage::user(vec![ContentPart::Text {
+                    text: format!(
+                        "This {} source is invalid: {}\n\
+                         Reply again with the complete, corrected source in a single ```{} block.",
+                        format.name(),
+                        error,
+                        format.fence_tag()
+                    ),
+                }]));
+                repairs += 1;
+            }
+            Err(error) => {
+                return Err(VisionError::Parse {
+                    message: format!("invalid {} source: {}", format.name(), error),
+                    body: completion.content,
+                })
+            }
+        }
+    }
+}
+
 /// Analyze a data visualization (chart, graph, dashboard).
 ///
 /// Optionally specify `analysis_focus` (e.g., "trends", "anomalies", "comparison").
 pub async fn analyze_data_viz(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     prompt: &str,
     analysis_focus: Option<&str>,
 ) -> Result<String> {
-    let image = client.process_image(image_source)?;
+    analyze_data_viz_detailed(client, image_source, prompt, analysis_focus)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`analyze_data_viz`] but returns the full [`VisionCompletion`].
+pub async fn analyze_data_viz_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    prompt: &str,
+    analysis_focus: Option<&str>,
+) -> Result<VisionCompletion> {
+    let image = client.process_image(image_source).await?;
     let enhanced_prompt = match analysis_focus {
         Some(focus) => format!("Analysis focus: {}\n\n{}", focus, prompt),
         None => prompt.to_string(),
     };
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::DATA_VIZ_ANALYSIS,
             vec![image],
             &enhanced_prompt,
@@ -129,45 +447,670 @@ pub async fn analyze_data_viz(
         .await
 }
 
+/// Extract the data behind a chart as a typed [`ChartData`]: kind, axis labels
+/// and units, legend, and series of (x, y) points.
+///
+/// Points the model estimated from their position rather than read from a
+/// label are marked [`estimated`](crate::chart::DataPoint::estimated).
+pub async fn extract_chart_data(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<ChartData> {
+    extract_chart_data_detailed(client, image_source)
+        .await
+        .map(|(chart, _)| chart)
+}
+
+/// Same as [`extract_chart_data`] but also returns the full [`VisionCompletion`].
+pub async fn extract_chart_data_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+) -> Result<(ChartData, VisionCompletion)> {
+    let image = client.process_image(image_source).await?;
+    client
+        .completion_json_detailed_with_retry(
+            prompts::CHART_DATA_EXTRACTION,
+            vec![image],
+            "Extract the data shown in this chart.",
+            DEFAULT_RETRIES,
+        )
+        .await
+}
+
 /// Compare two UI screenshots (expected vs actual) for visual regression.
+///
+/// With [`VisionConfig::tiling`](crate::VisionConfig::tiling) set (feature
+/// `image`), large local screenshots are compared region by region.
 pub async fn ui_diff_check(
     client: &VisionClient<impl HttpClient>,
-    expected: &str,
-    actual: &str,
+    expected: impl Into<MediaSource>,
+    actual: impl Into<MediaSource>,
     prompt: &str,
 ) -> Result<String> {
-    let expected_img = client.process_image(expected)?;
-    let actual_img = client.process_image(actual)?;
+    ui_diff_check_detailed(client, expected, actual, prompt)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`ui_diff_check`] but returns the full [`VisionCompletion`].
+pub async fn ui_diff_check_detailed(
+    client: &VisionClient<impl HttpClient>,
+    expected: impl Into<MediaSource>,
+    actual: impl Into<MediaSource>,
+    prompt: &str,
+) -> Result<VisionCompletion> {
+    let (expected, actual) = (expected.into(), actual.into());
+    #[cfg(feature = "image")]
+    let tiling = tiling_for(client, &[&expected, &actual]);
+    #[cfg(feature = "image")]
+    if let Some(tiling) = tiling {
+        return ui_diff_check_tiled(client, expected, actual, prompt, tiling).await;
+    }
+
+    let expected_img = client.process_image(expected).await?;
+    let actual_img = client.process_image(actual).await?;
+
+    client
+        .completion_detailed_with_retry(
+            prompts::UI_DIFF_CHECK,
+            vec![expected_img, actual_img],
+            &ui_diff_prompt(prompt),
+            DEFAULT_RETRIES,
+        )
+        .await
+}
+
+/// Compare two UI screenshots and return a typed [`UiDiffReport`] with
+/// categorized, severity-ranked differences and an overall verdict.
+///
+/// With [`VisionConfig::tiling`](crate::VisionConfig::tiling) set (feature
+/// `image`), large local screenshots are compared one region per request and
+/// the reports merged, with region boxes mapped back onto the whole
+/// screenshot. A region only one screenshot reaches is reported as a
+/// high-severity layout difference.
+pub async fn ui_diff_report(
+    client: &VisionClient<impl HttpClient>,
+    expected: impl Into<MediaSource>,
+    actual: impl Into<MediaSource>,
+    prompt: &str,
+) -> Result<UiDiffReport> {
+    ui_diff_report_detailed(client, expected, actual, prompt)
+        .await
+        .map(|(report, _)| report)
+}
+
+/// Same as [`ui_diff_report`] but also returns the full [`VisionCompletion`].
+pub async fn ui_diff_report_detailed(
+    client: &VisionClient<impl HttpClient>,
+    expected: impl Into<MediaSource>,
+    actual: impl Into<MediaSource>,
+    prompt: &str,
+) -> Result<(UiDiffReport, VisionCompletion)> {
+    let (expected, actual) = (expected.into(), actual.into());
+    #[cfg(feature = "image")]
+    let tiling = tiling_for(client, &[&expected, &actual]);
+    #[cfg(feature = "image")]
+    if let Some(tiling) = tiling {
+        return ui_diff_report_tiled(client, expected, actual, prompt, tiling).await;
+    }
 
-    let enhanced_prompt = format!(
+    let expected_img = client.process_image(expected).await?;
+    let actual_img = client.process_image(actual).await?;
+    client
+        .completion_json_detailed_with_retry(
+            prompts::UI_DIFF_CHECK,
+            vec![expected_img, actual_img],
+            &ui_diff_prompt(prompt),
+            DEFAULT_RETRIES,
+        )
+        .await
+}
+
+/// Tell the model which image is the reference and which the implementation.
+fn ui_diff_prompt(prompt: &str) -> String {
+    format!(
         "<images>\nThe first image is the EXPECTED/REFERENCE design (the target).\n\
          The second image is the ACTUAL/CURRENT implementation (what needs to be checked).\n\
          </images>\n\n{}",
         prompt
+    )
+}
+
+/// The configured tiling, unless it is off or any source is a remote URL
+/// (which cannot be decoded locally).
+#[cfg(feature = "image")]
+fn tiling_for<'a>(
+    client: &'a VisionClient<impl HttpClient>,
+    sources: &[&MediaSource],
+) -> Option<&'a ImageTiling> {
+    let remote = sources.iter().any(|s| matches!(s, MediaSource::Url(_)));
+    client.config().tiling.as_ref().filter(|_| !remote)
+}
+
+#[cfg(feature = "image")]
+async fn extract_text_tiled(
+    client: &VisionClient<impl HttpClient>,
+    image_source: MediaSource,
+    prompt: &str,
+    tiling: &ImageTiling,
+) -> Result<VisionCompletion> {
+    let tiles = client.process_image_tiles(image_source, tiling).await?;
+    let regions: Vec<_> = tiles.iter().map(|(region, _)| *region).collect();
+    let (width, height) = canvas_size(&regions);
+    if tiles.len() == 1 || tiling.mode == TileMode::MultiImage {
+        let enhanced_prompt = match tiles.len() {
+            1 => prompt.to_string(),
+            _ => format!("{}\n\n{}", tiles_preamble(&regions), prompt),
+        };
+        return client
+            .completion_detailed_with_retry(
+                prompts::TEXT_EXTRACTION,
+                tiles.into_iter().map(|(_, part)| part).collect(),
+                &enhanced_prompt,
+                DEFAULT_RETRIES,
+            )
+            .await;
+    }
+
+    let mut completions = Vec::with_capacity(tiles.len());
+    for (i, (region, part)) in tiles.into_iter().enumerate() {
+        let enhanced_prompt = format!(
+            "<tile>\nThis image is tile {} of {} of a {}x{} screenshot, covering {}.\n\
+             Skip lines cut off at the top or bottom edge; the neighbouring tile shows them in full.\n\
+             </tile>\n\n{}",
+            i + 1,
+            regions.len(),
+            width,
+            height,
+            describe_region(&region),
+            prompt
+        );
+        let completion = client
+            .completion_detailed_with_retry(
+                prompts::TEXT_EXTRACTION,
+                vec![part],
+                &enhanced_prompt,
+                DEFAULT_RETRIES,
+            )
+            .await?;
+        completions.push(completion);
+    }
+    let texts: Vec<&str> = completions.iter().map(|c| c.content.as_str()).collect();
+    let content = merge_tile_text(&texts);
+    Ok(combine_completions(completions, content))
+}
+
+#[cfg(feature = "image")]
+async fn ui_diff_check_tiled(
+    client: &VisionClient<impl HttpClient>,
+    expected: MediaSource,
+    actual: MediaSource,
+    prompt: &str,
+    tiling: &ImageTiling,
+) -> Result<VisionCompletion> {
+    let pairs = client
+        .process_image_tile_pairs(expected, actual, tiling)
+        .await?;
+    if pairs.len() == 1 {
+        // A single region is the whole of both images.
+        return client
+            .completion_detailed_with_retry(
+                prompts::UI_DIFF_CHECK,
+                pairs
+                    .into_iter()
+                    .flat_map(|(_, parts)| parts)
+                    .flatten()
+                    .collect(),
+                &ui_diff_prompt(prompt),
+                DEFAULT_RETRIES,
+            )
+            .await;
+    }
+    let regions: Vec<_> = pairs.iter().map(|(region, _)| *region).collect();
+    let (width, height) = canvas_size(&regions);
+
+    if tiling.mode == TileMode::MultiImage {
+        let mut preamble = format!(
+            "<images>\nThe EXPECTED/REFERENCE design (the target) and the ACTUAL/CURRENT \
+             implementation (what needs to be checked) are split into the same {} overlapping \
+             regions of a {}x{} canvas, sent in this order:\n",
+            pairs.len(),
+            width,
+            height
+        );
+        let mut parts = Vec::new();
+        for (i, (region, [expected, actual])) in pairs.into_iter().enumerate() {
+            for (label, part) in [("EXPECTED", expected), ("ACTUAL", actual)] {
+                match part {
+                    Some(part) => {
+                        parts.push(part);
+                        preamble.push_str(&format!(
+                            "Image {}: {}, region {} ({})\n",
+                            parts.len(),
+                            label,
+                            i + 1,
+                            describe_region(&region)
+                        ));
+                    }
+                    None => preamble.push_str(&format!(
+                        "(The {} screenshot does not reach region {})\n",
+                        label,
+                        i + 1
+                    )),
+                }
+            }
+        }
+        preamble.push_str("Neighbouring regions overlap; report each difference once.\n</images>");
+        return client
+            .completion_detailed_with_retry(
+                prompts::UI_DIFF_CHECK,
+                parts,
+                &format!("{}\n\n{}", preamble, prompt),
+                DEFAULT_RETRIES,
+            )
+            .await;
+    }
+
+    let mut completions = Vec::new();
+    let mut sections = Vec::with_capacity(pairs.len());
+    for (i, (region, [expected, actual])) in pairs.into_iter().enumerate() {
+        let heading = format!(
+            "## Region {} of {} ({})",
+            i + 1,
+            regions.len(),
+            describe_region(&region)
+        );
+        let (expected, actual) = match (expected, actual) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (expected, _) => {
+                let missing = if expected.is_some() {
+                    "ACTUAL"
+                } else {
+                    "EXPECTED"
+                };
+                sections.push(format!(
+                    "{}\n\nNot compared: the {} screenshot does not reach this region.",
+                    heading, missing
+                ));
+                continue;
+            }
+        };
+        let enhanced_prompt =
+            region_diff_prompt(i, regions.len(), &region, (width, height), prompt);
+        let completion = client
+            .completion_detailed_with_retry(
+                prompts::UI_DIFF_CHECK,
+                vec![expected, actual],
+                &enhanced_prompt,
+                DEFAULT_RETRIES,
+            )
+            .await?;
+        sections.push(format!("{}\n\n{}", heading, completion.content.trim()));
+        completions.push(completion);
+    }
+    Ok(combine_completions(completions, sections.join("\n\n")))
+}
+
+#[cfg(feature = "image")]
+async fn ui_diff_report_tiled(
+    client: &VisionClient<impl HttpClient>,
+    expected: MediaSource,
+    actual: MediaSource,
+    prompt: &str,
+    tiling: &ImageTiling,
+) -> Result<(UiDiffReport, VisionCompletion)> {
+    let pairs = client
+        .process_image_tile_pairs(expected, actual, tiling)
+        .await?;
+    if pairs.len() == 1 {
+        return client
+            .completion_json_detailed_with_retry(
+                prompts::UI_DIFF_CHECK,
+                pairs
+                    .into_iter()
+                    .flat_map(|(_, parts)| parts)
+                    .flatten()
+                    .collect(),
+                &ui_diff_prompt(prompt),
+                DEFAULT_RETRIES,
+            )
+            .await;
+    }
+    let regions: Vec<_> = pairs.iter().map(|(region, _)| *region).collect();
+    let (width, height) = canvas_size(&regions);
+
+    let mut completions = Vec::new();
+    let mut summaries = Vec::new();
+    let mut report = UiDiffReport {
+        verdict: DiffVerdict::Pass,
+        summary: String::new(),
+        differences: Vec::new(),
+    };
+    for (i, (region, [expected, actual])) in pairs.into_iter().enumerate() {
+        let (expected, actual) = match (expected, actual) {
+            (Some(expected), Some(actual)) => (expected, actual),
+            (expected, _) => {
+                let (present, missing) = match expected {
+                    Some(_) => ("EXPECTED", "ACTUAL"),
+                    None => ("ACTUAL", "EXPECTED"),
+                };
+                report.verdict = DiffVerdict::Fail;
+                report.differences.push(UiDifference {
+                    category: DiffCategory::Layout,
+                    severity: Severity::High,
+                    description: format!(
+                        "Region {} ({}) is in the {} screenshot, but the {} screenshot does not reach it.",
+                        i + 1,
+                        describe_region(&region),
+                        present,
+                        missing
+                    ),
+                    region: Some(region_to_canvas(
+                        BoundingBox::new(0.0, 0.0, 1000.0, 1000.0),
+                        &region,
+                        (width, height),
+                    )),
+                });
+                continue;
+            }
+        };
+        let enhanced_prompt =
+            region_diff_prompt(i, regions.len(), &region, (width, height), prompt);
+        let (part, completion) = client
+            .completion_json_detailed_with_retry::<UiDiffReport>(
+                prompts::UI_DIFF_CHECK,
+                vec![expected, actual],
+                &enhanced_prompt,
+                DEFAULT_RETRIES,
+            )
+            .await?;
+        if !part.passed() {
+            report.verdict = DiffVerdict::Fail;
+        }
+        if !part.summary.trim().is_empty() {
+            summaries.push(format!("Region {}: {}", i + 1, part.summary.trim()));
+        }
+        report
+            .differences
+            .extend(part.differences.into_iter().map(|d| {
+                UiDifference {
+                    region: d
+                        .region
+                        .map(|b| region_to_canvas(b, &region, (width, height))),
+                    ..d
+                }
+            }));
+        completions.push(completion);
+    }
+    report.summary = summaries.join(" ");
+    // Stable, so differences keep their region order within a severity.
+    report
+        .differences
+        .sort_by_key(|d| std::cmp::Reverse(d.severity));
+    let content = serde_json::to_string(&report).unwrap_or_default();
+    Ok((report, combine_completions(completions, content)))
+}
+
+/// Map a box normalized to `region` onto the `canvas` (width, height) it is
+/// part of.
+#[cfg(feature = "image")]
+fn region_to_canvas(bbox: BoundingBox, region: &TileRegion, canvas: (u32, u32)) -> BoundingBox {
+    let (width, height) = (canvas.0.max(1) as f64, canvas.1.max(1) as f64);
+    let x = |v: f64| (region.x as f64 + v / 1000.0 * region.width as f64) / width * 1000.0;
+    let y = |v: f64| (region.y as f64 + v / 1000.0 * region.height as f64) / height * 1000.0;
+    BoundingBox::new(x(bbox.x1), y(bbox.y1), x(bbox.x2), y(bbox.y2))
+}
+
+/// [`ui_diff_prompt`] for region `index` of `count` of a `canvas`.
+#[cfg(feature = "image")]
+fn region_diff_prompt(
+    index: usize,
+    count: usize,
+    region: &TileRegion,
+    canvas: (u32, u32),
+    prompt: &str,
+) -> String {
+    format!(
+        "<images>\nThe first image is the EXPECTED/REFERENCE design (the target).\n\
+         The second image is the ACTUAL/CURRENT implementation (what needs to be checked).\n\
+         Both show region {} of {} ({}) of {}x{} screenshots; report only differences in this region.\n\
+         </images>\n\n{}",
+        index + 1,
+        count,
+        describe_region(region),
+        canvas.0,
+        canvas.1,
+        prompt
+    )
+}
+
+/// Size of the image covered by `regions`.
+#[cfg(feature = "image")]
+fn canvas_size(regions: &[TileRegion]) -> (u32, u32) {
+    regions.iter().fold((0, 0), |(w, h), r| {
+        (w.max(r.x + r.width), h.max(r.y + r.height))
+    })
+}
+
+#[cfg(feature = "image")]
+fn describe_region(region: &TileRegion) -> String {
+    format!(
+        "x {}-{}, y {}-{}",
+        region.x,
+        region.x + region.width,
+        region.y,
+        region.y + region.height
+    )
+}
+
+/// Describe tiles sent together so the model reads them as one image.
+#[cfg(feature = "image")]
+fn tiles_preamble(regions: &[TileRegion]) -> String {
+    let (width, height) = canvas_size(regions);
+    let mut preamble = format!(
+        "<tiles>\nThe {}x{} screenshot is split into {} overlapping tiles, in reading order:\n",
+        width,
+        height,
+        regions.len()
     );
+    for (i, region) in regions.iter().enumerate() {
+        preamble.push_str(&format!("Tile {}: {}\n", i + 1, describe_region(region)));
+    }
+    preamble.push_str(
+        "Text near a tile edge appears in both neighbouring tiles; report each line once.\n</tiles>",
+    );
+    preamble
+}
 
-    client
-        .completion_with_retry(
-            prompts::UI_DIFF_CHECK,
-            vec![expected_img, actual_img],
-            &enhanced_prompt,
+/// Most lines at each end of neighbouring tiles' text that may be cut off or
+/// garbled and are skipped when looking for the overlap.
+#[cfg(feature = "image")]
+const OVERLAP_SLACK: usize = 2;
+
+/// Join the text extracted from consecutive tiles, dropping the lines that
+/// each tile repeats from the end of the previous one.
+///
+/// Code fences wrapping every tile's text are merged into one.
+#[cfg(feature = "image")]
+fn merge_tile_text(texts: &[&str]) -> String {
+    let mut fence = None;
+    let mut merged: Vec<&str> = Vec::new();
+    for text in texts {
+        let mut lines: Vec<&str> = text.trim_matches('\n').lines().collect();
+        if lines.len() >= 2
+            && lines[0].trim_start().starts_with("```")
+            && lines[lines.len() - 1].trim() == "```"
+        {
+            fence.get_or_insert(lines[0]);
+            lines = lines[1..lines.len() - 1].to_vec();
+        }
+        let (keep, skip) = tile_overlap(&merged, &lines);
+        merged.truncate(keep);
+        merged.extend_from_slice(&lines[skip..]);
+    }
+    let body = merged.join("\n");
+    match fence {
+        Some(open) => format!("{}\n{}\n```", open, body),
+        None => body,
+    }
+}
+
+/// How many lines of `previous` to keep and of `next` to skip, where `next`
+/// starts with a repeat of the end of `previous`.
+///
+/// Prefers the longest repeated run, allowing up to [`OVERLAP_SLACK`] cut-off
+/// lines between the run and the tile edges; runs that need slack must be at
+/// least two lines long, and blank lines alone never count.
+#[cfg(feature = "image")]
+fn tile_overlap(previous: &[&str], next: &[&str]) -> (usize, usize) {
+    let same = |a: &str, b: &str| a.trim() == b.trim();
+    let mut best: Option<(usize, usize, usize)> = None;
+    for slack in 0..=OVERLAP_SLACK {
+        for dropped in 0..=slack {
+            let skipped = slack - dropped;
+            if dropped > previous.len() || skipped > next.len() {
+                continue;
+            }
+            let end = previous.len() - dropped;
+            let max_run = end.min(next.len() - skipped);
+            let run = (1..=max_run).rev().find(|&run| {
+                let (tail, head) = (&previous[end - run..end], &next[skipped..skipped + run]);
+                tail.iter().zip(head).all(|(a, b)| same(a, b))
+                    && tail.iter().any(|line| !line.trim().is_empty())
+            });
+            let Some(run) = run.filter(|&run| slack == 0 || run >= 2) else {
+                continue;
+            };
+            if best.is_none_or(|(best_run, _, _)| run > best_run) {
+                best = Some((run, end, skipped + run));
+            }
+        }
+    }
+    best.map_or((previous.len(), 0), |(_, keep, skip)| (keep, skip))
+}
+
+/// Combine the completions of per-tile calls into one with `content`, summing
+/// their token usage.
+#[cfg(feature = "image")]
+fn combine_completions(completions: Vec<VisionCompletion>, content: String) -> VisionCompletion {
+    let mut usage: Option<VisionUsage> = None;
+    let mut reasoning = Vec::new();
+    let mut finish_reason = None;
+    for completion in &completions {
+        if let Some(u) = &completion.usage {
+            usage.get_or_insert_with(VisionUsage::default).merge(u);
+        }
+        if let Some(r) = completion
+            .reasoning_content
+            .as_deref()
+            .filter(|r| !r.is_empty())
+        {
+            reasoning.push(r);
+        }
+        if finish_reason
+            .as_deref()
+            .is_none_or(|reason| reason == "stop")
+        {
+            finish_reason.clone_from(&completion.finish_reason);
+        }
+    }
+    let reasoning_content = (!reasoning.is_empty()).then(|| reasoning.join("\n\n"));
+    let last = completions.last();
+    VisionCompletion {
+        id: last.and_then(|c| c.id.clone()),
+        request_id: last.and_then(|c| c.request_id.clone()),
+        model: last.and_then(|c| c.model.clone()),
+        created: last.and_then(|c| c.created),
+        content,
+        reasoning_content,
+        finish_reason,
+        usage,
+    }
+}
+
+/// Find the named UI elements in a screenshot and return their bounding boxes.
+///
+/// Elements the model cannot see are omitted; elements it sees several times
+/// appear once per occurrence. For local images (feature `image`), each element
+/// also gets its [`PixelBox`](crate::grounding::PixelBox) in the original image,
+/// however the image was resized for the request.
+pub async fn locate_elements(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    elements: &[&str],
+) -> Result<Vec<LocatedElement>> {
+    locate_elements_detailed(client, image_source, elements)
+        .await
+        .map(|(found, _)| found)
+}
+
+/// Same as [`locate_elements`] but also returns the full [`VisionCompletion`].
+pub async fn locate_elements_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    elements: &[&str],
+) -> Result<(Vec<LocatedElement>, VisionCompletion)> {
+    if elements.iter().all(|e| e.trim().is_empty()) {
+        return Err(VisionError::InvalidRequest(
+            "At least one element name is required".to_string(),
+        ));
+    }
+    let (image, original_size) = prepare_for_grounding(client, image_source.into()).await?;
+    let mut prompt = "Locate these elements:\n".to_string();
+    for element in elements.iter().filter(|e| !e.trim().is_empty()) {
+        prompt.push_str(&format!("- {}\n", element.trim()));
+    }
+    let completion = client
+        .completion_detailed_with_retry(
+            prompts::ELEMENT_LOCATION,
+            vec![image],
+            &prompt,
             DEFAULT_RETRIES,
         )
-        .await
+        .await?;
+    let found = grounding::located_elements(&completion.content, elements, original_size);
+    Ok((found, completion))
+}
+
+/// Process an image for a grounding query, with the original size when it can
+/// be read locally.
+async fn prepare_for_grounding(
+    client: &VisionClient<impl HttpClient>,
+    image_source: MediaSource,
+) -> Result<(ContentPart, Option<(u32, u32)>)> {
+    #[cfg(feature = "image")]
+    if !matches!(image_source, MediaSource::Url(_)) {
+        let (image, scale) = client.prepare_image(image_source).await?;
+        return Ok((image, Some((scale.original_width, scale.original_height))));
+    }
+    Ok((client.process_image(image_source).await?, None))
 }
 
 /// General-purpose image analysis.
 pub async fn analyze_image(
     client: &VisionClient<impl HttpClient>,
-    image_source: &str,
+    image_source: impl Into<MediaSource>,
     prompt: &str,
 ) -> Result<String> {
+    analyze_image_detailed(client, image_source, prompt)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`analyze_image`] but returns the full [`VisionCompletion`].
+pub async fn analyze_image_detailed(
+    client: &VisionClient<impl HttpClient>,
+    image_source: impl Into<MediaSource>,
+    prompt: &str,
+) -> Result<VisionCompletion> {
     if prompt.trim().is_empty() {
-        bail!("Prompt is required for image analysis");
+        return Err(VisionError::InvalidRequest(
+            "Prompt is required for image analysis".to_string(),
+        ));
     }
-    let image = client.process_image(image_source)?;
+    let image = client.process_image(image_source).await?;
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::GENERAL_IMAGE_ANALYSIS,
             vec![image],
             prompt,
@@ -179,15 +1122,28 @@ pub async fn analyze_image(
 /// Analyze video content.
 pub async fn analyze_video(
     client: &VisionClient<impl HttpClient>,
-    video_source: &str,
+    video_source: impl Into<MediaSource>,
     prompt: &str,
 ) -> Result<String> {
+    analyze_video_detailed(client, video_source, prompt)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`analyze_video`] but returns the full [`VisionCompletion`].
+pub async fn analyze_video_detailed(
+    client: &VisionClient<impl HttpClient>,
+    video_source: impl Into<MediaSource>,
+    prompt: &str,
+) -> Result<VisionCompletion> {
     if prompt.trim().is_empty() {
-        bail!("Prompt is required for video analysis");
+        return Err(VisionError::InvalidRequest(
+            "Prompt is required for video analysis".to_string(),
+        ));
     }
-    let video = client.process_video(video_source)?;
+    let video = client.process_video(video_source).await?;
     client
-        .completion_with_retry(
+        .completion_detailed_with_retry(
             prompts::VIDEO_ANALYSIS,
             vec![video],
             prompt,
@@ -196,6 +1152,71 @@ pub async fn analyze_video(
         .await
 }
 
+/// Analyze a video from frames sampled locally (feature `video`).
+///
+/// For recordings too large to send whole: the frames chosen by `sampling` are
+/// sent as images, after a preamble listing their timestamps.
+#[cfg(feature = "video")]
+pub async fn analyze_video_frames(
+    client: &VisionClient<impl HttpClient>,
+    video_source: impl Into<MediaSource>,
+    prompt: &str,
+    sampling: &crate::video::VideoSampling,
+) -> Result<String> {
+    analyze_video_frames_detailed(client, video_source, prompt, sampling)
+        .await
+        .map(|c| c.content)
+}
+
+/// Same as [`analyze_video_frames`] but returns the full [`VisionCompletion`].
+#[cfg(feature = "video")]
+pub async fn analyze_video_frames_detailed(
+    client: &VisionClient<impl HttpClient>,
+    video_source: impl Into<MediaSource>,
+    prompt: &str,
+    sampling: &crate::video::VideoSampling,
+) -> Result<VisionCompletion> {
+    if prompt.trim().is_empty() {
+        return Err(VisionError::InvalidRequest(
+            "Prompt is required for video analysis".to_string(),
+        ));
+    }
+    let frames = client.process_video_frames(video_source, sampling).await?;
+    if frames.is_empty() {
+        return Err(VisionError::InvalidMedia(
+            "No frames were sampled from the video".to_string(),
+        ));
+    }
+    let timestamps: Vec<_> = frames.iter().map(|(t, _)| *t).collect();
+    let enhanced_prompt = format!("{}\n\n{}", frames_preamble(&timestamps), prompt);
+    client
+        .completion_detailed_with_retry(
+            prompts::VIDEO_ANALYSIS,
+            frames.into_iter().map(|(_, part)| part).collect(),
+            &enhanced_prompt,
+            DEFAULT_RETRIES,
+        )
+        .await
+}
+
+/// Describe sampled frames so the model can refer to them by timestamp.
+#[cfg(feature = "video")]
+fn frames_preamble(timestamps: &[std::time::Duration]) -> String {
+    let mut preamble = format!(
+        "<video>\nThe video is provided as {} frames sampled at these timestamps, in order:\n",
+        timestamps.len()
+    );
+    for (i, t) in timestamps.iter().enumerate() {
+        preamble.push_str(&format!(
+            "Frame {}: {}\n",
+            i + 1,
+            crate::video::format_timestamp(*t)
+        ));
+    }
+    preamble.push_str("Refer to moments in the video by these timestamps.\n</video>");
+    preamble
+}
+
 #[cfg(test)]
 mod tests {
     use crate::prompts;
@@ -236,17 +1257,270 @@ mod tests {
         assert!(enhanced.contains("UML class"));
     }
 
+    #[cfg(feature = "video")]
+    #[test]
+    fn test_frames_preamble_lists_timestamps() {
+        use std::time::Duration;
+        let preamble = super::frames_preamble(&[Duration::ZERO, Duration::from_millis(65_500)]);
+        assert!(preamble.contains("2 frames"));
+        assert!(preamble.contains("Frame 1: 00:00.0"));
+        assert!(preamble.contains("Frame 2: 01:05.5"));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_merge_tile_text_drops_overlap() {
+        let merged = super::merge_tile_text(&[
+            "line 1\nline 2\nline 3\nline 4",
+            "line 3\n  line 4\nline 5",
+            "line 6",
+        ]);
+        assert_eq!(merged, "line 1\nline 2\nline 3\nline 4\nline 5\nline 6");
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_merge_tile_text_skips_cut_lines_and_fences() {
+        let merged = super::merge_tile_text(&[
+            "```rust\nfn main() {\n    let a = 1;\n    let b = 2;\n    let c~\n```",
+            "```rust\n    b = 2;\n    let a = 1;\n    let b = 2;\n    let c = 3;\n}\n```",
+        ]);
+        assert_eq!(
+            merged,
+            "```rust\nfn main() {\n    let a = 1;\n    let b = 2;\n    let c = 3;\n}\n```"
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tile_overlap_requires_real_match() {
+        assert_eq!(super::tile_overlap(&["a", "b"], &["c", "d"]), (2, 0));
+        assert_eq!(super::tile_overlap(&["a", ""], &["", "c"]), (2, 0));
+        // A single repeated line only counts right at the tile edges.
+        assert_eq!(
+            super::tile_overlap(&["a", "b", "x"], &["y", "b", "c"]),
+            (3, 0)
+        );
+        assert_eq!(super::tile_overlap(&["a", "b"], &["b", "c"]), (2, 1));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_combine_completions() {
+        use crate::types::{VisionCompletion, VisionUsage};
+        let completion = |id: &str, reason: &str, tokens| VisionCompletion {
+            id: Some(id.to_string()),
+            request_id: None,
+            model: Some("glm-4.6v".to_string()),
+            created: None,
+            content: String::new(),
+            reasoning_content: Some(format!("thought {}", id)),
+            finish_reason: Some(reason.to_string()),
+            usage: Some(VisionUsage {
+                prompt_tokens: tokens,
+                completion_tokens: 1,
+                total_tokens: tokens + 1,
+                ..VisionUsage::default()
+            }),
+        };
+        let combined = super::combine_completions(
+            vec![
+                completion("a", "stop", 10),
+                completion("b", "length", 20),
+                completion("c", "stop", 30),
+            ],
+            "merged".to_string(),
+        );
+        assert_eq!(combined.content, "merged");
+        assert_eq!(combined.id.as_deref(), Some("c"));
+        assert_eq!(combined.finish_reason.as_deref(), Some("length"));
+        assert_eq!(
+            combined.reasoning_content.as_deref(),
+            Some("thought a\n\nthought b\n\nthought c")
+        );
+        let usage = combined.usage.unwrap();
+        assert_eq!((usage.prompt_tokens, usage.total_tokens), (60, 63));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_region_to_canvas() {
+        use crate::grounding::BoundingBox;
+
+        let region = crate::preprocess::TileRegion {
+            row: 1,
+            column: 0,
+            x: 0,
+            y: 1000,
+            width: 500,
+            height: 1000,
+        };
+        let bbox = super::region_to_canvas(
+            BoundingBox::new(0.0, 0.0, 500.0, 500.0),
+            &region,
+            (1000, 4000),
+        );
+        assert_eq!(bbox, BoundingBox::new(0.0, 250.0, 250.0, 375.0));
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_tiles_preamble_lists_regions() {
+        let tiling = crate::ImageTiling::default();
+        let regions = crate::preprocess::tile_layout(1440, 3000, &tiling);
+        let preamble = super::tiles_preamble(&regions);
+        assert!(preamble.contains("The 1440x3000 screenshot is split into 3 overlapping tiles"));
+        assert!(preamble.contains("Tile 1: x 0-1440, y 0-1536"));
+        assert!(preamble.contains("Tile 3: x 0-1440, y 1464-3000"));
+    }
+
     #[test]
     fn test_ui_diff_prompt_enhancement() {
         let prompt = "Compare these two designs";
-        let enhanced = format!(
-            "<images>\nThe first image is the EXPECTED/REFERENCE design (the target).\n\
-             The second image is the ACTUAL/CURRENT implementation (what needs to be checked).\n\
-             </images>\n\n{}",
-            prompt
-        );
+        let enhanced = super::ui_diff_prompt(prompt);
         assert!(enhanced.contains("EXPECTED/REFERENCE"));
         assert!(enhanced.contains("ACTUAL/CURRENT"));
         assert!(enhanced.contains(prompt));
     }
+
+    /// A client answering each request with the next of `contents`.
+    fn scripted_client(contents: &[&str]) -> crate::VisionClient<crate::replay::ReplayHttpClient> {
+        use crate::replay::{Cassette, Interaction, RecordedResponse, ReplayHttpClient};
+
+        let interactions = contents
+            .iter()
+            .map(|content| Interaction {
+                request: None,
+                response: RecordedResponse {
+                    status: 200,
+                    body: serde_json::json!({
+                        "choices": [{"finish_reason": "stop", "message": {"content": content}}],
+                        "usage": {"prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120}
+                    }),
+                },
+            })
+            .collect();
+        let config = crate::VisionConfig::new("test-key").with_base_url("https://example.com/v1");
+        crate::VisionClient::new(config, ReplayHttpClient::new(Cassette { interactions }))
+    }
+
+    const FAKE_PNG: &[u8] = b"\x89PNG\r\n\x1a\nfake-png-data";
+
+    #[cfg(feature = "code")]
+    #[tokio::test]
+    async fn test_extract_code_repairs_invalid_blocks() {
+        let client = scripted_client(&[
+            "```rust\n1 fn main() {\n2     let x = 1 +;\n3 }\n```\n```python\nprint(1\n```",
+            "```rust\nfn main() {\n    let x = 1 + 1;\n}\n```\n```python\nprint(1\n```",
+        ]);
+        let (blocks, completion) =
+            super::extract_code_detailed(&client, crate::MediaSource::bytes(FAKE_PNG), None)
+                .await
+                .unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].code, "fn main() {\n    let x = 1 + 1;\n}");
+        assert_eq!(blocks[0].syntax_error, None);
+        // Python has no local parser, so it is returned as transcribed.
+        assert_eq!(blocks[1].syntax_error, None);
+        assert_eq!(completion.usage.unwrap().total_tokens, 240);
+        assert!(client.http().is_exhausted());
+
+        let client = scripted_client(&["```json\n{\"a\" 1}\n```"; 3]);
+        let blocks = super::extract_code(&client, crate::MediaSource::bytes(FAKE_PNG), None)
+            .await
+            .unwrap();
+        let error = blocks[0].syntax_error.as_ref().unwrap();
+        assert_eq!((error.line, error.column), (1, 6));
+        assert!(client.http().is_exhausted());
+    }
+
+    #[tokio::test]
+    async fn test_extract_logs() {
+        let client = scripted_client(&[r#"{"entries": [
+            {"timestamp": "12:00:01", "level": "error", "message": "bind failed",
+             "raw": "[2024-05-01T12:00:01Z ERROR my_app::http] bind failed",
+             "continuation": ["  caused by: address in use"]},
+            {"level": "info", "message": "retrying", "raw": "[2024-05-01T12:00:02Z INFO  my_app] retrying"}
+        ]}"#]);
+        let entries = super::extract_logs(&client, crate::MediaSource::bytes(FAKE_PNG))
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].timestamp.as_deref(),
+            Some("2024-05-01T12:00:01Z")
+        );
+        assert_eq!(entries[0].target.as_deref(), Some("my_app::http"));
+        assert_eq!(entries[0].continuation, ["  caused by: address in use"]);
+        assert_eq!(entries[1].level, Some(crate::logs::LogLevel::Info));
+    }
+
+    #[tokio::test]
+    async fn test_extract_tables() {
+        let client = scripted_client(&[
+            "{\"tables\": [{\"header_rows\": 1, \"rows\": [[{\"text\": \"User\"}, \
+             {\"text\": \"Status\"}], [{\"text\": \"ana\"}, {\"text\": \"Active\"}]]}]}",
+        ]);
+        let tables = super::extract_tables(&client, crate::MediaSource::bytes(FAKE_PNG))
+            .await
+            .unwrap();
+        assert_eq!(tables.len(), 1);
+        assert_eq!(tables[0].to_csv(), "User,Status\r\nana,Active\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_extract_chart_data() {
+        let client = scripted_client(&[
+            "```json\n{\"kind\": \"line\", \"series\": [{\"name\": \"p95\", \
+             \"points\": [{\"x\": 1, \"y\": 120, \"estimated\": true}]}]}\n```",
+        ]);
+        let chart = super::extract_chart_data(&client, crate::MediaSource::bytes(FAKE_PNG))
+            .await
+            .unwrap();
+        assert_eq!(chart.kind, crate::chart::ChartKind::Line);
+        assert!(chart.is_estimated());
+        assert_eq!(chart.to_csv(), "series,x,y,estimated\r\np95,1,120,true\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_diagram_to_source_repairs_invalid_source() {
+        use crate::diagram::DiagramFormat;
+
+        let client = scripted_client(&[
+            "```mermaid\nflowchart LR\n  A[Client --> B\n```",
+            "Fixed:\n```mermaid\nflowchart LR\n  A[Client] --> B[API]\n```",
+        ]);
+        let (source, completion) = super::diagram_to_source_detailed(
+            &client,
+            crate::MediaSource::bytes(FAKE_PNG),
+            DiagramFormat::Mermaid,
+            Some("flowchart"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(source, "flowchart LR\n  A[Client] --> B[API]");
+        assert_eq!(completion.usage.unwrap().total_tokens, 240);
+        assert!(client.http().is_exhausted());
+
+        let client = scripted_client(&["digraph {", "digraph {", "digraph {"]);
+        let err = super::diagram_to_source(
+            &client,
+            crate::MediaSource::bytes(FAKE_PNG),
+            DiagramFormat::Dot,
+            None,
+        )
+        .await
+        .unwrap_err();
+        match err {
+            crate::VisionError::Parse { message, .. } => {
+                assert_eq!(
+                    message,
+                    "invalid Graphviz DOT source: line 1: `{` is never closed"
+                )
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(client.http().is_exhausted());
+    }
 }
@@ -4,16 +4,22 @@ use serde::{Deserialize, Serialize};
 // Request types
 // ---------------------------------------------------------------------------
 
+/// Chat completions request body.
+///
+/// `messages` is owned by default; the client serializes a borrowed
+/// `&[VisionMessage]` instead, so sending a conversation does not copy it.
 #[derive(Debug, Serialize)]
-pub struct VisionChatRequest {
+pub struct VisionChatRequest<M = Vec<VisionMessage>> {
     pub model: String,
-    pub messages: Vec<VisionMessage>,
+    pub messages: M,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub thinking: Option<ThinkingConfig>,
     pub stream: bool,
     pub temperature: f32,
     pub top_p: f32,
     pub max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
 }
 
 #[derive(Debug, Serialize)]
@@ -22,13 +28,55 @@ pub struct ThinkingConfig {
     pub kind: String,
 }
 
-#[derive(Debug, Serialize)]
+/// Output format requested from the API (`{"type": "json_object"}`).
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseFormat {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+impl ResponseFormat {
+    /// Ask for a single JSON object as the answer.
+    pub fn json_object() -> Self {
+        Self {
+            kind: "json_object".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VisionMessage {
     pub role: String,
     pub content: VisionContent,
 }
 
-#[derive(Debug, Serialize)]
+impl VisionMessage {
+    /// A system message with plain text content.
+    pub fn system(text: impl Into<String>) -> Self {
+        Self {
+            role: "system".to_string(),
+            content: VisionContent::Text(text.into()),
+        }
+    }
+
+    /// A user message made of media and text parts.
+    pub fn user(parts: Vec<ContentPart>) -> Self {
+        Self {
+            role: "user".to_string(),
+            content: VisionContent::Parts(parts),
+        }
+    }
+
+    /// An assistant message with plain text content.
+    pub fn assistant(text: impl Into<String>) -> Self {
+        Self {
+            role: "assistant".to_string(),
+            content: VisionContent::Text(text.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum VisionContent {
     Text(String),
@@ -57,17 +105,167 @@ pub struct UrlHolder {
 
 #[derive(Debug, Deserialize)]
 pub struct VisionChatResponse {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub created: Option<i64>,
     pub choices: Vec<VisionChoice>,
+    #[serde(default)]
+    pub usage: Option<VisionUsage>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct VisionChoice {
+    #[serde(default)]
+    pub index: u32,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
     pub message: VisionChoiceMessage,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct VisionChoiceMessage {
+    #[serde(default)]
+    pub role: Option<String>,
     pub content: Option<String>,
+    #[serde(default)]
+    pub reasoning_content: Option<String>,
+}
+
+/// Token accounting reported by the API for a completion.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VisionUsage {
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    #[serde(default)]
+    pub completion_tokens: u32,
+    #[serde(default)]
+    pub total_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl VisionUsage {
+    /// Prompt tokens served from the provider's cache (0 if not reported).
+    pub fn cached_tokens(&self) -> u32 {
+        self.prompt_tokens_details
+            .as_ref()
+            .map_or(0, |d| d.cached_tokens)
+    }
+
+    /// Completion tokens spent on reasoning (0 if not reported).
+    pub fn reasoning_tokens(&self) -> u32 {
+        self.completion_tokens_details
+            .as_ref()
+            .map_or(0, |d| d.reasoning_tokens)
+    }
+
+    /// Add the token counts of another completion, e.g. one of several calls
+    /// whose answers were merged.
+    pub fn merge(&mut self, other: &VisionUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        if let Some(details) = &other.prompt_tokens_details {
+            self.prompt_tokens_details
+                .get_or_insert_with(Default::default)
+                .cached_tokens += details.cached_tokens;
+        }
+        if let Some(details) = &other.completion_tokens_details {
+            self.completion_tokens_details
+                .get_or_insert_with(Default::default)
+                .reasoning_tokens += details.reasoning_tokens;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionTokensDetails {
+    #[serde(default)]
+    pub reasoning_tokens: u32,
+}
+
+/// A completed vision response with its metadata.
+///
+/// Returned by [`VisionClient::completion_detailed`](crate::VisionClient::completion_detailed)
+/// and the `*_detailed` functions in [`tools`](crate::tools).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisionCompletion {
+    /// Completion ID assigned by the API.
+    pub id: Option<String>,
+    /// Request ID assigned by the API (useful when contacting support).
+    pub request_id: Option<String>,
+    /// Model that produced the response.
+    pub model: Option<String>,
+    /// Unix timestamp (seconds) at which the response was created.
+    pub created: Option<i64>,
+    /// Final answer text.
+    pub content: String,
+    /// Reasoning text, present when thinking mode is enabled.
+    pub reasoning_content: Option<String>,
+    /// Why generation stopped (e.g. "stop", "length", "sensitive").
+    pub finish_reason: Option<String>,
+    /// Token usage, if reported.
+    pub usage: Option<VisionUsage>,
+}
+
+impl VisionCompletion {
+    /// Build a completion from the first choice of a chat response.
+    ///
+    /// Returns `None` if the response has no choices or the first choice has no content.
+    pub fn from_response(response: VisionChatResponse) -> Option<Self> {
+        let choice = response.choices.into_iter().next()?;
+        Some(Self {
+            id: response.id,
+            request_id: response.request_id,
+            model: response.model,
+            created: response.created,
+            content: choice.message.content?,
+            reasoning_content: choice.message.reasoning_content,
+            finish_reason: choice.finish_reason,
+            usage: response.usage,
+        })
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Streaming types
+// ---------------------------------------------------------------------------
+
+/// A single `data:` payload of a streamed (`stream: true`) completion.
+#[derive(Debug, Deserialize)]
+pub struct VisionChatChunk {
+    #[serde(default)]
+    pub choices: Vec<VisionChunkChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VisionChunkChoice {
+    #[serde(default)]
+    pub delta: VisionDelta,
+    #[serde(default)]
+    pub finish_reason: Option<String>,
+}
+
+/// Incremental content of a streamed completion.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct VisionDelta {
+    #[serde(default)]
+    pub content: Option<String>,
+    #[serde(default)]
+    pub reasoning_content: Option<String>,
 }
 
 // ---------------------------------------------------------------------------
@@ -79,6 +277,10 @@ pub fn image_mime_type(ext: &str) -> Option<&'static str> {
     match ext.to_lowercase().as_str() {
         "png" => Some("image/png"),
         "jpg" | "jpeg" => Some("image/jpeg"),
+        "webp" => Some("image/webp"),
+        "gif" => Some("image/gif"),
+        "bmp" => Some("image/bmp"),
+        "tif" | "tiff" => Some("image/tiff"),
         _ => None,
     }
 }
@@ -96,6 +298,158 @@ pub fn video_mime_type(ext: &str) -> Option<&'static str> {
     }
 }
 
+/// Detects the MIME type of image or video data from its file signature.
+///
+/// Recognizes PNG, JPEG, GIF, WebP, BMP, TIFF, HEIC/AVIF, MP4/MOV/M4V (`ftyp`
+/// box), WebM/Matroska (EBML), AVI (RIFF) and WMV (ASF). The result may be a
+/// type the API does not accept; check it with [`is_supported_image`],
+/// [`is_convertible_image`] or [`is_supported_video`].
+pub fn sniff_mime_type(data: &[u8]) -> Option<&'static str> {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const EBML: &[u8] = &[0x1a, 0x45, 0xdf, 0xa3];
+    const ASF: &[u8] = &[0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11];
+
+    if data.starts_with(PNG) {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") {
+        match &data[8..12] {
+            b"WEBP" => Some("image/webp"),
+            b"AVI " => Some("video/x-msvideo"),
+            _ => None,
+        }
+    } else if data.len() >= 26 && data.starts_with(b"BM") {
+        Some("image/bmp")
+    } else if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        Some("image/tiff")
+    } else if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        match &data[8..12] {
+            b"qt  " => Some("video/quicktime"),
+            b"M4V " | b"M4VH" | b"M4VP" => Some("video/x-m4v"),
+            b"avif" | b"avis" => Some("image/avif"),
+            b"heic" | b"heix" | b"mif1" | b"msf1" => Some("image/heic"),
+            _ => Some("video/mp4"),
+        }
+    } else if data.starts_with(EBML) {
+        let header = &data[..data.len().min(64)];
+        if header.windows(8).any(|w| w == b"matroska") {
+            Some("video/x-matroska")
+        } else {
+            Some("video/webm")
+        }
+    } else if data.starts_with(ASF) {
+        Some("video/x-ms-wmv")
+    } else {
+        None
+    }
+}
+
+/// Returns true if the API accepts images of this MIME type as-is.
+pub fn is_supported_image(mime: &str) -> bool {
+    matches!(mime, "image/png" | "image/jpeg")
+}
+
+/// Returns true for image types that are converted to PNG or JPEG locally
+/// before sending (requires the `image` feature).
+pub fn is_convertible_image(mime: &str) -> bool {
+    matches!(
+        mime,
+        "image/webp" | "image/gif" | "image/bmp" | "image/tiff"
+    )
+}
+
+/// Returns true if the API accepts videos of this MIME type.
+pub fn is_supported_video(mime: &str) -> bool {
+    matches!(
+        mime,
+        "video/mp4"
+            | "video/quicktime"
+            | "video/x-m4v"
+            | "video/x-msvideo"
+            | "video/webm"
+            | "video/x-ms-wmv"
+    )
+}
+
+/// MIME types of a media input, detected from its content and declared by its
+/// file extension or caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaType {
+    /// MIME type detected from the file signature, if recognized.
+    pub sniffed: Option<&'static str>,
+    /// MIME type implied by the file extension or declared by the caller, if recognized.
+    pub declared: Option<&'static str>,
+}
+
+impl MediaType {
+    /// Detect the media type of `data` read from `path`.
+    pub fn detect(path: &std::path::Path, data: &[u8]) -> Self {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        Self::with_declared(image_mime_type(ext).or_else(|| video_mime_type(ext)), data)
+    }
+
+    /// Detect the media type of `data` with an explicitly declared MIME type.
+    pub fn with_declared(declared: Option<&'static str>, data: &[u8]) -> Self {
+        Self {
+            sniffed: sniff_mime_type(data),
+            declared,
+        }
+    }
+
+    /// The MIME type to send: the sniffed type, or the declared type when the
+    /// two are compatible (e.g. `.m4v` for an MP4 container).
+    pub fn mime(&self) -> Option<&'static str> {
+        match (self.sniffed, self.declared) {
+            (Some(sniffed), Some(ext)) if same_family(sniffed, ext) => Some(ext),
+            (sniffed, _) => sniffed,
+        }
+    }
+
+    /// Returns true if the declared type names a different format than the content.
+    pub fn is_mismatch(&self) -> bool {
+        match (self.sniffed, self.declared) {
+            (Some(sniffed), Some(ext)) => !same_family(sniffed, ext),
+            _ => false,
+        }
+    }
+}
+
+/// Returns the static MIME string for a known image or video MIME type.
+pub fn known_mime_type(mime: &str) -> Option<&'static str> {
+    const KNOWN: [&str; 15] = [
+        "image/png",
+        "image/jpeg",
+        "image/gif",
+        "image/webp",
+        "image/bmp",
+        "image/tiff",
+        "image/avif",
+        "image/heic",
+        "video/mp4",
+        "video/quicktime",
+        "video/x-m4v",
+        "video/x-msvideo",
+        "video/webm",
+        "video/x-matroska",
+        "video/x-ms-wmv",
+    ];
+    let mime = mime.trim().to_ascii_lowercase();
+    match mime.as_str() {
+        "image/jpg" => Some("image/jpeg"),
+        _ => KNOWN.iter().find(|k| **k == mime).copied(),
+    }
+}
+
+/// MP4, QuickTime and M4V share the ISO base media container, so the `ftyp`
+/// brand alone does not reliably distinguish them.
+fn same_family(a: &str, b: &str) -> bool {
+    const ISO_BMFF: [&str; 3] = ["video/mp4", "video/quicktime", "video/x-m4v"];
+    a == b || (ISO_BMFF.contains(&a) && ISO_BMFF.contains(&b))
+}
+
 /// Returns true if `source` looks like an HTTP(S) URL.
 pub fn is_url(source: &str) -> bool {
     source.starts_with("http://") || source.starts_with("https://")
@@ -162,8 +516,66 @@ mod tests {
         assert_eq!(image_mime_type("jpg"), Some("image/jpeg"));
         assert_eq!(image_mime_type("jpeg"), Some("image/jpeg"));
         assert_eq!(image_mime_type("PNG"), Some("image/png"));
-        assert_eq!(image_mime_type("gif"), None);
-        assert_eq!(image_mime_type("bmp"), None);
+        assert_eq!(image_mime_type("webp"), Some("image/webp"));
+        assert_eq!(image_mime_type("gif"), Some("image/gif"));
+        assert_eq!(image_mime_type("bmp"), Some("image/bmp"));
+        assert_eq!(image_mime_type("TIF"), Some("image/tiff"));
+        assert_eq!(image_mime_type("heic"), None);
+        assert!(is_convertible_image("image/gif"));
+        assert!(!is_supported_image("image/gif"));
+    }
+
+    #[test]
+    fn test_sniff_mime_type() {
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1a\n...."), Some("image/png"));
+        assert_eq!(sniff_mime_type(b"\xff\xd8\xff\xe0JFIF"), Some("image/jpeg"));
+        assert_eq!(sniff_mime_type(b"GIF89a...."), Some("image/gif"));
+        assert_eq!(sniff_mime_type(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(
+            sniff_mime_type(b"RIFF\0\0\0\0AVI LIST"),
+            Some("video/x-msvideo")
+        );
+        assert_eq!(sniff_mime_type(b"\0\0\0\x18ftypisom"), Some("video/mp4"));
+        assert_eq!(
+            sniff_mime_type(b"\0\0\0\x14ftypqt  "),
+            Some("video/quicktime")
+        );
+        assert_eq!(sniff_mime_type(b"\0\0\0\x1cftypavif"), Some("image/avif"));
+        assert_eq!(
+            sniff_mime_type(b"\x1a\x45\xdf\xa3\x9f\x42\x82\x84webm"),
+            Some("video/webm")
+        );
+        assert_eq!(
+            sniff_mime_type(b"\x30\x26\xb2\x75\x8e\x66\xcf\x11\xa6\xd9"),
+            Some("video/x-ms-wmv")
+        );
+        assert_eq!(sniff_mime_type(b"BM"), None);
+        assert_eq!(sniff_mime_type(b"plain text"), None);
+        assert_eq!(sniff_mime_type(b""), None);
+    }
+
+    #[test]
+    fn test_media_type_mismatch() {
+        let jpeg = b"\xff\xd8\xff\xe0JFIF";
+        let media = MediaType::detect(std::path::Path::new("shot.png"), jpeg);
+        assert!(media.is_mismatch());
+        assert_eq!(media.mime(), Some("image/jpeg"));
+
+        let mp4 = b"\0\0\0\x18ftypisom";
+        let media = MediaType::detect(std::path::Path::new("clip.m4v"), mp4);
+        assert!(!media.is_mismatch());
+        assert_eq!(media.mime(), Some("video/x-m4v"));
+
+        let media = MediaType::detect(std::path::Path::new("upload"), jpeg);
+        assert!(!media.is_mismatch());
+        assert_eq!(media.mime(), Some("image/jpeg"));
+    }
+
+    #[test]
+    fn test_known_mime_type() {
+        assert_eq!(known_mime_type("image/PNG"), Some("image/png"));
+        assert_eq!(known_mime_type("image/jpg"), Some("image/jpeg"));
+        assert_eq!(known_mime_type("text/plain"), None);
     }
 
     #[test]
@@ -216,6 +628,7 @@ mod tests {
             temperature: 0.8,
             top_p: 0.6,
             max_tokens: 32768,
+            response_format: None,
         };
         let json = serde_json::to_value(&req).unwrap();
         assert_eq!(json["model"], "glm-4.6v");
@@ -223,5 +636,108 @@ mod tests {
         assert_eq!(json["messages"][1]["content"][0]["type"], "image_url");
         assert_eq!(json["thinking"]["type"], "enabled");
         assert_eq!(json["stream"], false);
+        assert!(json.get("response_format").is_none());
+
+        let req = VisionChatRequest {
+            response_format: Some(ResponseFormat::json_object()),
+            ..req
+        };
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["response_format"]["type"], "json_object");
+    }
+
+    #[test]
+    fn test_recorded_responses_deserialize() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/responses");
+        let mut count = 0;
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let raw = std::fs::read_to_string(&path).unwrap();
+            let response: VisionChatResponse = serde_json::from_str(&raw).unwrap();
+            let completion = VisionCompletion::from_response(response)
+                .unwrap_or_else(|| panic!("no content in {}", path.display()));
+            assert!(!completion.content.is_empty());
+            assert_eq!(completion.finish_reason.as_deref(), Some("stop"));
+            assert!(completion.request_id.is_some());
+            let usage = completion.usage.unwrap();
+            assert_eq!(
+                usage.total_tokens,
+                usage.prompt_tokens + usage.completion_tokens
+            );
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn test_vision_completion_from_response() {
+        let response: VisionChatResponse = serde_json::from_str(
+            r#"{
+                "id": "abc",
+                "request_id": "req-1",
+                "model": "glm-4.6v",
+                "created": 1770983951,
+                "choices": [{
+                    "index": 0,
+                    "finish_reason": "length",
+                    "message": {"role": "assistant", "content": "Hi", "reasoning_content": "Think"}
+                }],
+                "usage": {
+                    "prompt_tokens": 10,
+                    "completion_tokens": 5,
+                    "total_tokens": 15,
+                    "prompt_tokens_details": {"cached_tokens": 4},
+                    "completion_tokens_details": {"reasoning_tokens": 2}
+                }
+            }"#,
+        )
+        .unwrap();
+        let completion = VisionCompletion::from_response(response).unwrap();
+        assert_eq!(completion.id.as_deref(), Some("abc"));
+        assert_eq!(completion.request_id.as_deref(), Some("req-1"));
+        assert_eq!(completion.created, Some(1770983951));
+        assert_eq!(completion.content, "Hi");
+        assert_eq!(completion.reasoning_content.as_deref(), Some("Think"));
+        assert_eq!(completion.finish_reason.as_deref(), Some("length"));
+        let usage = completion.usage.unwrap();
+        assert_eq!(usage.cached_tokens(), 4);
+        assert_eq!(usage.reasoning_tokens(), 2);
+
+        let mut total = VisionUsage {
+            prompt_tokens: 1,
+            total_tokens: 1,
+            ..VisionUsage::default()
+        };
+        total.merge(&usage);
+        assert_eq!((total.prompt_tokens, total.total_tokens), (11, 16));
+        assert_eq!(total.cached_tokens(), 4);
+        assert_eq!(total.reasoning_tokens(), 2);
+    }
+
+    #[test]
+    fn test_vision_completion_missing_content() {
+        let response: VisionChatResponse =
+            serde_json::from_str(r#"{"choices":[{"message":{"content":null}}]}"#).unwrap();
+        assert!(VisionCompletion::from_response(response).is_none());
+        let response: VisionChatResponse = serde_json::from_str(r#"{"choices":[]}"#).unwrap();
+        assert!(VisionCompletion::from_response(response).is_none());
+    }
+
+    #[test]
+    fn test_vision_chat_chunk_deserialization() {
+        let chunk: VisionChatChunk = serde_json::from_str(
+            r#"{"id":"1","choices":[{"index":0,"delta":{"role":"assistant","reasoning_content":"Hmm"}}]}"#,
+        )
+        .unwrap();
+        let choice = &chunk.choices[0];
+        assert_eq!(choice.delta.reasoning_content.as_deref(), Some("Hmm"));
+        assert!(choice.delta.content.is_none());
+        assert!(choice.finish_reason.is_none());
+
+        let last: VisionChatChunk = serde_json::from_str(
+            r#"{"choices":[{"index":0,"finish_reason":"stop","delta":{"content":""}}],"usage":{"total_tokens":3}}"#,
+        )
+        .unwrap();
+        assert_eq!(last.choices[0].finish_reason.as_deref(), Some("stop"));
     }
 }
@@ -0,0 +1,797 @@
+//! Local frame sampling of videos (feature `video`).
+//!
+//! Used by [`VisionClient::process_video_frames`](crate::VisionClient::process_video_frames)
+//! and [`tools::analyze_video_frames`](crate::tools::analyze_video_frames) to send a
+//! long screen recording as a handful of timestamped images instead of one video
+//! that exceeds `max_video_size_mb`.
+//!
+//! MP4/MOV (ISO-BMFF) and Matroska/WebM containers are demuxed locally. H.264 is
+//! decoded with OpenH264; VP8 streams can only be sampled at keyframes, which are
+//! decoded as lossy WebP images. Other codecs (HEVC, VP9, AV1) are rejected.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use image::{ImageFormat, RgbImage};
+use matroska_demuxer::MatroskaFile;
+use openh264::decoder::{DecodedYUV, Decoder, DecoderConfig, Flush};
+use openh264::formats::YUVSource;
+use openh264::OpenH264API;
+
+use crate::error::{Result, VisionError};
+use crate::preprocess::sample_indices;
+
+/// Minimum video time between two scene-change comparisons.
+const SCENE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Edge length of the luma grid compared for scene changes.
+const THUMB_EDGE: usize = 32;
+
+/// Annex B start code prepended to each H.264 NAL unit.
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// How frames are picked from a video.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplingMode {
+    /// One frame at every multiple of the interval.
+    Interval(Duration),
+    /// The first frame, then each frame whose picture differs from the last
+    /// sampled one by more than the threshold (mean absolute luma difference,
+    /// 0.0–1.0).
+    SceneChange(f64),
+}
+
+/// Frame sampling options for [`sample_frames`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoSampling {
+    /// How frames are picked.
+    pub mode: SamplingMode,
+    /// Upper bound on the number of frames; more are thinned evenly (default 16).
+    pub max_frames: usize,
+}
+
+impl Default for VideoSampling {
+    fn default() -> Self {
+        Self::every(Duration::from_secs(5))
+    }
+}
+
+impl VideoSampling {
+    /// Sample one frame every `interval`.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            mode: SamplingMode::Interval(interval),
+            max_frames: 16,
+        }
+    }
+
+    /// Sample a frame at each scene change above `threshold` (0.0–1.0; 0.1 works
+    /// well for screen recordings).
+    pub fn scene_changes(threshold: f64) -> Self {
+        Self {
+            mode: SamplingMode::SceneChange(threshold),
+            max_frames: 16,
+        }
+    }
+
+    /// Set the maximum number of frames.
+    pub fn with_max_frames(mut self, max_frames: usize) -> Self {
+        self.max_frames = max_frames;
+        self
+    }
+}
+
+/// A decoded video frame and its presentation time.
+#[derive(Debug, Clone)]
+pub struct SampledFrame {
+    /// Presentation time from the start of the video.
+    pub timestamp: Duration,
+    /// The decoded picture.
+    pub image: RgbImage,
+}
+
+/// Demux and decode a video of type `mime`, returning the frames selected by
+/// `sampling` in presentation order.
+///
+/// The video is read twice: once to choose timestamps and once to decode the
+/// chosen frames, so at most `max_frames` pictures are held in memory.
+pub fn sample_frames<R: Read + Seek>(
+    mut reader: R,
+    mime: &str,
+    sampling: &VideoSampling,
+) -> Result<Vec<SampledFrame>> {
+    let container = Container::from_mime(mime)?;
+    let mut targets = match sampling.mode {
+        SamplingMode::Interval(interval) => {
+            if interval.is_zero() {
+                return Err(VisionError::InvalidRequest(
+                    "Sampling interval must be greater than zero".to_string(),
+                ));
+            }
+            interval_targets(candidate_timestamps(&mut reader, container)?, interval)
+        }
+        SamplingMode::SceneChange(threshold) => {
+            scene_change_targets(&mut reader, container, threshold)?
+        }
+    };
+    targets = sample_indices(targets.len(), sampling.max_frames)
+        .into_iter()
+        .map(|i| targets[i])
+        .collect();
+
+    let mut frames = Vec::with_capacity(targets.len());
+    let mut next = targets.iter().peekable();
+    decode_frames(&mut reader, container, &mut |timestamp, picture| {
+        let Some(&&target) = next.peek() else {
+            return Ok(false);
+        };
+        if timestamp >= target {
+            frames.push(SampledFrame {
+                timestamp,
+                image: picture.to_rgb()?,
+            });
+            while next.next_if(|&&t| t <= timestamp).is_some() {}
+        }
+        Ok(next.peek().is_some())
+    })?;
+    Ok(frames)
+}
+
+/// Format a timestamp as `mm:ss.s`, or `h:mm:ss.s` from one hour on.
+pub fn format_timestamp(timestamp: Duration) -> String {
+    let tenths = timestamp.as_millis() / 100;
+    let (hours, rest) = (tenths / 36_000, tenths % 36_000);
+    let (minutes, rest) = (rest / 600, rest % 600);
+    let (seconds, tenth) = (rest / 10, rest % 10);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}.{}", hours, minutes, seconds, tenth)
+    } else {
+        format!("{:02}:{:02}.{}", minutes, seconds, tenth)
+    }
+}
+
+/// Pick the first candidate at or after each multiple of `interval`.
+fn interval_targets(mut candidates: Vec<Duration>, interval: Duration) -> Vec<Duration> {
+    candidates.sort();
+    let mut targets = Vec::new();
+    let mut tick = Duration::ZERO;
+    for timestamp in candidates {
+        if timestamp >= tick {
+            targets.push(timestamp);
+            while tick <= timestamp {
+                tick += interval;
+            }
+        }
+    }
+    targets
+}
+
+/// Decode the video and collect the timestamps of scene changes.
+fn scene_change_targets<R: Read + Seek>(
+    reader: &mut R,
+    container: Container,
+    threshold: f64,
+) -> Result<Vec<Duration>> {
+    let mut targets = Vec::new();
+    let mut last: Option<(Duration, Vec<u8>)> = None;
+    decode_frames(reader, container, &mut |timestamp, picture| {
+        if let Some((checked, thumb)) = &mut last {
+            if timestamp < *checked + SCENE_CHECK_INTERVAL {
+                return Ok(true);
+            }
+            *checked = timestamp;
+            let current = picture.luma_grid();
+            if luma_difference(thumb, &current) > threshold {
+                targets.push(timestamp);
+                *thumb = current;
+            }
+        } else {
+            targets.push(timestamp);
+            last = Some((timestamp, picture.luma_grid()));
+        }
+        Ok(true)
+    })?;
+    Ok(targets)
+}
+
+/// Mean absolute difference of two luma grids, from 0.0 to 1.0.
+fn luma_difference(a: &[u8], b: &[u8]) -> f64 {
+    let total: u64 = a.iter().zip(b).map(|(&x, &y)| x.abs_diff(y) as u64).sum();
+    total as f64 / (a.len().max(1) as f64 * 255.0)
+}
+
+/// Timestamps of every frame that can be decoded, without decoding anything.
+fn candidate_timestamps<R: Read + Seek>(
+    reader: &mut R,
+    container: Container,
+) -> Result<Vec<Duration>> {
+    let (codec, mut demuxer) = open(reader, container)?;
+    let mut timestamps = Vec::new();
+    while let Some(packet) = demuxer.next_packet()? {
+        if codec.is_decodable(&packet) {
+            timestamps.push(packet.timestamp);
+        }
+    }
+    Ok(timestamps)
+}
+
+/// Decode the video from the start, calling `visit` with each picture until it
+/// returns `false`.
+fn decode_frames<R: Read + Seek>(
+    reader: &mut R,
+    container: Container,
+    visit: &mut dyn FnMut(Duration, &Picture<'_>) -> Result<bool>,
+) -> Result<()> {
+    reader
+        .seek(SeekFrom::Start(0))
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to read video: {}", e)))?;
+    let (codec, mut demuxer) = open(reader, container)?;
+    let mut decoder = FrameDecoder::new(&codec)?;
+    let mut decoded = 0;
+    let mut visit = |timestamp: Duration, picture: &Picture<'_>| {
+        decoded += 1;
+        visit(timestamp, picture)
+    };
+    while let Some(packet) = demuxer.next_packet()? {
+        if !decoder.decode(packet, &mut visit)? {
+            return Ok(());
+        }
+    }
+    decoder.finish(&mut visit)?;
+    if decoded == 0 {
+        return Err(VisionError::InvalidMedia(
+            "Failed to decode any video frames".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    Mp4,
+    Matroska,
+}
+
+impl Container {
+    fn from_mime(mime: &str) -> Result<Self> {
+        match mime {
+            "video/mp4" | "video/quicktime" | "video/x-m4v" => Ok(Container::Mp4),
+            "video/webm" | "video/x-matroska" => Ok(Container::Matroska),
+            _ => Err(VisionError::InvalidMedia(format!(
+                "Frame sampling supports MP4, MOV, MKV and WebM videos, not {}",
+                mime
+            ))),
+        }
+    }
+}
+
+/// A compressed frame in presentation time.
+struct Packet {
+    timestamp: Duration,
+    data: Vec<u8>,
+}
+
+enum Codec {
+    /// H.264 with length-prefixed NAL units and Annex B parameter sets.
+    H264 {
+        length_size: usize,
+        parameter_sets: Vec<u8>,
+    },
+    Vp8,
+}
+
+impl Codec {
+    fn is_decodable(&self, packet: &Packet) -> bool {
+        match self {
+            Codec::H264 { .. } => true,
+            Codec::Vp8 => is_vp8_keyframe(&packet.data),
+        }
+    }
+}
+
+trait Demuxer {
+    fn next_packet(&mut self) -> Result<Option<Packet>>;
+}
+
+fn open<'r, R: Read + Seek>(
+    reader: &'r mut R,
+    container: Container,
+) -> Result<(Codec, Box<dyn Demuxer + 'r>)> {
+    match container {
+        Container::Mp4 => open_mp4(reader),
+        Container::Matroska => open_matroska(reader),
+    }
+}
+
+fn unsupported_codec(codec: &str) -> VisionError {
+    VisionError::InvalidMedia(format!(
+        "Frame sampling supports H.264 and VP8 video, not {}",
+        codec
+    ))
+}
+
+struct Mp4Demuxer<R> {
+    reader: mp4::Mp4Reader<R>,
+    track_id: u32,
+    timescale: u32,
+    next_sample: u32,
+}
+
+fn open_mp4<'r, R: Read + Seek>(reader: &'r mut R) -> Result<(Codec, Box<dyn Demuxer + 'r>)> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        VisionError::InvalidMedia(format!("Failed to read MP4 video: {}", e))
+    };
+    let size = reader.seek(SeekFrom::End(0)).map_err(|e| invalid(&e))?;
+    reader.seek(SeekFrom::Start(0)).map_err(|e| invalid(&e))?;
+    let mp4 = mp4::Mp4Reader::read_header(reader, size).map_err(|e| invalid(&e))?;
+
+    let track = mp4
+        .tracks()
+        .values()
+        .filter(|t| matches!(t.track_type(), Ok(mp4::TrackType::Video)))
+        .min_by_key(|t| t.track_id())
+        .ok_or_else(|| invalid(&"no video track"))?;
+    let codec = match &track.trak.mdia.minf.stbl.stsd.avc1 {
+        Some(avc1) => {
+            let avcc = &avc1.avcc;
+            let sets = avcc
+                .sequence_parameter_sets
+                .iter()
+                .chain(&avcc.picture_parameter_sets);
+            Codec::H264 {
+                length_size: (avcc.length_size_minus_one & 3) as usize + 1,
+                parameter_sets: annex_b(sets.map(|nal| nal.bytes.as_slice())),
+            }
+        }
+        None => {
+            let name = track
+                .box_type()
+                .map(|t| t.to_string())
+                .unwrap_or_else(|_| "an unknown codec".to_string());
+            return Err(unsupported_codec(&name));
+        }
+    };
+
+    let (track_id, timescale) = (track.track_id(), track.timescale().max(1));
+    Ok((
+        codec,
+        Box::new(Mp4Demuxer {
+            reader: mp4,
+            track_id,
+            timescale,
+            next_sample: 1,
+        }),
+    ))
+}
+
+impl<R: Read + Seek> Demuxer for Mp4Demuxer<R> {
+    fn next_packet(&mut self) -> Result<Option<Packet>> {
+        let sample = self
+            .reader
+            .read_sample(self.track_id, self.next_sample)
+            .map_err(|e| VisionError::InvalidMedia(format!("Failed to read MP4 video: {}", e)))?;
+        self.next_sample += 1;
+        Ok(sample.map(|sample| {
+            let ticks = (sample.start_time as i64 + sample.rendering_offset as i64).max(0) as u128;
+            let nanos = ticks * 1_000_000_000 / self.timescale as u128;
+            Packet {
+                timestamp: Duration::from_nanos(nanos as u64),
+                data: sample.bytes.to_vec(),
+            }
+        }))
+    }
+}
+
+struct MatroskaDemuxer<R: Read + Seek> {
+    file: MatroskaFile<R>,
+    track: u64,
+    timestamp_scale: u64,
+    frame: matroska_demuxer::Frame,
+}
+
+fn open_matroska<'r, R: Read + Seek>(reader: &'r mut R) -> Result<(Codec, Box<dyn Demuxer + 'r>)> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        VisionError::InvalidMedia(format!("Failed to read Matroska/WebM video: {}", e))
+    };
+    let file = MatroskaFile::open(reader).map_err(|e| invalid(&e))?;
+    let track = file
+        .tracks()
+        .iter()
+        .find(|t| t.track_type() == matroska_demuxer::TrackType::Video)
+        .ok_or_else(|| invalid(&"no video track"))?;
+    let codec = match track.codec_id() {
+        "V_MPEG4/ISO/AVC" => track
+            .codec_private()
+            .and_then(parse_avcc)
+            .ok_or_else(|| invalid(&"missing H.264 decoder configuration"))?,
+        "V_VP8" => Codec::Vp8,
+        other => return Err(unsupported_codec(other)),
+    };
+
+    let track = track.track_number().get();
+    let timestamp_scale = file.info().timestamp_scale().get();
+    Ok((
+        codec,
+        Box::new(MatroskaDemuxer {
+            file,
+            track,
+            timestamp_scale,
+            frame: Default::default(),
+        }),
+    ))
+}
+
+impl<R: Read + Seek> Demuxer for MatroskaDemuxer<R> {
+    fn next_packet(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let more = self.file.next_frame(&mut self.frame).map_err(|e| {
+                VisionError::InvalidMedia(format!("Failed to read Matroska/WebM video: {}", e))
+            })?;
+            if !more {
+                return Ok(None);
+            }
+            if self.frame.track == self.track {
+                return Ok(Some(Packet {
+                    timestamp: Duration::from_nanos(
+                        self.frame.timestamp.saturating_mul(self.timestamp_scale),
+                    ),
+                    data: std::mem::take(&mut self.frame.data),
+                }));
+            }
+        }
+    }
+}
+
+/// Parse an `AVCDecoderConfigurationRecord` (`avcC`).
+fn parse_avcc(record: &[u8]) -> Option<Codec> {
+    let length_size = (*record.get(4)? & 3) as usize + 1;
+    let mut rest = record.get(5..)?;
+    let mut sets = Vec::new();
+    // SPS count is in the low five bits; the PPS count is a full byte.
+    for mask in [0x1f, 0xff] {
+        let (&count, tail) = rest.split_first()?;
+        rest = tail;
+        for _ in 0..(count & mask) {
+            let len = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) as usize;
+            sets.push(rest.get(2..2 + len)?);
+            rest = &rest[2 + len..];
+        }
+    }
+    Some(Codec::H264 {
+        length_size,
+        parameter_sets: annex_b(sets.into_iter()),
+    })
+}
+
+fn annex_b<'a>(nals: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut out = Vec::new();
+    for nal in nals {
+        out.extend_from_slice(&START_CODE);
+        out.extend_from_slice(nal);
+    }
+    out
+}
+
+fn is_vp8_keyframe(data: &[u8]) -> bool {
+    data.first().is_some_and(|tag| tag & 1 == 0)
+}
+
+/// A decoded picture, before conversion to RGB.
+enum Picture<'a> {
+    Yuv(&'a DecodedYUV<'a>),
+    Rgb(RgbImage),
+}
+
+impl Picture<'_> {
+    fn to_rgb(&self) -> Result<RgbImage> {
+        match self {
+            Picture::Yuv(yuv) => {
+                let (width, height) = yuv.dimensions();
+                let mut buf = vec![0; width * height * 3];
+                yuv.write_rgb8(&mut buf);
+                RgbImage::from_raw(width as u32, height as u32, buf).ok_or_else(|| {
+                    VisionError::InvalidMedia("Decoded frame has invalid dimensions".to_string())
+                })
+            }
+            Picture::Rgb(image) => Ok(image.clone()),
+        }
+    }
+
+    /// Luma sampled on a `THUMB_EDGE` × `THUMB_EDGE` grid.
+    fn luma_grid(&self) -> Vec<u8> {
+        let (width, height) = match self {
+            Picture::Yuv(yuv) => yuv.dimensions(),
+            Picture::Rgb(image) => (image.width() as usize, image.height() as usize),
+        };
+        let mut grid = Vec::with_capacity(THUMB_EDGE * THUMB_EDGE);
+        for gy in 0..THUMB_EDGE {
+            let y = (2 * gy + 1) * height / (2 * THUMB_EDGE);
+            for gx in 0..THUMB_EDGE {
+                let x = (2 * gx + 1) * width / (2 * THUMB_EDGE);
+                grid.push(match self {
+                    Picture::Yuv(yuv) => yuv.y()[y * yuv.strides().0 + x],
+                    Picture::Rgb(image) => {
+                        let [r, g, b] = image.get_pixel(x as u32, y as u32).0;
+                        ((299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000) as u8
+                    }
+                });
+            }
+        }
+        grid
+    }
+}
+
+enum FrameDecoder {
+    H264 {
+        decoder: Box<Decoder>,
+        length_size: usize,
+        /// Presentation times of frames fed to the decoder but not yet output.
+        pending: BinaryHeap<Reverse<Duration>>,
+        buf: Vec<u8>,
+    },
+    Vp8,
+}
+
+impl FrameDecoder {
+    fn new(codec: &Codec) -> Result<Self> {
+        match codec {
+            Codec::H264 {
+                length_size,
+                parameter_sets,
+            } => {
+                let config = DecoderConfig::new().flush_after_decode(Flush::NoFlush);
+                let mut decoder = Decoder::with_api_config(OpenH264API::from_source(), config)
+                    .map_err(|e| {
+                        VisionError::InvalidMedia(format!("Failed to start H.264 decoder: {}", e))
+                    })?;
+                // Parameter sets only configure the decoder; a failure shows up
+                // as undecodable frames below.
+                let _ = decoder.decode(parameter_sets);
+                Ok(FrameDecoder::H264 {
+                    decoder: Box::new(decoder),
+                    length_size: *length_size,
+                    pending: BinaryHeap::new(),
+                    buf: Vec::new(),
+                })
+            }
+            Codec::Vp8 => Ok(FrameDecoder::Vp8),
+        }
+    }
+
+    /// Decode one packet, visiting any picture it completes. Packets that fail
+    /// to decode are skipped. Returns `false` once `visit` does.
+    fn decode(
+        &mut self,
+        packet: Packet,
+        visit: &mut dyn FnMut(Duration, &Picture<'_>) -> Result<bool>,
+    ) -> Result<bool> {
+        match self {
+            FrameDecoder::H264 {
+                decoder,
+                length_size,
+                pending,
+                buf,
+            } => {
+                buf.clear();
+                let mut data = packet.data.as_slice();
+                while data.len() > *length_size {
+                    let (len, rest) = data.split_at(*length_size);
+                    let len = len.iter().fold(0usize, |n, &b| n << 8 | b as usize);
+                    let nal = &rest[..len.min(rest.len())];
+                    buf.extend_from_slice(&START_CODE);
+                    buf.extend_from_slice(nal);
+                    data = &rest[nal.len()..];
+                }
+                match decoder.decode(buf) {
+                    Ok(picture) => {
+                        pending.push(Reverse(packet.timestamp));
+                        match picture {
+                            Some(yuv) => {
+                                let Reverse(timestamp) = pending.pop().unwrap_or_default();
+                                visit(timestamp, &Picture::Yuv(&yuv))
+                            }
+                            None => Ok(true),
+                        }
+                    }
+                    Err(_) => Ok(true),
+                }
+            }
+            FrameDecoder::Vp8 => {
+                if !is_vp8_keyframe(&packet.data) {
+                    return Ok(true);
+                }
+                match decode_vp8_keyframe(&packet.data) {
+                    Ok(image) => visit(packet.timestamp, &Picture::Rgb(image)),
+                    Err(_) => Ok(true),
+                }
+            }
+        }
+    }
+
+    /// Visit pictures still buffered in the decoder.
+    fn finish(
+        &mut self,
+        visit: &mut dyn FnMut(Duration, &Picture<'_>) -> Result<bool>,
+    ) -> Result<()> {
+        if let FrameDecoder::H264 {
+            decoder, pending, ..
+        } = self
+        {
+            for yuv in decoder.flush_remaining().unwrap_or_default() {
+                let Reverse(timestamp) = pending.pop().unwrap_or_default();
+                if !visit(timestamp, &Picture::Yuv(&yuv))? {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode a VP8 keyframe by wrapping it in a lossy WebP container.
+fn decode_vp8_keyframe(frame: &[u8]) -> Result<RgbImage> {
+    let padded = frame.len() + frame.len() % 2;
+    let mut webp = Vec::with_capacity(20 + padded);
+    webp.extend_from_slice(b"RIFF");
+    webp.extend_from_slice(&((12 + padded) as u32).to_le_bytes());
+    webp.extend_from_slice(b"WEBPVP8 ");
+    webp.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+    webp.extend_from_slice(frame);
+    webp.resize(20 + padded, 0);
+    image::load_from_memory_with_format(&webp, ImageFormat::WebP)
+        .map(|image| image.to_rgb8())
+        .map_err(|e| VisionError::InvalidMedia(format!("Failed to decode VP8 frame: {}", e)))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use openh264::encoder::Encoder;
+    use openh264::formats::{RgbSliceU8, YUVBuffer};
+
+    /// Encode an H.264 MP4 of `frames` 128×96 pictures, `step` apart, where
+    /// `shade(i)` is the grey level of frame `i`.
+    pub(crate) fn synthetic_mp4(frames: u32, step: Duration, shade: impl Fn(u32) -> u8) -> Vec<u8> {
+        let (width, height) = (128usize, 96usize);
+        let mut encoder = Encoder::new().unwrap();
+        let mut samples = Vec::new();
+        let (mut sps, mut pps) = (Vec::new(), Vec::new());
+        for i in 0..frames {
+            let rgb = vec![shade(i); width * height * 3];
+            let yuv = YUVBuffer::from_rgb_source(RgbSliceU8::new(&rgb, (width, height)));
+            let stream = encoder.encode(&yuv).unwrap().to_vec();
+            let mut sample = Vec::new();
+            let mut sync = false;
+            for nal in openh264::nal_units(&stream) {
+                let nal = nal.strip_prefix(&[0, 0, 0, 1][..]).unwrap_or(&nal[3..]);
+                match nal[0] & 0x1f {
+                    7 => sps = nal.to_vec(),
+                    8 => pps = nal.to_vec(),
+                    kind => {
+                        sync |= kind == 5;
+                        sample.extend_from_slice(&(nal.len() as u32).to_be_bytes());
+                        sample.extend_from_slice(nal);
+                    }
+                }
+            }
+            samples.push((sample, sync));
+        }
+
+        let config = mp4::Mp4Config {
+            major_brand: "isom".parse().unwrap(),
+            minor_version: 512,
+            compatible_brands: vec!["isom".parse().unwrap(), "avc1".parse().unwrap()],
+            timescale: 1000,
+        };
+        let mut writer =
+            mp4::Mp4Writer::write_start(std::io::Cursor::new(Vec::new()), &config).unwrap();
+        writer
+            .add_track(&mp4::TrackConfig {
+                track_type: mp4::TrackType::Video,
+                timescale: 1000,
+                language: "und".to_string(),
+                media_conf: mp4::MediaConfig::AvcConfig(mp4::AvcConfig {
+                    width: width as u16,
+                    height: height as u16,
+                    seq_param_set: sps,
+                    pic_param_set: pps,
+                }),
+            })
+            .unwrap();
+        let step = step.as_millis() as u64;
+        for (i, (sample, is_sync)) in samples.into_iter().enumerate() {
+            writer
+                .write_sample(
+                    1,
+                    &mp4::Mp4Sample {
+                        start_time: i as u64 * step,
+                        duration: step as u32,
+                        rendering_offset: 0,
+                        is_sync,
+                        bytes: bytes::Bytes::from(sample),
+                    },
+                )
+                .unwrap();
+        }
+        writer.write_end().unwrap();
+        writer.into_writer().into_inner()
+    }
+
+    /// Ten seconds at 2 fps: black for five seconds, then white.
+    fn cut_at_five_seconds() -> Vec<u8> {
+        synthetic_mp4(
+            20,
+            Duration::from_millis(500),
+            |i| if i < 10 { 0 } else { 255 },
+        )
+    }
+
+    fn timestamps(frames: &[SampledFrame]) -> Vec<u64> {
+        frames
+            .iter()
+            .map(|f| f.timestamp.as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_frames_at_interval() {
+        let video = cut_at_five_seconds();
+        let sampling = VideoSampling::every(Duration::from_secs(2));
+        let frames = sample_frames(std::io::Cursor::new(video), "video/mp4", &sampling).unwrap();
+        assert_eq!(timestamps(&frames), vec![0, 2000, 4000, 6000, 8000]);
+        assert_eq!(frames[0].image.dimensions(), (128, 96));
+        assert!(frames[0].image.get_pixel(64, 48).0[0] < 32);
+        assert!(frames[4].image.get_pixel(64, 48).0[0] > 224);
+    }
+
+    #[test]
+    fn test_sample_frames_at_scene_changes() {
+        let video = cut_at_five_seconds();
+        let sampling = VideoSampling::scene_changes(0.3);
+        let frames = sample_frames(std::io::Cursor::new(video), "video/mp4", &sampling).unwrap();
+        assert_eq!(timestamps(&frames), vec![0, 5000]);
+    }
+
+    #[test]
+    fn test_sample_frames_thins_to_max_frames() {
+        let video = cut_at_five_seconds();
+        let sampling = VideoSampling::every(Duration::from_millis(500)).with_max_frames(3);
+        let frames = sample_frames(std::io::Cursor::new(video), "video/mp4", &sampling).unwrap();
+        assert_eq!(timestamps(&frames), vec![0, 4500, 9500]);
+    }
+
+    #[test]
+    fn test_sample_frames_rejects_other_containers() {
+        let err = sample_frames(
+            std::io::Cursor::new(Vec::new()),
+            "video/x-msvideo",
+            &VideoSampling::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("MP4, MOV, MKV and WebM"));
+    }
+
+    #[test]
+    fn test_parse_avcc() {
+        let record = [
+            1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 2, 0x67, 0xaa, 1, 0, 1, 0x68,
+        ];
+        let Some(Codec::H264 {
+            length_size,
+            parameter_sets,
+        }) = parse_avcc(&record)
+        else {
+            panic!("expected H.264 configuration");
+        };
+        assert_eq!(length_size, 4);
+        assert_eq!(parameter_sets, [0, 0, 0, 1, 0x67, 0xaa, 0, 0, 0, 1, 0x68]);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(Duration::from_millis(83_450)), "01:23.4");
+        assert_eq!(format_timestamp(Duration::from_secs(3725)), "1:02:05.0");
+    }
+}